@@ -0,0 +1,266 @@
+//! Building graphs from Rust, without going through the language.
+//!
+//! ```
+//! use cobble::builder::GraphBuilder;
+//! use cobble::nodes::Operation;
+//!
+//! let g = GraphBuilder::new();
+//! let a = g.voronoi(50).invert();
+//! let out = g.math(Operation::Multiply, a, g.voronoi(20));
+//! g.output("albedo", out);
+//!
+//! let document = g.finish();
+//! let graph = document.graph().unwrap();
+//! assert_eq!(4, graph.len());
+//! ```
+//!
+//! Graphs are built as [`Document`]s, which resolve to the same graphs as the equivalent scripts, and render the same
+//! way. A builder can also start from a document saved from a script, and refer to the script's definitions.
+
+use std::cell::RefCell;
+
+use crate::compiler::document::{Argument, Document, DocumentNode, DocumentOutput};
+use crate::nodes::Operation;
+use crate::Metric;
+
+#[derive(Debug, Default)]
+pub struct GraphBuilder {
+    document: RefCell<Document>,
+}
+
+/// A node added to a builder, which can be given as an input to further nodes.
+#[derive(Clone, Copy, Debug)]
+pub struct Handle<'g> {
+    builder: &'g GraphBuilder,
+    id: u64,
+}
+
+impl GraphBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start from the nodes, definitions and results of an existing document.
+    pub fn from_document(document: Document) -> Self {
+        Self {
+            document: RefCell::new(document),
+        }
+    }
+
+    /// Get the document built so far.
+    pub fn finish(self) -> Document {
+        self.document.into_inner()
+    }
+
+    /// Add any node, built-in or not. Unknown nodes and wrong inputs are reported when the document is resolved, and
+    /// bad arguments when it is rendered.
+    pub fn node(
+        &self,
+        name: &str,
+        positional: Vec<Argument>,
+        named: Vec<(&str, Argument)>,
+        inputs: &[Handle],
+    ) -> Handle<'_> {
+        let mut document = self.document.borrow_mut();
+
+        let id = document
+            .nodes
+            .iter()
+            .map(|node| node.id + 1)
+            .max()
+            .unwrap_or_default();
+
+        let mut named: Vec<_> = named
+            .into_iter()
+            .map(|(name, arg)| (name.to_string(), arg))
+            .collect();
+        named.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        document.nodes.push(DocumentNode {
+            id,
+            name: name.into(),
+            positional,
+            named,
+            inputs: inputs.iter().map(|input| input.id).collect(),
+            metadata: None,
+        });
+
+        Handle { builder: self, id }
+    }
+
+    /// Get the node a definition produces, if there is a definition of that name producing a single node.
+    pub fn definition(&self, name: &str) -> Option<Handle<'_>> {
+        match self.document.borrow().definitions.get(name)?.as_slice() {
+            [id] => Some(Handle {
+                builder: self,
+                id: *id,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Define a name for a node, replacing any earlier definition of that name.
+    pub fn define(&self, name: &str, node: Handle) {
+        self.document
+            .borrow_mut()
+            .definitions
+            .insert(name.into(), vec![node.id]);
+    }
+
+    /// Mark a node as a result, under the given name.
+    pub fn output(&self, name: &str, node: Handle) {
+        self.document.borrow_mut().outputs.push(DocumentOutput {
+            name: Some(name.into()),
+            node: node.id,
+        });
+    }
+
+    pub fn voronoi(&self, points: usize) -> Handle<'_> {
+        self.node("voronoi", vec![number(points as f64)], vec![], &[])
+    }
+
+    pub fn voronoi_with_metric(&self, points: usize, metric: Metric) -> Handle<'_> {
+        let metric = match metric {
+            Metric::Euclidian => "Euclidian",
+            Metric::EuclidianSquared => "EuclidianSquared",
+            Metric::Manhattan => "Manhattan",
+            Metric::Chebyshev => "Chebyshev",
+        };
+
+        self.node(
+            "voronoi",
+            vec![number(points as f64), symbol(metric)],
+            vec![],
+            &[],
+        )
+    }
+
+    pub fn cells(&self, points: usize) -> Handle<'_> {
+        self.node("cells", vec![number(points as f64)], vec![], &[])
+    }
+
+    pub fn simplex(&self, scale: f64) -> Handle<'_> {
+        self.node("simplex", vec![number(scale)], vec![], &[])
+    }
+
+    pub fn random(&self) -> Handle<'_> {
+        self.node("random", vec![], vec![], &[])
+    }
+
+    pub fn math<'g>(&'g self, op: Operation, a: Handle<'g>, b: Handle<'g>) -> Handle<'g> {
+        let op = match op {
+            Operation::Add => "Add",
+            Operation::Multiply => "Multiply",
+            Operation::Subtract => "Subtract",
+            Operation::Divide => "Divide",
+        };
+
+        self.node("math", vec![symbol(op)], vec![], &[a, b])
+    }
+
+    pub fn mix<'g>(&'g self, factor: f64, a: Handle<'g>, b: Handle<'g>) -> Handle<'g> {
+        self.node("mix", vec![number(factor)], vec![], &[a, b])
+    }
+
+    pub fn geomix<'g>(&'g self, factor: f64, a: Handle<'g>, b: Handle<'g>) -> Handle<'g> {
+        self.node("geomix", vec![number(factor)], vec![], &[a, b])
+    }
+
+    pub fn dot<'g>(&'g self, a: Handle<'g>, b: Handle<'g>) -> Handle<'g> {
+        self.node("dot", vec![], vec![], &[a, b])
+    }
+
+    pub fn dot3<'g>(&'g self, a: Handle<'g>, b: Handle<'g>) -> Handle<'g> {
+        self.node("dot3", vec![], vec![], &[a, b])
+    }
+}
+
+impl<'g> Handle<'g> {
+    pub fn invert(self) -> Handle<'g> {
+        self.builder.node("invert", vec![], vec![], &[self])
+    }
+
+    pub fn grayscale(self) -> Handle<'g> {
+        self.builder.node("grayscale", vec![], vec![], &[self])
+    }
+
+    pub fn broadcast(self) -> Handle<'g> {
+        self.builder.node("broadcast", vec![], vec![], &[self])
+    }
+
+    pub fn gradient(self) -> Handle<'g> {
+        self.builder.node("gradient", vec![], vec![], &[self])
+    }
+
+    pub fn brighten(self, factor: f64) -> Handle<'g> {
+        self.builder
+            .node("brighten", vec![number(factor)], vec![], &[self])
+    }
+}
+
+fn number(num: f64) -> Argument {
+    Argument::Number(num.to_string())
+}
+
+fn symbol(sym: &str) -> Argument {
+    Argument::Symbol(sym.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::GraphBuilder;
+    use crate::compiler::document::Document;
+    use crate::compiler::source::Sources;
+    use crate::compiler::{compile, Errors};
+    use crate::nodes::Operation;
+    use crate::render::Renderer;
+    use crate::Config;
+
+    #[test]
+    fn builders_match_scripts() {
+        let mut sources = Sources::new();
+        let id =
+            sources.add("(voronoi(50) -> invert, voronoi(20)) -> math(Multiply) -> return".into());
+
+        let mut errors = Errors::new();
+        let script = compile(&sources, id, &mut errors);
+        assert!(errors.is_empty());
+
+        let g = GraphBuilder::new();
+        let a = g.voronoi(50).invert();
+        let out = g.math(Operation::Multiply, a, g.voronoi(20));
+        g.output("albedo", out);
+
+        let document = g.finish();
+        let built = document.graph().unwrap();
+
+        assert_eq!(Some("albedo"), built.output_name(0));
+        assert_eq!(
+            script.hashes()[&script.outputs()[0]],
+            built.hashes()[&built.outputs()[0]]
+        );
+    }
+
+    #[test]
+    fn builders_extend_scripts() {
+        let mut sources = Sources::new();
+        let id = sources.add("a = voronoi(5) -> invert".into());
+
+        let mut errors = Errors::new();
+        let script = compile(&sources, id, &mut errors);
+
+        let g = GraphBuilder::from_document(Document::from_graph(&script));
+        let a = g.definition("a").unwrap();
+        g.output("out", g.mix(0.25, a, g.simplex(2.0).brighten(2.0)));
+
+        let document = g.finish();
+        let graph = document.graph().unwrap();
+
+        // The inverted distances are a scalar, which is broadcast to mix with the colour.
+        assert_eq!(6, graph.len());
+
+        let mut renderer = Renderer::new(Config::new(8, 8).with_seed(0));
+        let textures = renderer.render(&graph, graph.outputs()).unwrap();
+        assert_eq!(8, textures[&graph.outputs()[0]].width());
+    }
+}
@@ -0,0 +1,614 @@
+//! A JSON document which graphs can be saved to and loaded from, without going through the language.
+//!
+//! ```json
+//! {
+//!   "format": "cobble",
+//!   "version": 1,
+//!   "nodes": [
+//!     { "id": 0, "name": "voronoi", "args": [20], "named": { "metric": "Manhattan" } },
+//!     { "id": 1, "name": "invert", "inputs": [0], "metadata": { "position": [120, 40] } }
+//!   ],
+//!   "definitions": { "a": [1] },
+//!   "outputs": [{ "name": "albedo", "node": 1 }]
+//! }
+//! ```
+//!
+//! Numeric arguments are JSON numbers, symbolic arguments are strings, and string arguments are objects like
+//! `{ "string": "moss.png" }`. Node ids only need to be unique within a
+//! document, and nodes may be listed in any order. Metadata, on the document or on any node, is kept but not read.
+//!
+//! The kinds of texture nodes are given are checked as they are in scripts, and inputs which need converting are
+//! converted by nodes which are added to the graph but not to the document.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use serde_json::{json, Map, Value as Json};
+
+use super::graph::{Graph, Node, NodeId};
+use super::token::{escape, unescape};
+use super::Value;
+use crate::nodes::{builtin, Mismatch, TextureKind};
+
+/// The version of the format written by [`Document::save`], and the only version which can be loaded.
+pub const VERSION: u64 = 1;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document {
+    pub nodes: Vec<DocumentNode>,
+
+    /// The nodes produced by each definition, by name.
+    pub definitions: BTreeMap<String, Vec<u64>>,
+
+    /// The results of the graph, in order.
+    pub outputs: Vec<DocumentOutput>,
+
+    pub metadata: Option<Json>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DocumentNode {
+    pub id: u64,
+    pub name: String,
+    pub positional: Vec<Argument>,
+
+    /// Named arguments, sorted by name.
+    pub named: Vec<(String, Argument)>,
+
+    /// The ids of the node's inputs, in the order the node takes them.
+    pub inputs: Vec<u64>,
+
+    /// Anything an editor keeps about the node, such as its position.
+    pub metadata: Option<Json>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DocumentOutput {
+    pub name: Option<String>,
+    pub node: u64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Argument {
+    Number(String),
+    Symbol(String),
+
+    /// The contents of a string literal, escaped as in a script.
+    String(String),
+}
+
+#[derive(Debug)]
+pub enum DocumentError {
+    Json(serde_json::Error),
+    Version(u64),
+
+    /// A part of the document, named by its path, does not have the expected shape.
+    Malformed(String),
+
+    DuplicateId(u64),
+    MissingNode(u64),
+    UnknownNode(String),
+    WrongInputs {
+        node: String,
+        expected: usize,
+        given: usize,
+    },
+    WrongKind {
+        node: String,
+        mismatch: Mismatch,
+    },
+    Cycle(u64),
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocumentError::Json(error) => write!(f, "invalid JSON: {error}"),
+            DocumentError::Version(version) => write!(
+                f,
+                "unsupported version {version}, expected version {VERSION}"
+            ),
+            DocumentError::Malformed(path) => write!(f, "malformed document at {path}"),
+            DocumentError::DuplicateId(id) => write!(f, "several nodes have the id {id}"),
+            DocumentError::MissingNode(id) => write!(f, "no node has the id {id}"),
+            DocumentError::UnknownNode(name) => write!(f, "unknown node '{name}'"),
+            DocumentError::WrongInputs {
+                node,
+                expected,
+                given,
+            } => write!(f, "'{node}' takes {expected} inputs, but was given {given}"),
+            DocumentError::WrongKind { node, mismatch } => match mismatch {
+                Mismatch::Input {
+                    input,
+                    given,
+                    expected,
+                } => {
+                    let expected: Vec<_> = expected.iter().map(TextureKind::name).collect();
+                    write!(
+                        f,
+                        "input {} of '{node}' is a {given} texture, but it takes {} textures",
+                        input + 1,
+                        expected.join(" or ")
+                    )
+                }
+                Mismatch::Inputs(a, b) => write!(
+                    f,
+                    "'{node}' was given a {a} texture and a {b} texture, which cannot be made the same kind"
+                ),
+            },
+            DocumentError::Cycle(id) => write!(f, "node {id} depends on itself"),
+        }
+    }
+}
+
+impl std::error::Error for DocumentError {}
+
+impl Document {
+    /// Describe a graph as a document, identifying each node by its index.
+    pub fn from_graph(graph: &Graph) -> Self {
+        let index = |id: &NodeId| id.index() as u64;
+        let argument = |value: &Value| match value {
+            Value::Number(_) => Argument::Number(value.number().unwrap_or(f64::NAN).to_string()),
+            Value::Symbol(sym) => Argument::Symbol(sym.to_string()),
+            Value::String(contents) => Argument::String(contents.to_string()),
+
+            // Parameters are substituted when resolving, so are only left in graphs with errors.
+            Value::Param(name) => Argument::Symbol(name.to_string()),
+        };
+
+        let nodes = graph
+            .ids()
+            .map(|id| {
+                let node = graph.node(id);
+                DocumentNode {
+                    id: index(&id),
+                    name: node.name().into(),
+                    positional: node.positional().iter().map(argument).collect(),
+                    named: node
+                        .named()
+                        .iter()
+                        .map(|(name, value)| (name.to_string(), argument(value)))
+                        .collect(),
+                    inputs: graph.inputs(id).iter().map(index).collect(),
+                    metadata: None,
+                }
+            })
+            .collect();
+
+        let definitions = graph
+            .definitions()
+            .map(|(name, ids)| (name.into(), ids.iter().map(index).collect()))
+            .collect();
+
+        let outputs = graph
+            .outputs()
+            .iter()
+            .enumerate()
+            .map(|(ndx, id)| DocumentOutput {
+                name: graph.output_name(ndx).map(Into::into),
+                node: index(id),
+            })
+            .collect();
+
+        Self {
+            nodes,
+            definitions,
+            outputs,
+            metadata: None,
+        }
+    }
+
+    /// Build the graph the document describes, checking that every node exists and is given the right inputs.
+    /// Arguments are not checked here, and a node with bad arguments fails to render instead.
+    pub fn graph(&self) -> Result<Graph<'_>, DocumentError> {
+        let mut positions = HashMap::new();
+        for (ndx, node) in self.nodes.iter().enumerate() {
+            if positions.insert(node.id, ndx).is_some() {
+                return Err(DocumentError::DuplicateId(node.id));
+            }
+        }
+
+        let mut builder = Builder {
+            document: self,
+            positions,
+            graph: Graph::new(),
+            added: HashMap::new(),
+            kinds: HashMap::new(),
+            visiting: Vec::new(),
+        };
+
+        for node in self.nodes.iter() {
+            builder.add(node.id)?;
+        }
+
+        for (name, ids) in self.definitions.iter() {
+            let ids = ids
+                .iter()
+                .map(|id| builder.add(*id))
+                .collect::<Result<_, _>>()?;
+            builder.graph.add_definition(name, ids);
+        }
+
+        for output in self.outputs.iter() {
+            let id = builder.add(output.node)?;
+            match &output.name {
+                Some(name) => builder.graph.add_named_output(name, id),
+                None => builder.graph.add_output(id),
+            }
+        }
+
+        Ok(builder.graph)
+    }
+
+    pub fn load(json: &str) -> Result<Self, DocumentError> {
+        let json = serde_json::from_str(json).map_err(DocumentError::Json)?;
+        Self::from_json(&json)
+    }
+
+    /// Write the document as pretty-printed JSON.
+    pub fn save(&self) -> String {
+        serde_json::to_string_pretty(&self.to_json()).expect("documents are valid JSON")
+    }
+
+    pub fn to_json(&self) -> Json {
+        let argument = |arg: &Argument| match arg {
+            Argument::Number(num) => num.parse().map_or(Json::Null, Json::Number),
+            Argument::Symbol(sym) => Json::String(sym.clone()),
+            Argument::String(contents) => json!({ "string": unescape(contents) }),
+        };
+
+        let nodes: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let mut object = Map::new();
+                object.insert("id".into(), node.id.into());
+                object.insert("name".into(), node.name.clone().into());
+
+                if !node.positional.is_empty() {
+                    let args = node.positional.iter().map(argument).collect();
+                    object.insert("args".into(), Json::Array(args));
+                }
+
+                if !node.named.is_empty() {
+                    let named = node
+                        .named
+                        .iter()
+                        .map(|(name, arg)| (name.clone(), argument(arg)))
+                        .collect();
+                    object.insert("named".into(), Json::Object(named));
+                }
+
+                if !node.inputs.is_empty() {
+                    object.insert("inputs".into(), node.inputs.clone().into());
+                }
+
+                if let Some(metadata) = &node.metadata {
+                    object.insert("metadata".into(), metadata.clone());
+                }
+
+                Json::Object(object)
+            })
+            .collect();
+
+        let outputs: Vec<_> = self
+            .outputs
+            .iter()
+            .map(|output| match &output.name {
+                Some(name) => json!({ "name": name, "node": output.node }),
+                None => json!({ "node": output.node }),
+            })
+            .collect();
+
+        let mut document = json!({
+            "format": "cobble",
+            "version": VERSION,
+            "nodes": nodes,
+            "definitions": self.definitions,
+            "outputs": outputs,
+        });
+
+        if let Some(metadata) = &self.metadata {
+            document["metadata"] = metadata.clone();
+        }
+
+        document
+    }
+
+    pub fn from_json(json: &Json) -> Result<Self, DocumentError> {
+        let malformed = |path: &str| DocumentError::Malformed(path.into());
+
+        if json["format"] != "cobble" {
+            return Err(malformed("format"));
+        }
+
+        match json["version"].as_u64() {
+            Some(VERSION) => {}
+            Some(version) => return Err(DocumentError::Version(version)),
+            None => return Err(malformed("version")),
+        }
+
+        let ids = |json: &Json, path: &str| match json {
+            Json::Null => Ok(Vec::new()),
+            json => json
+                .as_array()
+                .and_then(|ids| ids.iter().map(Json::as_u64).collect())
+                .ok_or_else(|| malformed(path)),
+        };
+
+        let argument = |json: &Json, path: &str| match json {
+            Json::Number(num) => Ok(Argument::Number(num.to_string())),
+            Json::String(sym) => Ok(Argument::Symbol(sym.clone())),
+            Json::Object(object) => match (object.len(), &object.get("string")) {
+                (1, Some(Json::String(text))) => Ok(Argument::String(escape(text))),
+                _ => Err(malformed(path)),
+            },
+            _ => Err(malformed(path)),
+        };
+
+        let mut nodes = Vec::new();
+        for (ndx, node) in array(&json["nodes"], "nodes")?.iter().enumerate() {
+            let path = format!("nodes[{ndx}]");
+
+            let positional = match &node["args"] {
+                Json::Null => Vec::new(),
+                args => array(args, &format!("{path}.args"))?
+                    .iter()
+                    .enumerate()
+                    .map(|(n, arg)| argument(arg, &format!("{path}.args[{n}]")))
+                    .collect::<Result<_, _>>()?,
+            };
+
+            let named = match &node["named"] {
+                Json::Null => Vec::new(),
+                named => named
+                    .as_object()
+                    .ok_or_else(|| malformed(&format!("{path}.named")))?
+                    .iter()
+                    .map(|(name, arg)| {
+                        Ok((name.clone(), argument(arg, &format!("{path}.{name}"))?))
+                    })
+                    .collect::<Result<_, _>>()?,
+            };
+
+            nodes.push(DocumentNode {
+                id: node["id"]
+                    .as_u64()
+                    .ok_or_else(|| malformed(&format!("{path}.id")))?,
+                name: node["name"]
+                    .as_str()
+                    .ok_or_else(|| malformed(&format!("{path}.name")))?
+                    .into(),
+                positional,
+                named,
+                inputs: ids(&node["inputs"], &format!("{path}.inputs"))?,
+                metadata: node.get("metadata").cloned(),
+            });
+        }
+
+        let definitions = match &json["definitions"] {
+            Json::Null => BTreeMap::new(),
+            definitions => definitions
+                .as_object()
+                .ok_or_else(|| malformed("definitions"))?
+                .iter()
+                .map(|(name, nodes)| {
+                    Ok((name.clone(), ids(nodes, &format!("definitions.{name}"))?))
+                })
+                .collect::<Result<_, _>>()?,
+        };
+
+        let mut outputs = Vec::new();
+        for (ndx, output) in array(&json["outputs"], "outputs")?.iter().enumerate() {
+            let path = format!("outputs[{ndx}]");
+            outputs.push(DocumentOutput {
+                name: match &output["name"] {
+                    Json::Null => None,
+                    name => Some(
+                        name.as_str()
+                            .ok_or_else(|| malformed(&format!("{path}.name")))?
+                            .into(),
+                    ),
+                },
+                node: output["node"]
+                    .as_u64()
+                    .ok_or_else(|| malformed(&format!("{path}.node")))?,
+            });
+        }
+
+        Ok(Self {
+            nodes,
+            definitions,
+            outputs,
+            metadata: json.get("metadata").cloned(),
+        })
+    }
+}
+
+fn array<'a>(json: &'a Json, path: &str) -> Result<&'a Vec<Json>, DocumentError> {
+    json.as_array()
+        .ok_or_else(|| DocumentError::Malformed(path.into()))
+}
+
+/// Adds the nodes of a document to a graph, each after its inputs.
+struct Builder<'doc> {
+    document: &'doc Document,
+
+    /// The position of each node in the document, by id.
+    positions: HashMap<u64, usize>,
+
+    graph: Graph<'doc>,
+    added: HashMap<u64, NodeId>,
+    kinds: HashMap<NodeId, TextureKind>,
+
+    /// The nodes whose inputs are being added, to find cycles.
+    visiting: Vec<u64>,
+}
+
+impl<'doc> Builder<'doc> {
+    fn add(&mut self, id: u64) -> Result<NodeId, DocumentError> {
+        if let Some(added) = self.added.get(&id) {
+            return Ok(*added);
+        }
+
+        if self.visiting.contains(&id) {
+            return Err(DocumentError::Cycle(id));
+        }
+
+        let document = self.document;
+        let node = self
+            .positions
+            .get(&id)
+            .map(|ndx| &document.nodes[*ndx])
+            .ok_or(DocumentError::MissingNode(id))?;
+
+        let info =
+            builtin(&node.name).ok_or_else(|| DocumentError::UnknownNode(node.name.clone()))?;
+        if node.inputs.len() != info.kind.inputs() {
+            return Err(DocumentError::WrongInputs {
+                node: node.name.clone(),
+                expected: info.kind.inputs(),
+                given: node.inputs.len(),
+            });
+        }
+
+        self.visiting.push(id);
+        let inputs: Vec<_> = node
+            .inputs
+            .iter()
+            .map(|input| self.add(*input))
+            .collect::<Result<_, _>>()?;
+        self.visiting.pop();
+
+        let given: Vec<_> = inputs.iter().map(|input| self.kinds[input]).collect();
+        let (conversions, kind) =
+            info.check(&given)
+                .map_err(|mismatch| DocumentError::WrongKind {
+                    node: node.name.clone(),
+                    mismatch,
+                })?;
+
+        let inputs = inputs
+            .into_iter()
+            .zip(conversions)
+            .map(|(input, conversion)| match conversion {
+                Some(conversion) => {
+                    let converted = Node::simple(conversion.node());
+                    let converted = self.graph.add_node(converted, vec![input]);
+                    self.kinds.insert(converted, conversion.to());
+                    converted
+                }
+                None => input,
+            })
+            .collect();
+
+        let value = |arg: &'doc Argument| match arg {
+            Argument::Number(num) => Value::Number(num),
+            Argument::Symbol(sym) => Value::Symbol(sym),
+            Argument::String(contents) => Value::String(contents),
+        };
+
+        let positional = node.positional.iter().map(value).collect();
+        let named = node
+            .named
+            .iter()
+            .map(|(name, arg)| (name.as_str(), value(arg)));
+
+        let added = self
+            .graph
+            .add_node(Node::with_args(&node.name, positional, named), inputs);
+        self.added.insert(id, added);
+        self.kinds.insert(added, kind);
+        Ok(added)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Document, DocumentError};
+    use crate::compiler::source::Sources;
+    use crate::compiler::{compile, Errors};
+
+    #[test]
+    fn documents_round_trip() {
+        let mut sources = Sources::new();
+        let id = sources.add(
+            "(a, b) -> math(Multiply) -> return\na = voronoi(50%, metric = Manhattan) -> invert\nb = simplex(3)"
+                .into(),
+        );
+
+        let mut errors = Errors::new();
+        let graph = compile(&sources, id, &mut errors);
+        assert!(errors.is_empty());
+
+        let mut document = Document::from_graph(&graph);
+        document.nodes[0].metadata = Some(serde_json::json!({ "position": [1, 2] }));
+
+        let loaded = Document::load(&document.save()).unwrap();
+        assert_eq!(document, loaded);
+
+        let reloaded = loaded.graph().unwrap();
+        assert_eq!(graph.len(), reloaded.len());
+        assert_eq!(
+            graph.to_string().replace("50%", "0.5"),
+            reloaded.to_string()
+        );
+    }
+
+    #[test]
+    fn documents_round_trip_strings() {
+        let mut sources = Sources::new();
+        let id = sources.add(r#"image("moss \"2\".png", space = Srgb) -> return"#.into());
+
+        let mut errors = Errors::new();
+        let graph = compile(&sources, id, &mut errors);
+        assert!(errors.is_empty());
+
+        let document = Document::from_graph(&graph);
+        let json = document.save();
+        assert!(json.contains(r#""string": "moss \"2\".png""#), "{json}");
+
+        let loaded = Document::load(&json).unwrap();
+        assert_eq!(document, loaded);
+        assert_eq!(graph.to_string(), loaded.graph().unwrap().to_string());
+    }
+
+    #[test]
+    fn loading_checks_nodes() {
+        let load = |nodes: &str| {
+            let json = format!(
+                r#"{{ "format": "cobble", "version": 1, "nodes": [{nodes}], "outputs": [{{ "node": 0 }}] }}"#
+            );
+            Document::load(&json).and_then(|document| document.graph().map(|_| ()))
+        };
+
+        assert!(load(
+            r#"{ "id": 1, "name": "voronoi" }, { "id": 0, "name": "invert", "inputs": [1] }"#
+        )
+        .is_ok());
+        assert!(matches!(
+            load(r#"{ "id": 0, "name": "invert", "inputs": [0] }"#),
+            Err(DocumentError::Cycle(0))
+        ));
+        assert!(matches!(
+            load(r#"{ "id": 0, "name": "invert", "inputs": [3] }"#),
+            Err(DocumentError::MissingNode(3))
+        ));
+        assert!(matches!(
+            load(r#"{ "id": 0, "name": "mix", "inputs": [] }"#),
+            Err(DocumentError::WrongInputs { .. })
+        ));
+        assert!(matches!(
+            load(r#"{ "id": 1, "name": "cells" }, { "id": 0, "name": "gradient", "inputs": [1] }"#),
+            Err(DocumentError::WrongKind { .. })
+        ));
+        assert!(matches!(
+            load(r#"{ "id": 0, "name": "voronio" }"#),
+            Err(DocumentError::UnknownNode(_))
+        ));
+        assert!(matches!(
+            load(r#"{ "id": 0, "name": 3 }"#),
+            Err(DocumentError::Malformed(path)) if path == "nodes[0].name"
+        ));
+    }
+}
@@ -1,4 +1,8 @@
-use crate::compiler::source::Span;
+use std::fmt;
+
+use crate::compiler::source::{Sources, Span};
+use crate::compiler::token::Token;
+use crate::nodes::{Mismatch, TextureKind};
 
 #[derive(Debug)]
 pub struct Error {
@@ -6,6 +10,7 @@ pub struct Error {
     pub message: String,
     pub notes: Vec<String>,
     pub helps: Vec<String>,
+    pub expected: Vec<&'static str>,
 }
 
 impl Error {
@@ -15,6 +20,7 @@ impl Error {
             message: message.into(),
             notes: Vec::new(),
             helps: Vec::new(),
+            expected: Vec::new(),
         }
     }
 
@@ -27,6 +33,79 @@ impl Error {
         let helps = helps.into_iter().map(Into::into).collect();
         Self { helps, ..self }
     }
+
+    /// Get a report of this error which points out where in the given sources it occurred.
+    pub fn report<'a>(&'a self, sources: &'a Sources) -> Report<'a> {
+        Report {
+            error: self,
+            sources,
+        }
+    }
+
+    /// Note which tokens would have been accepted where this error occurred.
+    pub fn with_expected(self, expected: &[Token]) -> Self {
+        let expected = expected.iter().map(Token::description).collect();
+        Self { expected, ..self }
+    }
+
+    /// List the tokens which would have been accepted, as in "a, b or c", or `None` if none were noted.
+    pub fn expected_list(&self) -> Option<String> {
+        let (last, rest) = self.expected.split_last()?;
+        if rest.is_empty() {
+            Some(last.to_string())
+        } else {
+            Some(format!("{} or {last}", rest.join(", ")))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Report<'a> {
+    error: &'a Error,
+    sources: &'a Sources,
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Report { error, sources } = self;
+        let source = sources.get(&error.at.file);
+
+        let (line, column) = source.position(error.at.start);
+        writeln!(
+            f,
+            "{}: error: {}",
+            sources.location(error.at),
+            error.message
+        )?;
+
+        let text = source.line(line);
+        let width = line.to_string().len();
+        let start = column - 1;
+        let end = source.position(error.at.end).1 - 1;
+        let underline = if end > start && source.position(error.at.end).0 == line {
+            end - start
+        } else {
+            1
+        };
+
+        writeln!(f, "{:width$} |", "")?;
+        writeln!(f, "{line} | {text}")?;
+        writeln!(f, "{:width$} | {:start$}{}", "", "", "^".repeat(underline))?;
+
+        for note in error.notes.iter() {
+            writeln!(f, "{:width$} = note: {note}", "")?;
+        }
+
+        for help in error.helps.iter() {
+            writeln!(f, "{:width$} = help: {help}", "")?;
+        }
+
+        if let Some(expected) = error.expected_list() {
+            writeln!(f, "{:width$} = expected {expected}", "")?;
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Default)]
@@ -47,7 +126,7 @@ impl Errors {
         self.errors.len()
     }
 
-    pub(super) fn at(&mut self, span: Span) -> ErrorAdder {
+    pub(super) fn at(&mut self, span: Span) -> ErrorAdder<'_> {
         ErrorAdder { errors: self, span }
     }
 }
@@ -58,6 +137,20 @@ pub(super) struct ErrorAdder<'a> {
 }
 
 impl ErrorAdder<'_> {
+    pub fn args_on_definition(&mut self, name: &str) {
+        let notes = vec![format!(
+            "'{name}' is a definition, and only built-in nodes have parameters"
+        )];
+        self.add(Error::new(self.span, "unexpected arguments").with_notes(notes));
+    }
+
+    pub fn argument_repeated(&mut self, param: &str) {
+        self.add(Error::new(
+            self.span,
+            format!("argument '{param}' given more than once"),
+        ));
+    }
+
     pub fn cycle(&mut self) {
         let notes = vec!["this name depends upon itself, and so cannot be produced"];
         self.add(Error::new(self.span, "cycle detected").with_notes(notes));
@@ -67,6 +160,10 @@ impl ErrorAdder<'_> {
         self.add(Error::new(self.span, "expected a definition"));
     }
 
+    pub fn expected_expression(&mut self, expected: &[Token]) {
+        self.add(Error::new(self.span, "expected an expression").with_expected(expected));
+    }
+
     pub fn expected_name(&mut self, symbol: Option<&str>) {
         let (notes, helps) = symbol_notes(symbol);
         self.add(
@@ -83,10 +180,38 @@ impl ErrorAdder<'_> {
         ));
     }
 
-    pub fn expected_statement(&mut self) {
+    pub fn expected_number(&mut self) {
+        self.add(Error::new(self.span, "expected a number"));
+    }
+
+    pub fn expected_positive(&mut self) {
+        self.add(Error::new(self.span, "expected a positive number"));
+    }
+
+    pub fn expected_separator(&mut self, expected: &[Token]) {
+        self.add(
+            Error::new(self.span, "expected a comma or a closing parenthesis")
+                .with_expected(expected),
+        );
+    }
+
+    pub fn expected_statement(&mut self, expected: &[Token]) {
         let notes = vec!["statements include return pipelines and assignments"];
 
-        self.add(Error::new(self.span, "expected a statement").with_notes(notes));
+        self.add(
+            Error::new(self.span, "expected a statement")
+                .with_notes(notes)
+                .with_expected(expected),
+        );
+    }
+
+    pub fn expected_string(&mut self) {
+        self.add(Error::new(self.span, "expected a string"));
+    }
+
+    pub fn expected_symbol(&mut self, options: &[&str]) {
+        let notes = vec![format!("expected one of {}", options.join(", "))];
+        self.add(Error::new(self.span, "expected a symbol").with_notes(notes));
     }
 
     pub fn expected_value(&mut self) {
@@ -105,18 +230,134 @@ impl ErrorAdder<'_> {
         );
     }
 
+    pub fn input_to_definition(&mut self, name: &str) {
+        let notes = vec![format!(
+            "'{name}' is a definition, and only built-in nodes take inputs"
+        )];
+        self.add(Error::new(self.span, "unexpected input").with_notes(notes));
+    }
+
+    pub fn malformed_param(&mut self, expected: &[Token]) {
+        let notes = vec!["parameters are declared as 'param name = default', optionally followed by '(min..max)'"];
+        self.add(
+            Error::new(self.span, "malformed parameter")
+                .with_notes(notes)
+                .with_expected(expected),
+        );
+    }
+
+    pub fn missing_argument(&mut self, node: &str, param: &str) {
+        self.add(Error::new(
+            self.span,
+            format!("missing argument '{param}' for '{node}'"),
+        ));
+    }
+
+    pub fn param_out_of_range(&mut self, name: &str, value: &str, overridden: bool) {
+        let mut notes = vec![format!(
+            "'{name}' is {value}, which is outside of its range"
+        )];
+        if overridden {
+            notes.push(format!("the value of '{name}' was given when compiling"));
+        }
+
+        self.add(Error::new(self.span, "parameter out of range").with_notes(notes));
+    }
+
+    pub fn param_override_not_number(&mut self, name: &str, value: &str) {
+        let notes = vec![format!("'{name}' was given '{value}' when compiling")];
+        self.add(Error::new(self.span, "expected a number").with_notes(notes));
+    }
+
+    pub fn param_override_not_finite(&mut self, name: &str, value: &str) {
+        let notes = vec![format!("'{name}' was given '{value}' when compiling")];
+        self.add(Error::new(self.span, "expected a finite number").with_notes(notes));
+    }
+
     pub fn repeated_name_init(&mut self) {
         self.add(Error::new(self.span, "named argument repeated"));
     }
 
+    pub fn repeated_param(&mut self, name: &str) {
+        self.add(Error::new(
+            self.span,
+            format!("parameter '{name}' declared more than once"),
+        ));
+    }
+
+    pub fn reversed_range(&mut self) {
+        let notes = vec!["the minimum of a range must not be greater than its maximum"];
+        self.add(Error::new(self.span, "reversed range").with_notes(notes));
+    }
+
+    pub fn too_many_arguments(&mut self, node: &str, max: usize) {
+        let notes = vec![format!("'{node}' takes at most {max} arguments")];
+        self.add(Error::new(self.span, "too many arguments").with_notes(notes));
+    }
+
     pub fn unclosed_paren(&mut self) {
-        self.add(Error::new(self.span, "unclosed group"));
+        self.add(Error::new(self.span, "unclosed group").with_expected(&[Token::CloseParen]));
+    }
+
+    pub fn unclosed_string(&mut self) {
+        let notes = vec!["a string must be closed on the line it starts on"];
+        self.add(Error::new(self.span, "unclosed string").with_notes(notes));
     }
 
     pub fn unexpected_return(&mut self) {
         self.add(Error::new(self.span, "unexpected 'return'"));
     }
 
+    pub fn unknown_argument(&mut self, node: &str, arg: &str) {
+        self.add(Error::new(
+            self.span,
+            format!("'{node}' has no parameter named '{arg}'"),
+        ));
+    }
+
+    pub fn unknown_name(&mut self, name: &str) {
+        let notes = vec!["a name must either be defined or refer to a built-in node"];
+        self.add(Error::new(self.span, format!("unknown name '{name}'")).with_notes(notes));
+    }
+
+    pub fn unknown_param(&mut self, name: &str) {
+        let notes = vec!["arguments may only refer to parameters declared with 'param'"];
+        self.add(Error::new(self.span, format!("unknown parameter '{name}'")).with_notes(notes));
+    }
+
+    pub fn unknown_symbol(&mut self, symbol: &str, options: &[&str]) {
+        let notes = vec![format!("expected one of {}", options.join(", "))];
+        self.add(Error::new(self.span, format!("unknown symbol '{symbol}'")).with_notes(notes));
+    }
+
+    pub fn wrong_kind(&mut self, node: &str, mismatch: &Mismatch) {
+        let notes = match mismatch {
+            Mismatch::Input {
+                input,
+                given,
+                expected,
+            } => {
+                let expected: Vec<_> = expected.iter().map(TextureKind::name).collect();
+                vec![
+                    format!("input {} of '{node}' is a {given} texture", input + 1),
+                    format!("'{node}' takes {} textures", expected.join(" or ")),
+                ]
+            }
+            Mismatch::Inputs(a, b) => vec![format!(
+                "'{node}' was given a {a} texture and a {b} texture, which cannot be made the same kind"
+            )],
+        };
+        self.add(Error::new(self.span, "wrong kind of texture").with_notes(notes));
+    }
+
+    pub fn wrong_inputs(&mut self, node: &str, expected: usize, given: usize) {
+        let plural = if expected == 1 { "input" } else { "inputs" };
+        let notes = vec![format!(
+            "'{node}' takes {expected} {plural}, but was given {given}"
+        )];
+        self.add(Error::new(self.span, "wrong number of inputs").with_notes(notes));
+    }
+
     fn add(&mut self, msg: Error) {
         self.errors.errors.push(msg);
     }
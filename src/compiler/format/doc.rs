@@ -0,0 +1,177 @@
+/// The number of columns each level of indentation adds.
+const INDENT: usize = 4;
+
+/// A document describing the possible layouts of some text. Groups are laid out on a single line if they fit, and
+/// otherwise have all their line breaks taken.
+#[derive(Clone, Debug)]
+pub enum Doc {
+    Text(String),
+
+    /// A space, or a line break if the enclosing group is broken.
+    Line,
+
+    /// Nothing, or a line break if the enclosing group is broken.
+    SoftLine,
+
+    /// A line break, which forces every enclosing group to break.
+    HardLine,
+
+    /// A line break, unless the current line is still empty.
+    EnsureLine,
+
+    /// Nothing, but forces every enclosing group to break.
+    BreakParent,
+
+    /// Text which is only included if the enclosing group is broken.
+    IfBreak(&'static str),
+
+    /// Text which is deferred until the end of the current line, and which forces every enclosing group to break.
+    LineSuffix(String),
+
+    Indent(Vec<Doc>),
+    Group(Vec<Doc>),
+    Concat(Vec<Doc>),
+}
+
+impl Doc {
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(text.into())
+    }
+
+    /// Does this document force any group containing it to break?
+    fn forces_break(&self) -> bool {
+        match self {
+            Doc::HardLine | Doc::BreakParent | Doc::LineSuffix(_) => true,
+            Doc::Indent(docs) | Doc::Group(docs) | Doc::Concat(docs) => {
+                docs.iter().any(Doc::forces_break)
+            }
+            Doc::Text(_) | Doc::Line | Doc::SoftLine | Doc::EnsureLine | Doc::IfBreak(_) => false,
+        }
+    }
+
+    /// Get the width of this document laid out on a single line.
+    fn flat_width(&self) -> usize {
+        match self {
+            Doc::Text(text) => text.chars().count(),
+            Doc::Line => 1,
+            Doc::Indent(docs) | Doc::Group(docs) | Doc::Concat(docs) => {
+                docs.iter().map(Doc::flat_width).sum()
+            }
+            Doc::SoftLine
+            | Doc::HardLine
+            | Doc::EnsureLine
+            | Doc::BreakParent
+            | Doc::IfBreak(_)
+            | Doc::LineSuffix(_) => 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+/// Lay out the document, trying to keep lines within the given width.
+pub fn print(doc: &Doc, width: usize) -> String {
+    let mut printer = Printer {
+        out: String::new(),
+        column: 0,
+        suffixes: Vec::new(),
+    };
+
+    let mut stack = vec![(0, Mode::Break, doc)];
+
+    while let Some((indent, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(text) => printer.text(text),
+
+            Doc::Line if mode == Mode::Flat => printer.text(" "),
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => printer.newline(indent),
+
+            Doc::EnsureLine => {
+                if !printer.line_is_empty() {
+                    printer.newline(indent);
+                }
+            }
+
+            Doc::BreakParent => {}
+
+            Doc::IfBreak(text) => {
+                if mode == Mode::Break {
+                    printer.text(text);
+                }
+            }
+
+            Doc::LineSuffix(text) => printer.suffixes.push(text.clone()),
+
+            Doc::Indent(docs) => {
+                stack.extend(docs.iter().rev().map(|doc| (indent + INDENT, mode, doc)));
+            }
+
+            Doc::Group(docs) => {
+                let breaks =
+                    docs.iter().any(Doc::forces_break) || printer.column + doc.flat_width() > width;
+                let mode = if breaks { Mode::Break } else { Mode::Flat };
+                stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+            }
+
+            Doc::Concat(docs) => {
+                stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+            }
+        }
+    }
+
+    printer.flush_suffixes();
+    let trimmed = printer.out.trim_end().len();
+    printer.out.truncate(trimmed);
+
+    if !printer.out.is_empty() {
+        printer.out.push('\n');
+    }
+
+    printer.out
+}
+
+struct Printer {
+    out: String,
+    column: usize,
+    suffixes: Vec<String>,
+}
+
+impl Printer {
+    fn text(&mut self, text: &str) {
+        self.out.push_str(text);
+        self.column += text.chars().count();
+    }
+
+    fn newline(&mut self, indent: usize) {
+        self.flush_suffixes();
+        self.trim_spaces();
+
+        self.out.push('\n');
+        self.out.extend(std::iter::repeat_n(' ', indent));
+        self.column = indent;
+    }
+
+    fn flush_suffixes(&mut self) {
+        for suffix in std::mem::take(&mut self.suffixes) {
+            self.trim_spaces();
+            self.text(" ");
+            self.text(&suffix);
+        }
+    }
+
+    fn trim_spaces(&mut self) {
+        let trimmed = self.out.trim_end_matches(' ').len();
+        self.column -= self.out.len() - trimmed;
+        self.out.truncate(trimmed);
+    }
+
+    fn line_is_empty(&self) -> bool {
+        let line = self.out.rsplit('\n').next().unwrap_or("");
+        line.trim().is_empty()
+    }
+}
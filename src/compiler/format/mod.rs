@@ -0,0 +1,498 @@
+//! Pretty-printing of programs in a canonical style.
+
+mod doc;
+
+use std::collections::HashMap;
+
+use self::doc::Doc;
+use crate::compiler::parse::cst::{Element, Kind, Node, Trivia};
+use crate::compiler::token::Token;
+
+/// The width formatted programs try to keep their lines within.
+pub const WIDTH: usize = 80;
+
+/// Format a program in the canonical style, keeping all of its comments. The tree should be free of syntax errors;
+/// any skipped tokens are kept, but their layout is not preserved.
+pub fn format(cst: &Node) -> String {
+    let mut formatter = Formatter::new(cst);
+    let doc = formatter.program(cst);
+    doc::print(&doc, WIDTH)
+}
+
+#[derive(Debug)]
+struct Comment<'src> {
+    text: &'src str,
+
+    /// The number of line breaks between this comment and whatever came before it.
+    newlines: usize,
+}
+
+/// Comments attached to the tokens they precede or follow, keyed by the start of the token.
+#[derive(Debug, Default)]
+struct Formatter<'src> {
+    leading: HashMap<usize, Vec<Comment<'src>>>,
+    trailing: HashMap<usize, Vec<Comment<'src>>>,
+
+    /// The number of line breaks before each token.
+    newlines: HashMap<usize, usize>,
+
+    /// Comments after the last token.
+    dangling: Vec<Comment<'src>>,
+}
+
+impl<'src> Formatter<'src> {
+    /// Attach every comment in the tree to a token. A comment on the same line as a preceding token trails that
+    /// token, while any other comment leads the token after it.
+    fn new(cst: &Node<'src>) -> Self {
+        fn walk<'src>(
+            node: &Node<'src>,
+            this: &mut Formatter<'src>,
+            prev: &mut Option<usize>,
+            newlines: &mut usize,
+            pending: &mut Vec<Comment<'src>>,
+        ) {
+            for elem in node.children.iter() {
+                match elem {
+                    Element::Node(node) => walk(node, this, prev, newlines, pending),
+
+                    Element::Trivia {
+                        trivia: Trivia::Whitespace,
+                        text,
+                        ..
+                    } => *newlines += text.matches('\n').count(),
+
+                    Element::Trivia {
+                        trivia: Trivia::Comment,
+                        text,
+                        ..
+                    } => {
+                        let comment = Comment {
+                            text,
+                            newlines: *newlines,
+                        };
+
+                        match prev {
+                            Some(at) if *newlines == 0 && pending.is_empty() => {
+                                this.trailing.entry(*at).or_default().push(comment)
+                            }
+
+                            _ => pending.push(comment),
+                        }
+
+                        *newlines = 0;
+                    }
+
+                    Element::Token { span, .. } => {
+                        this.leading.insert(span.start, std::mem::take(pending));
+                        this.newlines.insert(span.start, *newlines);
+                        *newlines = 0;
+                        *prev = Some(span.start);
+                    }
+                }
+            }
+        }
+
+        let mut this = Self::default();
+        let mut pending = Vec::new();
+        walk(cst, &mut this, &mut None, &mut 0, &mut pending);
+        this.dangling = pending;
+        this
+    }
+
+    fn program(&mut self, node: &Node<'src>) -> Doc {
+        let mut docs = Vec::new();
+
+        for (index, stmt) in node.significant().enumerate() {
+            let first = first_token(stmt);
+
+            // Only blank lines between statements and their leading comments are kept.
+            let leading = first
+                .and_then(|at| self.leading.remove(&at))
+                .unwrap_or_default();
+
+            let mut lines = leading
+                .into_iter()
+                .map(|comment| (comment.newlines, Some(comment.text)))
+                .collect::<Vec<_>>();
+            lines.push((first.map(|at| self.newlines[&at]).unwrap_or(0), None));
+
+            for (line, (newlines, comment)) in lines.into_iter().enumerate() {
+                if index > 0 || line > 0 {
+                    docs.push(Doc::HardLine);
+                    if newlines > 1 {
+                        docs.push(Doc::HardLine);
+                    }
+                }
+
+                if let Some(comment) = comment {
+                    docs.push(Doc::text(comment));
+                }
+            }
+
+            docs.push(self.expr(stmt));
+        }
+
+        for comment in std::mem::take(&mut self.dangling) {
+            if !docs.is_empty() {
+                docs.push(Doc::HardLine);
+                if comment.newlines > 1 {
+                    docs.push(Doc::HardLine);
+                }
+            }
+
+            docs.push(Doc::text(comment.text));
+        }
+
+        Doc::Concat(docs)
+    }
+
+    fn expr(&mut self, elem: &Element<'src>) -> Doc {
+        let node = match elem {
+            Element::Node(node) => node,
+            _ => return self.token(elem),
+        };
+
+        let children: Vec<_> = node.significant().collect();
+
+        match node.kind {
+            Kind::Assign | Kind::Param | Kind::Pipe => {
+                let mut docs = Vec::new();
+
+                for (index, child) in children.into_iter().enumerate() {
+                    if index > 0 {
+                        docs.push(Doc::text(" "));
+                    }
+
+                    docs.push(self.expr(child));
+                }
+
+                Doc::Concat(docs)
+            }
+
+            Kind::Init | Kind::Range => {
+                let docs = children.into_iter().map(|child| self.expr(child)).collect();
+                Doc::Concat(docs)
+            }
+
+            Kind::Group => self.group(&children),
+
+            Kind::Program | Kind::Error | Kind::Skipped => {
+                let mut docs = Vec::new();
+
+                for (index, child) in children.into_iter().enumerate() {
+                    if index > 0 {
+                        docs.push(Doc::text(" "));
+                    }
+
+                    docs.push(self.expr(child));
+                }
+
+                Doc::Concat(docs)
+            }
+        }
+    }
+
+    /// Format a parenthesized group, which is broken over several lines with a trailing comma if it does not fit on
+    /// one line.
+    fn group(&mut self, children: &[&Element<'src>]) -> Doc {
+        let (open, rest) = match children.split_first() {
+            Some((open, rest)) if is_token(open, Token::OpenParen) => (*open, rest),
+            _ => return Doc::Concat(children.iter().map(|child| self.expr(child)).collect()),
+        };
+
+        let (close, rest) = match rest.split_last() {
+            Some((close, rest)) if is_token(close, Token::CloseParen) => (Some(*close), rest),
+            _ => (None, rest),
+        };
+
+        let mut items: Vec<(Doc, Vec<Doc>)> = Vec::new();
+        for child in rest {
+            if is_token(child, Token::Comma) {
+                // The separators are rewritten, but any comments around them are kept.
+                let comments = self.comments_around(child);
+                if let Some((_, after)) = items.last_mut() {
+                    after.push(comments);
+                } else {
+                    items.push((Doc::Concat(vec![]), vec![comments]));
+                }
+            } else {
+                let item = self.expr(child);
+                items.push((item, vec![]));
+            }
+        }
+
+        let mut inner = vec![Doc::SoftLine];
+        let count = items.len();
+        for (index, (item, after)) in items.into_iter().enumerate() {
+            inner.push(item);
+
+            if index + 1 < count {
+                inner.push(Doc::text(","));
+                inner.extend(after);
+                inner.push(Doc::Line);
+            } else {
+                inner.push(Doc::IfBreak(","));
+                inner.extend(after);
+            }
+        }
+
+        let close_trailing = match close {
+            Some(close) => {
+                inner.push(self.comments_around(close));
+                self.trailing_comments(close)
+            }
+
+            None => Doc::Concat(vec![]),
+        };
+
+        let open_leading = self.leading_comments(open);
+        let mut group = vec![
+            Doc::text("("),
+            self.trailing_comments(open),
+            Doc::Indent(inner),
+            Doc::SoftLine,
+        ];
+
+        if close.is_some() {
+            group.push(Doc::text(")"));
+        }
+
+        Doc::Concat(vec![open_leading, Doc::Group(group), close_trailing])
+    }
+
+    /// Format a token along with its comments.
+    fn token(&mut self, elem: &Element<'src>) -> Doc {
+        let text = match elem {
+            Element::Token { text, .. } => *text,
+            _ => "",
+        };
+
+        Doc::Concat(vec![
+            self.leading_comments(elem),
+            Doc::text(text),
+            self.trailing_comments(elem),
+        ])
+    }
+
+    /// Get the comments around a token which is not printed in its original place. These must be followed by a
+    /// line break.
+    fn comments_around(&mut self, elem: &Element<'src>) -> Doc {
+        let comments = self.leading.remove(&elem.span().start).unwrap_or_default();
+        let mut docs = Vec::new();
+
+        for comment in comments {
+            docs.push(Doc::EnsureLine);
+            docs.push(Doc::text(comment.text));
+            docs.push(Doc::BreakParent);
+        }
+
+        docs.push(self.trailing_comments(elem));
+        Doc::Concat(docs)
+    }
+
+    fn leading_comments(&mut self, elem: &Element<'src>) -> Doc {
+        let comments = self.leading.remove(&elem.span().start).unwrap_or_default();
+        let mut docs = Vec::new();
+
+        for comment in comments {
+            docs.push(Doc::EnsureLine);
+            docs.push(Doc::text(comment.text));
+            docs.push(Doc::HardLine);
+        }
+
+        Doc::Concat(docs)
+    }
+
+    fn trailing_comments(&mut self, elem: &Element<'src>) -> Doc {
+        let comments = self.trailing.remove(&elem.span().start).unwrap_or_default();
+        let docs = comments
+            .into_iter()
+            .map(|comment| Doc::LineSuffix(comment.text.into()))
+            .collect();
+
+        Doc::Concat(docs)
+    }
+}
+
+fn first_token(elem: &Element) -> Option<usize> {
+    match elem {
+        Element::Node(node) => node.significant().find_map(first_token),
+        Element::Token { span, .. } => Some(span.start),
+        Element::Trivia { .. } => None,
+    }
+}
+
+fn is_token(elem: &Element, token: Token) -> bool {
+    matches!(elem, Element::Token { token: tok, .. } if *tok == token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format;
+    use crate::compiler::parse::{parse, parse_cst};
+    use crate::compiler::source::Sources;
+    use crate::compiler::token::lex;
+    use crate::compiler::tree::{Node, Pipeline, Program};
+    use crate::compiler::{Errors, Value};
+
+    fn format_str(source: &str) -> String {
+        let mut sources = Sources::new();
+        let id = sources.add(source.into());
+
+        let mut errors = Errors::new();
+        let cst = parse_cst(
+            &sources.get(&id).content,
+            id,
+            lex(&sources, id),
+            &mut errors,
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+
+        format(&cst)
+    }
+
+    /// Describe the shape of a program, ignoring spans.
+    fn shape(source: &str) -> String {
+        fn pipeline(pipe: &Pipeline) -> String {
+            let nodes: Vec<_> = pipe
+                .nodes
+                .iter()
+                .map(|(node, _)| node_shape(node))
+                .collect();
+            nodes.join(" -> ")
+        }
+
+        fn node_shape(node: &Node) -> String {
+            match node {
+                Node::Name(name) => name.to_string(),
+                Node::Init {
+                    name,
+                    positional,
+                    named,
+                } => {
+                    let mut named: Vec<_> = named
+                        .iter()
+                        .map(|(name, (_, (value, _)))| format!("{name}={}", value_shape(value)))
+                        .collect();
+                    named.sort();
+
+                    let positional: Vec<_> = positional
+                        .iter()
+                        .map(|(value, _)| value_shape(value))
+                        .collect();
+                    format!("{}[{}; {}]", name.0, positional.join(" "), named.join(" "))
+                }
+                Node::Tuple(pipes) => {
+                    let pipes: Vec<_> = pipes.iter().map(|(pipe, _)| pipeline(pipe)).collect();
+                    format!("<{}>", pipes.join(", "))
+                }
+                Node::Invalid => "!".into(),
+            }
+        }
+
+        fn value_shape(value: &Value) -> String {
+            match value {
+                Value::Number(num) => num.to_string(),
+                Value::Symbol(sym) => sym.to_string(),
+                Value::Param(name) => format!("${name}"),
+                Value::String(contents) => format!("'{contents}'"),
+            }
+        }
+
+        fn program_shape(program: &Program) -> String {
+            let mut defs: Vec<_> = program
+                .defs
+                .iter()
+                .map(|(name, (_, (pipe, _)))| format!("{name} = {}", pipeline(pipe)))
+                .collect();
+            defs.sort();
+
+            let results: Vec<_> = program
+                .results
+                .iter()
+                .map(|(pipe, _)| pipeline(pipe))
+                .collect();
+
+            let params: Vec<_> = program
+                .params
+                .iter()
+                .map(|(param, _)| {
+                    let range = param.range.map(|((min, max), _)| format!("({min}..{max})"));
+                    format!(
+                        "{} = {}{range:?} {:?}",
+                        param.name.0, param.default.0, param.doc
+                    )
+                })
+                .collect();
+            format!("{defs:?} {results:?} {params:?}")
+        }
+
+        let mut sources = Sources::new();
+        let id = sources.add(source.into());
+
+        let mut errors = Errors::new();
+        let program = parse(&sources.get(&id).content, lex(&sources, id), &mut errors);
+        program_shape(&program)
+    }
+
+    fn check(source: &str, expected: &str) {
+        let formatted = format_str(source);
+        assert_eq!(expected, formatted);
+        assert_eq!(
+            formatted,
+            format_str(&formatted),
+            "formatting is not idempotent"
+        );
+        assert_eq!(shape(source), shape(&formatted));
+    }
+
+    #[test]
+    fn format_spacing() {
+        check(
+            "a=voronoi(50,metric=Manhattan)->linvert\nb   =  (a,simplex)->math( Multiply )",
+            "a = voronoi(50, metric = Manhattan) -> linvert\nb = (a, simplex) -> math(Multiply)\n",
+        );
+    }
+
+    #[test]
+    fn format_statements_on_separate_lines() {
+        check("a = voronoi b = a", "a = voronoi\nb = a\n");
+    }
+
+    #[test]
+    fn format_wraps_long_args() {
+        check(
+            "a = math(Multiply, first = Something, second = SomethingElse, third = 1234567890)",
+            "a = math(\n    Multiply,\n    first = Something,\n    second = SomethingElse,\n    third = 1234567890,\n)\n",
+        );
+    }
+
+    #[test]
+    fn format_unwraps_short_args() {
+        check("a = math(\n    Multiply,\n)", "a = math(Multiply)\n");
+    }
+
+    #[test]
+    fn format_keeps_comments() {
+        check(
+            "-- header\n\n\n\na = voronoi -- smooth\n-- next\nb = math(\n  -- op\n  Multiply, -- really\n  2\n)\n-- end",
+            "-- header\n\na = voronoi -- smooth\n-- next\nb = math(\n    -- op\n    Multiply, -- really\n    2,\n)\n-- end\n",
+        );
+    }
+
+    #[test]
+    fn format_params() {
+        check(
+            "-- how many cells\n-- there are\nparam   density=20( 1 .. 200 )\n\nparam scale = 2 -- unbounded\na = voronoi(density)",
+            "-- how many cells\n-- there are\nparam density = 20 (1..200)\n\nparam scale = 2 -- unbounded\na = voronoi(density)\n",
+        );
+    }
+
+    #[test]
+    fn format_moves_comments_with_separators() {
+        check(
+            "a = f( -- c\n x, g(y -- d\n), z\n -- e\n)\nb = math(X\n -- f\n , Y)",
+            "a = f( -- c\n    x,\n    g(\n        y, -- d\n    ),\n    z,\n    -- e\n)\nb = math(\n    X,\n    -- f\n    Y,\n)\n",
+        );
+    }
+}
@@ -1,12 +1,30 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
 
 use bimap::BiMap;
 
+use super::source::{Sources, Span};
 use super::Value;
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct NodeId(usize);
 
+impl NodeId {
+    /// Get the position of the node in the order nodes were added to its graph.
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+impl fmt::Display for NodeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct Node<'name>(
     &'name str,
@@ -32,13 +50,66 @@ impl<'name> Node<'name> {
         kw.sort_by_key(|(name, _)| *name);
         Self(name, positional, kw)
     }
+
+    pub fn name(&self) -> &'name str {
+        self.0
+    }
+
+    pub fn positional(&self) -> &[Value<'name>] {
+        &self.1
+    }
+
+    /// Get the named arguments of this node, sorted by name.
+    pub fn named(&self) -> &[(&'name str, Value<'name>)] {
+        &self.2
+    }
+}
+
+impl fmt::Display for Node<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)?;
+
+        if self.1.is_empty() && self.2.is_empty() {
+            return Ok(());
+        }
+
+        let positional = self.1.iter().map(ToString::to_string);
+        let named = self
+            .2
+            .iter()
+            .map(|(name, value)| format!("{name} = {value}"));
+        let args: Vec<_> = positional.chain(named).collect();
+        write!(f, "({})", args.join(", "))
+    }
 }
 
+/// A parameter declared by a program, along with the value it was resolved with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Param {
+    pub name: String,
+
+    /// The comment above the declaration, with each line's dashes removed.
+    pub doc: String,
+
+    pub default: f64,
+    pub range: Option<(f64, f64)>,
+    pub value: f64,
+
+    /// The declaration of the parameter.
+    pub span: Span,
+}
+
+/// A graph of nodes. A node is identified by both its arguments and its inputs, so that equal nodes given equal
+/// inputs are shared, and nodes are always added after their inputs.
 #[derive(Debug, Default)]
 pub struct Graph<'name> {
-    nodes: BiMap<NodeId, Node<'name>>,
-    incoming: HashMap<NodeId, HashSet<NodeId>>,
+    nodes: BiMap<NodeId, (Node<'name>, Vec<NodeId>)>,
     outgoing: HashMap<NodeId, HashSet<NodeId>>,
+    outputs: Vec<NodeId>,
+    output_names: Vec<Option<&'name str>>,
+    definitions: BTreeMap<&'name str, Vec<NodeId>>,
+    spans: HashMap<NodeId, Vec<Span>>,
+    params: Vec<Param>,
 
     count: usize,
 }
@@ -47,35 +118,356 @@ impl<'name> Graph<'name> {
     pub fn new() -> Self {
         Self {
             nodes: BiMap::new(),
-            incoming: HashMap::new(),
             outgoing: HashMap::new(),
+            outputs: Vec::new(),
+            output_names: Vec::new(),
+            definitions: BTreeMap::new(),
+            spans: HashMap::new(),
+            params: Vec::new(),
 
             count: 0,
         }
     }
 
-    pub fn add_node(&mut self, node: Node<'name>) -> NodeId {
+    /// Add a node which takes the given inputs, in order.
+    pub fn add_node(&mut self, node: Node<'name>, inputs: Vec<NodeId>) -> NodeId {
+        let node = (node, inputs);
+
         if let Some(id) = self.nodes.get_by_right(&node) {
-            *id
-        } else {
-            let id = self.reserve();
-            self.nodes.insert(id, node);
-            id
+            return *id;
         }
+
+        let id = NodeId(self.count);
+        self.count += 1;
+
+        for input in node.1.iter() {
+            self.outgoing.entry(*input).or_default().insert(id);
+        }
+
+        self.nodes.insert(id, node);
+        id
     }
 
-    pub fn add_edge(&mut self, from: NodeId, to: NodeId) {
-        self.incoming.entry(to).or_default().insert(from);
-        self.outgoing.entry(from).or_default().insert(to);
+    /// Record that a definition produces the given nodes.
+    pub fn add_definition(&mut self, name: &'name str, ids: Vec<NodeId>) {
+        self.definitions.insert(name, ids);
     }
 
-    pub fn add_reserved(&mut self, id: NodeId, node: Node<'name>) {
-        assert!(self.nodes.insert(id, node).did_overwrite());
+    /// Record that a node was produced by the given part of a source.
+    pub fn add_span(&mut self, id: NodeId, span: Span) {
+        let spans = self.spans.entry(id).or_default();
+        if !spans.contains(&span) {
+            spans.push(span);
+        }
     }
 
-    pub fn reserve(&mut self) -> NodeId {
-        let id = NodeId(self.count);
-        self.count += 1;
-        id
+    /// Mark a node as a result of the graph.
+    pub fn add_output(&mut self, id: NodeId) {
+        self.outputs.push(id);
+        self.output_names.push(None);
+    }
+
+    /// Mark a node as a result of the graph, under the given name.
+    pub fn add_named_output(&mut self, name: &'name str, id: NodeId) {
+        self.outputs.push(id);
+        self.output_names.push(Some(name));
+    }
+
+    /// Record a parameter the graph was resolved with.
+    pub fn add_param(&mut self, param: Param) {
+        self.params.push(param);
+    }
+
+    /// Get every parameter, in the order they were declared.
+    pub fn params(&self) -> &[Param] {
+        &self.params
+    }
+
+    /// Get the nodes produced by every definition, sorted by name.
+    pub fn definitions(&self) -> impl Iterator<Item = (&'name str, &[NodeId])> {
+        self.definitions
+            .iter()
+            .map(|(name, ids)| (*name, ids.as_slice()))
+    }
+
+    pub fn definition(&self, name: &str) -> Option<&[NodeId]> {
+        self.definitions.get(name).map(Vec::as_slice)
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node<'name> {
+        &self
+            .nodes
+            .get_by_left(&id)
+            .expect("node from another graph")
+            .0
+    }
+
+    /// Get every part of the sources which produced a node, in the order they were resolved. Nodes shared by several
+    /// parts of the sources have several spans.
+    pub fn spans(&self, id: NodeId) -> &[Span] {
+        self.spans.get(&id).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn inputs(&self, id: NodeId) -> &[NodeId] {
+        &self
+            .nodes
+            .get_by_left(&id)
+            .expect("node from another graph")
+            .1
+    }
+
+    /// Get the nodes which take the given node as an input.
+    pub fn outgoing(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.outgoing.get(&id).into_iter().flatten().copied()
+    }
+
+    /// Get every node in the graph, with each node coming after its inputs.
+    pub fn ids(&self) -> impl Iterator<Item = NodeId> {
+        (0..self.count).map(NodeId)
+    }
+
+    /// Get a hash of every node which covers its arguments and, transitively, its inputs, but not its id. Nodes in
+    /// different graphs with equal hashes describe the same computation.
+    pub fn hashes(&self) -> HashMap<NodeId, u64> {
+        let mut hashes: HashMap<NodeId, u64> = HashMap::new();
+
+        for id in self.ids() {
+            let mut hasher = DefaultHasher::new();
+            self.node(id).hash(&mut hasher);
+
+            for input in self.inputs(id) {
+                hashes[input].hash(&mut hasher);
+            }
+
+            hashes.insert(id, hasher.finish());
+        }
+
+        hashes
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Get the results of the graph, in the order they were added.
+    pub fn outputs(&self) -> &[NodeId] {
+        &self.outputs
+    }
+
+    /// Get the name of the result at the given position in [`Graph::outputs`], if it has one.
+    pub fn output_name(&self, ndx: usize) -> Option<&'name str> {
+        self.output_names.get(ndx).copied().flatten()
+    }
+}
+
+impl<'name> Graph<'name> {
+    /// Display the graph with a comment after each node saying where in the given sources it came from.
+    pub fn annotated<'a>(&'a self, sources: &'a Sources) -> Annotated<'a, 'name> {
+        Annotated {
+            graph: self,
+            sources,
+        }
+    }
+
+    /// Describe the graph in Graphviz's DOT language.
+    pub fn dot<'a>(&'a self) -> Dot<'a, 'name> {
+        Dot {
+            graph: self,
+            sources: None,
+            thumbnails: HashMap::new(),
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, sources: Option<&Sources>) -> fmt::Result {
+        for id in self.ids() {
+            write!(f, "{id} = {}", self.node(id))?;
+
+            let inputs: Vec<_> = self.inputs(id).iter().map(ToString::to_string).collect();
+            if !inputs.is_empty() {
+                write!(f, " <- {}", inputs.join(", "))?;
+            }
+
+            if let Some(sources) = sources.filter(|_| !self.spans(id).is_empty()) {
+                let spans: Vec<_> = self
+                    .spans(id)
+                    .iter()
+                    .map(|span| sources.location(*span))
+                    .collect();
+                write!(f, "  -- {}", spans.join(", "))?;
+            }
+
+            writeln!(f)?;
+        }
+
+        for (name, ids) in self.definitions() {
+            let ids: Vec<_> = ids.iter().map(ToString::to_string).collect();
+            writeln!(f, "{name} = {}", ids.join(", "))?;
+        }
+
+        for (ndx, id) in self.outputs().iter().enumerate() {
+            match self.output_name(ndx) {
+                Some(name) => writeln!(f, "return {id} as {name}")?,
+                None => writeln!(f, "return {id}")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Graph<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, None)
+    }
+}
+
+#[derive(Debug)]
+pub struct Annotated<'a, 'name> {
+    graph: &'a Graph<'name>,
+    sources: &'a Sources,
+}
+
+impl fmt::Display for Annotated<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.graph.write(f, Some(self.sources))
+    }
+}
+
+/// A graph in Graphviz's DOT language.
+///
+/// Each node is labelled with its arguments and the definitions which produce it. Nodes shared by several parts of the
+/// sources are filled, results are drawn with a double border, and edges into nodes with several inputs are numbered
+/// in the order the inputs are taken.
+#[derive(Debug)]
+pub struct Dot<'a, 'name> {
+    graph: &'a Graph<'name>,
+    sources: Option<&'a Sources>,
+    thumbnails: HashMap<NodeId, PathBuf>,
+}
+
+impl<'a, 'name> Dot<'a, 'name> {
+    /// Give each node a tooltip saying where in the given sources it came from.
+    pub fn with_sources(self, sources: &'a Sources) -> Self {
+        Self {
+            sources: Some(sources),
+            ..self
+        }
+    }
+
+    /// Show an image in each of the given nodes, such as a small render of its output.
+    pub fn with_thumbnails(self, thumbnails: impl IntoIterator<Item = (NodeId, PathBuf)>) -> Self {
+        Self {
+            thumbnails: thumbnails.into_iter().collect(),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Dot<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let graph = self.graph;
+
+        let mut names: HashMap<NodeId, Vec<&str>> = HashMap::new();
+        for (name, ids) in graph.definitions() {
+            for id in ids {
+                names.entry(*id).or_default().push(name);
+            }
+        }
+
+        writeln!(f, "digraph cobble {{")?;
+        writeln!(f, "    rankdir = LR;")?;
+        writeln!(f, "    node [shape = box, fontname = monospace];")?;
+
+        for id in graph.ids() {
+            let mut label = graph.node(id).to_string();
+            if let Some(names) = names.get(&id) {
+                label = format!("{}\\n{label}", names.join(", "));
+            }
+
+            let mut attrs = vec![format!("label = {}", quote(&label))];
+
+            if graph.spans(id).len() > 1 {
+                attrs.push("style = filled, fillcolor = lightgoldenrod1".into());
+            }
+
+            if graph.outputs().contains(&id) {
+                attrs.push("peripheries = 2".into());
+            }
+
+            if let Some(sources) = self.sources {
+                let spans: Vec<_> = graph
+                    .spans(id)
+                    .iter()
+                    .map(|span| sources.location(*span))
+                    .collect();
+                attrs.push(format!("tooltip = {}", quote(&spans.join(", "))));
+            }
+
+            if let Some(path) = self.thumbnails.get(&id) {
+                let path = quote(&path.display().to_string());
+                attrs.push(format!("image = {path}, imagepos = tc, labelloc = b"));
+            }
+
+            writeln!(f, "    n{} [{}];", id.index(), attrs.join(", "))?;
+        }
+
+        for id in graph.ids() {
+            let inputs = graph.inputs(id);
+            for (ndx, input) in inputs.iter().enumerate() {
+                write!(f, "    n{} -> n{}", input.index(), id.index())?;
+
+                if inputs.len() > 1 {
+                    write!(f, " [label = {ndx}]")?;
+                }
+
+                writeln!(f, ";")?;
+            }
+        }
+
+        writeln!(f, "}}")
+    }
+}
+
+/// Quote a string for DOT, leaving any escape sequences such as `\n` in place.
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compiler::source::Sources;
+    use crate::compiler::{compile, Errors};
+
+    #[test]
+    fn dot_marks_shared_nodes_and_results() {
+        let mut sources = Sources::new();
+        let id = sources.add(
+            "(a, voronoi(5)) -> mix -> return\na = voronoi(20) -> invert\nvoronoi(20) -> invert -> return"
+                .into(),
+        );
+
+        let mut errors = Errors::new();
+        let graph = compile(&sources, id, &mut errors);
+        assert!(errors.is_empty());
+
+        assert_eq!(
+            r#"digraph cobble {
+    rankdir = LR;
+    node [shape = box, fontname = monospace];
+    n0 [label = "voronoi(20)", style = filled, fillcolor = lightgoldenrod1, tooltip = "<input>:2:5, <input>:3:1"];
+    n1 [label = "a\ninvert", style = filled, fillcolor = lightgoldenrod1, peripheries = 2, tooltip = "<input>:2:20, <input>:3:16"];
+    n2 [label = "voronoi(5)", tooltip = "<input>:1:5"];
+    n3 [label = "mix", peripheries = 2, tooltip = "<input>:1:20"];
+    n0 -> n1;
+    n1 -> n3 [label = 0];
+    n2 -> n3 [label = 1];
+}
+"#,
+            graph.dot().with_sources(&sources).to_string()
+        );
     }
 }
@@ -1,3 +1,5 @@
+pub mod document;
+pub mod format;
 pub mod graph;
 pub mod parse;
 pub mod resolve;
@@ -7,10 +9,118 @@ pub mod tree;
 
 mod error;
 
-pub use error::{Error, Errors};
+use std::collections::BTreeMap;
+use std::fmt;
+
+pub use error::{Error, Errors, Report};
+
+use self::graph::Graph;
+use self::source::{SourceId, Sources};
+
+/// Parameters which are given no values, and so keep their defaults.
+pub(crate) static DEFAULTS: Params = Params::new();
+
+/// Lex, parse and resolve a source into a graph, adding any errors found along the way.
+pub fn compile<'src>(sources: &'src Sources, id: SourceId, errors: &mut Errors) -> Graph<'src> {
+    compile_with_params(sources, id, &DEFAULTS, errors)
+}
+
+/// Compile a source, giving its parameters the values in `params` rather than their defaults. Values for parameters
+/// the source does not declare are ignored, so callers should check them against [`Graph::params`].
+pub fn compile_with_params<'src>(
+    sources: &'src Sources,
+    id: SourceId,
+    params: &'src Params,
+    errors: &mut Errors,
+) -> Graph<'src> {
+    let text = &sources.get(&id).content;
+    let program = parse::parse(text, token::lex(sources, id), errors);
+    resolve::resolve_with_params(program, params, errors)
+}
+
+/// Values given to the parameters of a program, by name. Values are kept as they were written, and are checked when
+/// the program is compiled.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Params {
+    values: BTreeMap<String, String>,
+}
+
+impl Params {
+    pub const fn new() -> Self {
+        Self {
+            values: BTreeMap::new(),
+        }
+    }
+
+    pub fn with(mut self, name: &str, value: f64) -> Self {
+        self.set(name, &value.to_string());
+        self
+    }
+
+    /// Give a parameter a value, written as a number literal.
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.values.insert(name.into(), value.into());
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    /// Get the name of every parameter given a value, sorted by name.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.values.keys().map(String::as_str)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
 
 #[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub enum Value<'src> {
     Number(&'src str),
     Symbol(&'src str),
+
+    /// The contents of a string literal, with any escapes left in.
+    String(&'src str),
+
+    /// A reference to a parameter, which is replaced by the parameter's value when resolving.
+    Param(&'src str),
+}
+
+impl Value<'_> {
+    /// Get the value of a number literal. Digit separators are ignored, and a trailing `%` divides the number by a
+    /// hundred.
+    pub fn number(&self) -> Option<f64> {
+        let Value::Number(num) = self else {
+            return None;
+        };
+
+        let (num, scale) = match num.strip_suffix('%') {
+            Some(num) => (num, 0.01),
+            None => (*num, 1.0),
+        };
+
+        let num: String = num.chars().filter(|c| !matches!(c, '_' | '\'')).collect();
+        num.parse::<f64>().ok().map(|num| num * scale)
+    }
+
+    /// Get the text of a string literal, with its escapes replaced.
+    pub fn string(&self) -> Option<String> {
+        match self {
+            Value::String(contents) => Some(token::unescape(contents)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Number(num) => write!(f, "{num}"),
+            Value::Symbol(sym) => write!(f, "{sym}"),
+            Value::String(contents) => write!(f, "\"{contents}\""),
+            Value::Param(name) => write!(f, "{name}"),
+        }
+    }
 }
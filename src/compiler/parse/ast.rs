@@ -1,4 +1,5 @@
 use crate::compiler::source::Span;
+use crate::compiler::tree::Param;
 
 pub type Spanned<T> = (T, Span);
 
@@ -11,7 +12,15 @@ pub enum Ast<'src> {
 
     Init(Box<Spanned<Ast<'src>>>, Vec<Spanned<Ast<'src>>>),
 
+    Param(Param<'src>),
+
     Name(&'src str),
     Number(&'src str),
     Symbol(&'src str),
+
+    /// The contents of a string literal, with any escapes left in.
+    String(&'src str),
+
+    /// A syntax error, which has already been reported.
+    Invalid,
 }
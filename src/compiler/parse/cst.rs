@@ -0,0 +1,430 @@
+//! A lossless concrete syntax tree. Unlike the [`Program`](crate::compiler::tree::Program), this keeps every token
+//! along with all the whitespace and comments between them, such that printing a tree reproduces its source exactly.
+
+use std::fmt;
+
+use std::ops::Range;
+
+use super::ast::{Ast, Spanned};
+use crate::compiler::source::{SourceId, Span};
+use crate::compiler::token::{string_contents, Token};
+use crate::compiler::tree::Param;
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Kind {
+    /// The root of the tree, containing every statement.
+    Program,
+
+    /// `name = pipeline`
+    Assign,
+
+    /// `a -> b -> c`
+    Pipe,
+
+    /// `name(args)`
+    Init,
+
+    /// `param name = default (min..max)`, along with the comment above it.
+    Param,
+
+    /// `(min..max)`
+    Range,
+
+    /// A parenthesized, comma separated group, used both for tuples and for initializer arguments.
+    Group,
+
+    /// An expression which could not be parsed.
+    Error,
+
+    /// Tokens which were skipped while recovering from an error.
+    Skipped,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Trivia {
+    Whitespace,
+    Comment,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Node<'src> {
+    pub kind: Kind,
+    pub span: Span,
+    pub children: Vec<Element<'src>>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Element<'src> {
+    Node(Node<'src>),
+
+    Token {
+        token: Token<'src>,
+        text: &'src str,
+        span: Span,
+    },
+
+    Trivia {
+        trivia: Trivia,
+        text: &'src str,
+        span: Span,
+    },
+}
+
+impl<'src> Node<'src> {
+    /// Get the child nodes and tokens of this node, skipping any trivia.
+    pub fn significant(&self) -> impl Iterator<Item = &Element<'src>> + '_ {
+        self.children
+            .iter()
+            .filter(|elem| !matches!(elem, Element::Trivia { .. }))
+    }
+
+    /// Lower this tree into the abstract syntax tree used by the rest of the parser.
+    pub(super) fn to_ast(&self) -> Vec<Spanned<Ast<'src>>> {
+        self.significant()
+            .filter(|elem| !elem.is_kind(Kind::Skipped))
+            .map(Element::to_ast)
+            .collect()
+    }
+
+    /// Get the parameter a complete declaration describes.
+    fn to_param(&self) -> Option<Param<'src>> {
+        let tokens: Vec<_> = self
+            .significant()
+            .map(|elem| match elem {
+                Element::Token { token, span, .. } => Some((*token, *span)),
+                _ => None,
+            })
+            .collect();
+
+        // The parser only reaches the default after the `=`, so checking the name and default is enough.
+        let name = match tokens.get(1) {
+            Some(Some((Token::Ident(name), span))) => (*name, *span),
+            _ => return None,
+        };
+
+        let default = match tokens.get(3) {
+            Some(Some((Token::Number(default), span))) => (*default, *span),
+            _ => return None,
+        };
+
+        let range = match self.significant().nth(4) {
+            Some(Element::Node(range)) => {
+                let bounds: Vec<_> = range
+                    .significant()
+                    .filter_map(|elem| match elem {
+                        Element::Token {
+                            token: Token::Number(num),
+                            ..
+                        } => Some(*num),
+                        _ => None,
+                    })
+                    .collect();
+
+                match bounds[..] {
+                    [min, max] => Some(((min, max), range.span)),
+                    _ => return None,
+                }
+            }
+            _ => None,
+        };
+
+        let leading: Vec<_> = self
+            .children
+            .iter()
+            .take_while(|elem| matches!(elem, Element::Trivia { .. }))
+            .filter_map(|elem| match elem {
+                Element::Trivia { trivia, text, span } => Some((*trivia, *text, *span)),
+                _ => None,
+            })
+            .collect();
+
+        Some(Param {
+            name,
+            default,
+            range,
+            doc: doc_comment(&leading),
+        })
+    }
+
+    /// Get the lowered items of a group.
+    fn items(&self) -> Vec<Spanned<Ast<'src>>> {
+        self.significant()
+            .filter(|elem| {
+                !elem.is_kind(Kind::Skipped)
+                    && !elem.is_token(Token::OpenParen)
+                    && !elem.is_token(Token::Comma)
+                    && !elem.is_token(Token::CloseParen)
+            })
+            .map(Element::to_ast)
+            .collect()
+    }
+}
+
+impl<'src> Element<'src> {
+    pub fn span(&self) -> Span {
+        match self {
+            Element::Node(node) => node.span,
+            Element::Token { span, .. } | Element::Trivia { span, .. } => *span,
+        }
+    }
+
+    fn is_kind(&self, kind: Kind) -> bool {
+        matches!(self, Element::Node(node) if node.kind == kind)
+    }
+
+    fn is_token(&self, token: Token) -> bool {
+        matches!(self, Element::Token { token: tok, .. } if *tok == token)
+    }
+
+    fn to_ast(&self) -> Spanned<Ast<'src>> {
+        let node = match self {
+            Element::Token { token, span, .. } => {
+                let ast = match token {
+                    Token::Ident(name) => Ast::Name(name),
+                    Token::Number(num) => Ast::Number(num),
+                    Token::Symbol(sym) => Ast::Symbol(sym),
+                    Token::String(literal) => match string_contents(literal) {
+                        Some(contents) => Ast::String(contents),
+                        None => Ast::Invalid,
+                    },
+                    _ => Ast::Invalid,
+                };
+
+                return (ast, *span);
+            }
+
+            Element::Trivia { span, .. } => return (Ast::Invalid, *span),
+            Element::Node(node) => node,
+        };
+
+        let mut children = node
+            .significant()
+            .filter(|elem| !elem.is_kind(Kind::Skipped));
+
+        let ast = match node.kind {
+            Kind::Program | Kind::Error | Kind::Skipped | Kind::Range => Ast::Invalid,
+
+            Kind::Param => match node.to_param() {
+                Some(param) => Ast::Param(param),
+                None => Ast::Invalid,
+            },
+
+            Kind::Assign => {
+                let target = children.next().map(Element::to_ast);
+                let value = children
+                    .find(|elem| !elem.is_token(Token::Equal))
+                    .map(Element::to_ast);
+
+                match (target, value) {
+                    (Some(target), Some(value)) => {
+                        let span = target.1 + value.1;
+                        return (Ast::Assign(Box::new(target), Box::new(value)), span);
+                    }
+
+                    _ => Ast::Invalid,
+                }
+            }
+
+            Kind::Pipe => {
+                let nodes: Vec<_> = children
+                    .filter(|elem| !elem.is_token(Token::Pipe))
+                    .map(Element::to_ast)
+                    .collect();
+
+                let span = nodes
+                    .iter()
+                    .map(|(_, span)| *span)
+                    .reduce(|a, b| a + b)
+                    .unwrap_or(node.span);
+
+                return (Ast::Pipe(nodes), span);
+            }
+
+            Kind::Init => {
+                let name = children.next().map(Element::to_ast);
+                let args = match children.next() {
+                    Some(Element::Node(group)) => group.items(),
+                    _ => vec![],
+                };
+
+                match name {
+                    Some(name) if args.is_empty() => return name,
+                    Some(name) => Ast::Init(Box::new(name), args),
+                    None => Ast::Invalid,
+                }
+            }
+
+            Kind::Group => {
+                let mut items = node.items();
+                if items.len() == 1 {
+                    return items.remove(0);
+                }
+
+                Ast::Tuple(items)
+            }
+        };
+
+        (ast, node.span)
+    }
+}
+
+/// Get the lines of the comment which directly precedes some trivia, if any. Each line must start its own line in the
+/// source, and the last must be directly followed by a line break.
+pub(super) fn doc_comment<'src>(trivia: &[(Trivia, &'src str, Span)]) -> Vec<&'src str> {
+    let mut lines = Vec::new();
+    let mut end = trivia.len();
+
+    while let [.., (Trivia::Comment, text, span), (Trivia::Whitespace, space, _)] = trivia[..end] {
+        if space.matches('\n').count() != 1 {
+            break;
+        }
+
+        let starts_line = match end.checked_sub(3).map(|at| trivia[at]) {
+            Some((Trivia::Whitespace, space, _)) => space.contains('\n'),
+            Some(_) => false,
+            None => span.start == 0,
+        };
+
+        if !starts_line {
+            break;
+        }
+
+        lines.push(text.trim_start_matches('-').trim());
+        end -= 2;
+    }
+
+    lines.reverse();
+    lines
+}
+
+impl fmt::Display for Node<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.children
+            .iter()
+            .try_for_each(|elem| write!(f, "{elem}"))
+    }
+}
+
+impl fmt::Display for Element<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Element::Node(node) => write!(f, "{node}"),
+            Element::Token { text, .. } | Element::Trivia { text, .. } => write!(f, "{text}"),
+        }
+    }
+}
+
+/// Incrementally builds a tree as the parser consumes tokens. The trivia between tokens is recovered from the gaps
+/// between their spans.
+#[derive(Debug)]
+pub(super) struct Builder<'src> {
+    text: &'src str,
+    file: SourceId,
+
+    /// The end of the last token or trivia added to the tree.
+    offset: usize,
+
+    stack: Vec<(Kind, Vec<Element<'src>>)>,
+}
+
+/// A position in the tree which a node may later be started at, such that it wraps everything after that position.
+#[derive(Clone, Copy, Debug, Default)]
+pub(super) struct Checkpoint(usize);
+
+impl<'src> Builder<'src> {
+    pub fn new(text: &'src str, file: SourceId) -> Self {
+        Self {
+            text,
+            file,
+            offset: 0,
+            stack: vec![(Kind::Program, Vec::new())],
+        }
+    }
+
+    pub fn start(&mut self, kind: Kind) {
+        self.stack.push((kind, Vec::new()));
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.stack.last().unwrap().1.len())
+    }
+
+    pub fn start_at(&mut self, Checkpoint(at): Checkpoint, kind: Kind) {
+        let children = self.children().split_off(at);
+        self.stack.push((kind, children));
+    }
+
+    pub fn finish(&mut self) {
+        let node = self.make_node();
+        self.children().push(Element::Node(node));
+    }
+
+    pub fn token(&mut self, token: Token<'src>, span: Span) {
+        self.trivia(span.start);
+
+        let text = &self.text[span.start..span.end];
+        self.children().push(Element::Token { token, text, span });
+        self.offset = span.end;
+    }
+
+    /// Finish the tree, adding any trailing trivia to its root.
+    pub fn finish_root(mut self) -> Node<'src> {
+        self.trivia(self.text.len());
+        assert_eq!(1, self.stack.len(), "unfinished nodes");
+        self.make_node()
+    }
+
+    /// Add all the trivia between the current offset and `until`.
+    fn trivia(&mut self, until: usize) {
+        for (trivia, text, span) in split_trivia(self.text, self.offset..until, self.file) {
+            self.children().push(Element::Trivia { trivia, text, span });
+        }
+        self.offset = self.offset.max(until);
+    }
+
+    fn make_node(&mut self) -> Node<'src> {
+        let (kind, children) = self.stack.pop().unwrap();
+
+        let span = children
+            .iter()
+            .filter(|elem| !matches!(elem, Element::Trivia { .. }))
+            .map(Element::span)
+            .reduce(|a, b| a + b)
+            .unwrap_or_else(|| Span::new(self.offset..self.offset, self.file));
+
+        Node {
+            kind,
+            span,
+            children,
+        }
+    }
+
+    fn children(&mut self) -> &mut Vec<Element<'src>> {
+        &mut self.stack.last_mut().unwrap().1
+    }
+}
+
+/// Split the text in the given range, which must lie between two tokens, into whitespace and comments.
+pub(super) fn split_trivia(
+    text: &str,
+    range: Range<usize>,
+    file: SourceId,
+) -> Vec<(Trivia, &str, Span)> {
+    let mut trivia = Vec::new();
+    let mut offset = range.start;
+
+    while offset < range.end {
+        let rest = &text[offset..range.end];
+
+        let (kind, len) = if rest.starts_with("--") {
+            (Trivia::Comment, rest.find('\n').unwrap_or(rest.len()))
+        } else {
+            (Trivia::Whitespace, rest.find("--").unwrap_or(rest.len()))
+        };
+
+        trivia.push((kind, &rest[..len], Span::new(offset..offset + len, file)));
+        offset += len;
+    }
+
+    trivia
+}
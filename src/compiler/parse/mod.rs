@@ -1,29 +1,71 @@
+pub mod cst;
+
 mod ast;
 mod parser;
 mod transform;
 
 use super::Errors;
-use crate::compiler::source::Span;
+use crate::compiler::source::{SourceId, Span};
 use crate::compiler::token::Token;
 use crate::compiler::tree::Program;
 
-pub fn parse<'src, I>(tokens: I, errors: &mut Errors) -> Program<'src>
+/// Parse the tokens lexed from the given source text into a program.
+pub fn parse<'src, I>(text: &'src str, tokens: I, errors: &mut Errors) -> Program<'src>
 where
     I: Iterator<Item = (Token<'src>, Span)>,
 {
-    let mut parser = parser::Parser::new(tokens, errors);
+    let mut parser = parser::Parser::new(text, tokens, errors);
     let program = parser.parse();
 
     let mut transformer = transform::Transformer::new(errors);
     transformer.make_program(program)
 }
 
+/// Parse the tokens lexed from the given source text into a lossless concrete syntax tree, which keeps all tokens,
+/// whitespace and comments. Only syntax errors are reported.
+pub fn parse_cst<'src, I>(
+    text: &'src str,
+    file: SourceId,
+    tokens: I,
+    errors: &mut Errors,
+) -> cst::Node<'src>
+where
+    I: Iterator<Item = (Token<'src>, Span)>,
+{
+    let mut parser = parser::Parser::new(text, tokens, errors).with_cst(file);
+    let _ = parser.parse();
+    parser.into_cst().expect("the parser was asked for a tree")
+}
+
+/// Parse the tokens lexed from the given source text into a concrete syntax tree, along with the program derived from
+/// it.
+pub fn parse_with_cst<'src, I>(
+    text: &'src str,
+    file: SourceId,
+    tokens: I,
+    errors: &mut Errors,
+) -> (Program<'src>, cst::Node<'src>)
+where
+    I: Iterator<Item = (Token<'src>, Span)>,
+{
+    let cst = parse_cst(text, file, tokens, errors);
+    (lower(&cst, errors), cst)
+}
+
+/// Derive a program from a concrete syntax tree, as [`parse`] would from the same tokens.
+pub fn lower<'src>(cst: &cst::Node<'src>, errors: &mut Errors) -> Program<'src> {
+    let mut transformer = transform::Transformer::new(errors);
+    transformer.make_program(cst.to_ast())
+}
+
 #[cfg(test)]
 mod test {
+    use super::cst::{Element, Node, Trivia};
     use super::parser::Parser;
-    use crate::compiler::source::Sources;
+    use super::{parse, parse_cst};
+    use crate::compiler::source::{Source, Sources};
     use crate::compiler::token::lex;
-    use crate::compiler::Errors;
+    use crate::compiler::{tree, Errors};
 
     #[test]
     fn parser_halts_on_success() {
@@ -32,7 +74,7 @@ mod test {
 
         let tokens = lex(&sources, source);
         let mut errors = Errors::new();
-        let mut parser = Parser::new(tokens, &mut errors);
+        let mut parser = Parser::new(&sources.get(&source).content, tokens, &mut errors);
         let _ = parser.parse();
 
         assert!(errors.is_empty());
@@ -45,7 +87,7 @@ mod test {
 
         let tokens = lex(&sources, source);
         let mut errors = Errors::new();
-        let mut parser = Parser::new(tokens, &mut errors);
+        let mut parser = Parser::new(&sources.get(&source).content, tokens, &mut errors);
         let _ = parser.parse();
 
         assert!(errors.is_empty());
@@ -58,7 +100,7 @@ mod test {
 
         let tokens = lex(&sources, source);
         let mut errors = Errors::new();
-        let mut parser = Parser::new(tokens, &mut errors);
+        let mut parser = Parser::new(&sources.get(&source).content, tokens, &mut errors);
         let _ = parser.parse();
 
         assert!(!errors.is_empty());
@@ -71,9 +113,224 @@ mod test {
 
         let tokens = lex(&sources, source);
         let mut errors = Errors::new();
-        let mut parser = Parser::new(tokens, &mut errors);
+        let mut parser = Parser::new(&sources.get(&source).content, tokens, &mut errors);
+        let _ = parser.parse();
+
+        assert!(!errors.is_empty());
+    }
+
+    /// Parse the source, and return the start and end of every reported error.
+    fn error_spans(source: impl Into<Source>) -> Vec<(usize, usize)> {
+        let mut sources = Sources::new();
+        let id = sources.add(source.into());
+
+        let tokens = lex(&sources, id);
+        let mut errors = Errors::new();
+        let mut parser = Parser::new(&sources.get(&id).content, tokens, &mut errors);
+        let _ = parser.parse();
+
+        errors
+            .errors
+            .iter()
+            .map(|error| (error.at.start, error.at.end))
+            .collect()
+    }
+
+    #[test]
+    fn recover_missing_comma() {
+        let source = "a = math(Multiply 2)\nb = voronoi";
+        assert_eq!(vec![(18, 19)], error_spans(source));
+    }
+
+    #[test]
+    fn recover_bad_argument() {
+        let source = "a = math(Multiply, = =, 2)\nb = voronoi";
+        assert_eq!(vec![(19, 20)], error_spans(source));
+    }
+
+    #[test]
+    fn recover_unclosed_paren() {
+        let source = "a = voronoi(50\nb = voronoi(20)\nc = (a, b) -> math(Multiply)";
+        assert_eq!(vec![(11, 12)], error_spans(source));
+    }
+
+    #[test]
+    fn recover_unclosed_paren_at_end() {
+        let source = "a = voronoi(50, 20";
+        assert_eq!(vec![(11, 12)], error_spans(source));
+    }
+
+    #[test]
+    fn recover_stray_close_paren() {
+        let source = "a = voronoi)\nb = simplex";
+        assert_eq!(vec![(11, 12)], error_spans(source));
+    }
+
+    #[test]
+    fn recover_missing_definition() {
+        let source = "a =\nb = voronoi -> linvert";
+        assert_eq!(vec![(4, 5)], error_spans(source));
+    }
+
+    #[test]
+    fn recover_missing_pipe_target() {
+        let source = "a = voronoi -> -> linvert\nb = a ->";
+        assert_eq!(vec![(15, 17), (34, 34)], error_spans(source));
+    }
+
+    #[test]
+    fn recover_garbage_line() {
+        let source = "a = voronoi\n) , = 12 ->\n    -> linvert\nb = a";
+        assert_eq!(vec![(12, 13)], error_spans(source));
+    }
+
+    #[test]
+    fn recover_multiline_args() {
+        let source = "a = math(\n    op = Multiply,\n    2,\n)\nb = a";
+        assert!(error_spans(source).is_empty());
+    }
+
+    #[test]
+    fn recover_malformed_params() {
+        let source = "param a = (1..2)\nparam b = 1 (2 3)\nparam c = 4 x\nb = voronoi(b)";
+        assert_eq!(vec![(10, 11), (32, 33), (47, 48)], error_spans(source));
+    }
+
+    #[test]
+    fn recover_unclosed_string() {
+        let source = "a = image(\"moss.png)\nb = voronoi -> return";
+        assert_eq!(vec![(10, 20), (9, 10)], error_spans(source));
+    }
+
+    #[test]
+    fn errors_list_expected_tokens() {
+        let mut sources = Sources::new();
+        let id = sources.add("a = voronoi(50 =)".into());
+
+        let tokens = lex(&sources, id);
+        let mut errors = Errors::new();
+        let mut parser = Parser::new(&sources.get(&id).content, tokens, &mut errors);
         let _ = parser.parse();
 
+        assert_eq!(1, errors.len());
+        assert_eq!(vec!["','", "')'"], errors.errors[0].expected);
+        assert_eq!(Some("',' or ')'".into()), errors.errors[0].expected_list());
+    }
+
+    #[test]
+    fn cst_is_lossless() {
+        let source = "-- materials\na = voronoi(50,  metric = Manhattan) -> linvert -- dark\n\n(a, ø b -> return\n  ";
+        let mut sources = Sources::new();
+        let id = sources.add(source.into());
+
+        let mut errors = Errors::new();
+        let cst = parse_cst(
+            &sources.get(&id).content,
+            id,
+            lex(&sources, id),
+            &mut errors,
+        );
+
         assert!(!errors.is_empty());
+        assert_eq!(source, cst.to_string());
+    }
+
+    #[test]
+    fn cst_keeps_comments() {
+        let source = "a = voronoi -- smooth\n-- and now\nb = a";
+        let mut sources = Sources::new();
+        let id = sources.add(source.into());
+
+        let mut errors = Errors::new();
+        let cst = parse_cst(
+            &sources.get(&id).content,
+            id,
+            lex(&sources, id),
+            &mut errors,
+        );
+
+        fn comments<'a>(node: &'a Node, into: &mut Vec<&'a str>) {
+            for elem in node.children.iter() {
+                match elem {
+                    Element::Node(node) => comments(node, into),
+                    Element::Trivia {
+                        trivia: Trivia::Comment,
+                        text,
+                        ..
+                    } => into.push(text),
+                    _ => {}
+                }
+            }
+        }
+
+        let mut found = Vec::new();
+        comments(&cst, &mut found);
+
+        assert!(errors.is_empty());
+        assert_eq!(vec!["-- smooth", "-- and now"], found);
+    }
+
+    #[test]
+    fn cst_lowers_like_the_parser() {
+        let fixtures = [
+            "a = voronoi a -> return",
+            "a = (voronoi, simplex) -> math(Multiply) a -> return",
+            "asjfas w309ru 2r3kj ",
+            "(bwuf8soil A1",
+            "a = math(Multiply 2)\nb = voronoi",
+            "a = math(Multiply, = =, 2)\nb = voronoi",
+            "a = voronoi(50\nb = voronoi(20)\nc = (a, b) -> math(Multiply)",
+            "a = voronoi(50, 20",
+            "a = voronoi)\nb = simplex",
+            "a =\nb = voronoi -> linvert",
+            "a = voronoi -> -> linvert\nb = a ->",
+            "a = voronoi\n) , = 12 ->\n    -> linvert\nb = a",
+            "a = math(\n    op = Multiply,\n    2,\n)\nb = a",
+            "param a = (1..2)\nparam b = 1 (2 3)\nparam c = 4 x\nb = voronoi(b)",
+            "a = image(\"moss.png)\nb = voronoi -> return",
+            "-- cells\n-- per side\nparam n = 8 (1..20)\nparam f = 2\na = voronoi(n) -> brighten(f) -- lit\n(a, a) -> mix -> return",
+            "a = image(\"moss.png\") -> resize(width = 64, height = 64)\nb = a -> return",
+            "-- materials\na = voronoi(50,  metric = Manhattan) -> linvert -- dark\n\n(a, ø b -> return\n  ",
+        ];
+
+        for source in fixtures {
+            let mut sources = Sources::new();
+            let id = sources.add(source.into());
+            let text = &sources.get(&id).content;
+
+            let mut errors = Errors::new();
+            let ast = Parser::new(text, lex(&sources, id), &mut errors).parse();
+            let cst = parse_cst(text, id, lex(&sources, id), &mut errors);
+
+            assert_eq!(
+                format!("{ast:?}"),
+                format!("{:?}", cst.to_ast()),
+                "{source:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_builds_program() {
+        let source =
+            "a = voronoi(50, metric = Manhattan) -> linvert\nb = (a, simplex) -> math(Multiply)";
+        let mut sources = Sources::new();
+        let id = sources.add(source.into());
+
+        let mut errors = Errors::new();
+        let program = parse(&sources.get(&id).content, lex(&sources, id), &mut errors);
+
+        assert!(errors.is_empty());
+        assert_eq!(2, program.defs.len());
+
+        let (_, (a, _)) = &program.defs["a"];
+        assert_eq!(2, a.nodes.len());
+        assert!(matches!(
+            &a.nodes[0].0,
+            tree::Node::Init { positional, named, .. } if positional.len() == 1 && named.len() == 1
+        ));
+
+        let (_, (b, _)) = &program.defs["b"];
+        assert!(matches!(&b.nodes[0].0, tree::Node::Tuple(pipes) if pipes.len() == 2));
     }
 }
@@ -1,28 +1,83 @@
 use super::ast::{Ast, Spanned};
-use crate::compiler::token::Token;
+use super::cst::{self, Builder, Checkpoint, Kind, Node};
+use crate::compiler::source::{SourceId, Span};
+use crate::compiler::token::{string_contents, Token};
+use crate::compiler::tree::Param;
 use crate::compiler::Errors;
 
+/// Tokens which may begin a top-level statement.
+const STATEMENT_START: &[Token<'static>] = &[Token::Ident(""), Token::OpenParen];
+
+/// Tokens which may begin an expression.
+const EXPR_START: &[Token<'static>] = &[
+    Token::Ident(""),
+    Token::Number(""),
+    Token::Symbol(""),
+    Token::String(""),
+    Token::OpenParen,
+];
+
+/// Tokens which may follow an argument in an argument list.
+const ARG_END: &[Token<'static>] = &[Token::Comma, Token::CloseParen];
+
 #[derive(Debug)]
 pub struct Parser<'src, 'a, I> {
+    text: &'src str,
     tokens: I,
     errors: &'a mut Errors,
 
     curr: Option<Spanned<Token<'src>>>,
+    next: Option<Spanned<Token<'src>>>,
     prev: Option<Spanned<Token<'src>>>,
+
+    /// The number of tokens consumed so far.
+    consumed: usize,
+
+    /// Records a lossless syntax tree as tokens are consumed, if one was asked for.
+    builder: Option<Builder<'src>>,
+
+    /// The number of currently open groups.
+    depth: usize,
+}
+
+/// The position of the parser at the start of a node, used to find the span of the tokens the node consumes.
+#[derive(Clone, Copy, Debug)]
+struct Mark {
+    consumed: usize,
+    start: Option<Span>,
 }
 
 impl<'src, 'a, I> Parser<'src, 'a, I>
 where
     I: Iterator<Item = Spanned<Token<'src>>>,
 {
-    pub fn new(mut it: I, errors: &'a mut Errors) -> Self {
+    /// Create a parser over the given tokens. The `text` is the source the tokens were lexed from, and is used to find
+    /// line breaks, which serve as synchronisation points when recovering from errors, and doc comments.
+    pub fn new(text: &'src str, mut it: I, errors: &'a mut Errors) -> Self {
         let curr = it.next();
+        let next = it.next();
         Self {
+            text,
             tokens: it,
             errors,
 
             curr,
+            next,
             prev: None,
+
+            consumed: 0,
+            builder: None,
+
+            depth: 0,
+        }
+    }
+
+    /// Also record a lossless concrete syntax tree of the given file while parsing, which is taken with
+    /// [`Parser::into_cst`] once parsing is done.
+    pub fn with_cst(self, file: SourceId) -> Self {
+        Self {
+            builder: Some(Builder::new(self.text, file)),
+            ..self
         }
     }
 
@@ -30,17 +85,30 @@ where
         self.program()
     }
 
+    /// Take the concrete syntax tree recorded while parsing, if one was asked for.
+    pub fn into_cst(self) -> Option<Node<'src>> {
+        self.builder.map(Builder::finish_root)
+    }
+
     fn is_done(&self) -> bool {
         self.curr.is_none()
     }
 
     fn advance(&mut self) {
+        if let (Some(builder), Some((token, span))) = (self.builder.as_mut(), self.curr) {
+            builder.token(token, span);
+        }
+
         self.prev = self.curr.take();
-        self.curr = self.tokens.next();
+        self.curr = self.next.take();
+        self.next = self.tokens.next();
+        self.consumed += 1;
     }
 
     fn peek(&self, matcher: impl Matcher) -> bool {
-        let Some((token, _)) = self.curr.as_ref() else { return false; };
+        let Some((token, _)) = self.curr.as_ref() else {
+            return false;
+        };
         matcher.matches(token)
     }
 
@@ -53,143 +121,411 @@ where
         }
     }
 
+    /// Get the span of the current token, or an empty span just past the previous token if there are no more tokens.
+    fn here(&self) -> Span {
+        match (&self.curr, &self.prev) {
+            (Some((_, span)), _) => *span,
+            (None, Some((_, span))) => Span {
+                start: span.end,
+                end: span.end,
+                file: span.file,
+            },
+            (None, None) => {
+                unreachable!("the parser always consumes a token before reporting an error")
+            }
+        }
+    }
+
+    fn mark(&self) -> Mark {
+        Mark {
+            consumed: self.consumed,
+            start: self.curr.as_ref().map(|(_, span)| *span),
+        }
+    }
+
+    /// Get the span of the tokens consumed since the given mark, or an empty span just past the previous token if none
+    /// were.
+    fn span_since(&self, mark: Mark) -> Span {
+        match (mark.start, &self.prev) {
+            (Some(start), Some((_, end))) if self.consumed > mark.consumed => start + *end,
+            (_, Some((_, prev))) => Span {
+                start: prev.end,
+                end: prev.end,
+                file: prev.file,
+            },
+            (Some(start), None) => Span {
+                start: 0,
+                end: 0,
+                file: start.file,
+            },
+            (None, None) => unreachable!("nodes are only parsed while there are tokens left"),
+        }
+    }
+
+    fn start(&mut self, kind: Kind) {
+        if let Some(builder) = self.builder.as_mut() {
+            builder.start(kind);
+        }
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        self.builder
+            .as_ref()
+            .map(Builder::checkpoint)
+            .unwrap_or_default()
+    }
+
+    fn start_at(&mut self, checkpoint: Checkpoint, kind: Kind) {
+        if let Some(builder) = self.builder.as_mut() {
+            builder.start_at(checkpoint, kind);
+        }
+    }
+
+    fn finish(&mut self) {
+        if let Some(builder) = self.builder.as_mut() {
+            builder.finish();
+        }
+    }
+
+    /// Is the current token the first on its line?
+    fn at_line_start(&self) -> bool {
+        let Some((_, curr)) = self.curr.as_ref() else {
+            return false;
+        };
+        let from = self.prev.as_ref().map(|(_, prev)| prev.end).unwrap_or(0);
+        from == 0 || self.text[from..curr.start].contains('\n')
+    }
+
+    /// Is the current token the start of an unindented definition? Such tokens are never part of an expression or
+    /// argument list, so they are used to stop errors (such as an unclosed group) from spilling onto the following
+    /// statements.
+    fn at_statement_start(&self) -> bool {
+        let Some((Token::Ident(_), curr)) = self.curr.as_ref() else {
+            return false;
+        };
+        let column_zero = curr.start == 0 || self.text[..curr.start].ends_with('\n');
+        column_zero && matches!(self.next, Some((Token::Equal, _)))
+    }
+
+    /// Is the current token one at which expression-level error recovery should stop?
+    fn at_sync_point(&self) -> bool {
+        self.is_done()
+            || self.at_statement_start()
+            || self.peek(Token::Pipe)
+            || (self.depth > 0 && self.peek(ARG_END))
+    }
+
+    /// Skip tokens until a synchronisation point is reached.
+    fn recover(&mut self) {
+        while !self.at_sync_point() {
+            self.advance();
+        }
+    }
+
+    /// Get the lines of the comment directly above the current token.
+    fn doc_comment(&self) -> Vec<&'src str> {
+        let Some((_, curr)) = self.curr.as_ref() else {
+            return vec![];
+        };
+        let from = self.prev.as_ref().map(|(_, prev)| prev.end).unwrap_or(0);
+        cst::doc_comment(&cst::split_trivia(self.text, from..curr.start, curr.file))
+    }
+
     /// ```abnf
-    /// program = *statement
+    /// program = *(param / statement)
     /// ```
     fn program(&mut self) -> Vec<Spanned<Ast<'src>>> {
         let mut res = Vec::new();
 
         while !self.is_done() {
-            if let Some((_, span)) =
-                self.consume(Not([Token::Ident(""), Token::OpenParen].as_slice()))
-            {
-                let span = *span;
-                self.errors.at(span).expected_statement();
+            if !self.peek(STATEMENT_START) {
+                let span = self.here();
+                self.errors.at(span).expected_statement(STATEMENT_START);
+
+                // Skip the rest of the line, until something that looks like the start of a new statement.
+                self.start(Kind::Skipped);
+                self.advance();
+                while !(self.is_done() || (self.at_line_start() && self.peek(STATEMENT_START))) {
+                    self.advance();
+                }
+                self.finish();
+
                 continue;
             }
 
-            res.push(self.statement());
+            if self.at_param() {
+                res.push(self.param());
+            } else {
+                res.push(self.statement());
+            }
         }
 
         res
     }
 
+    /// ```abnf
+    /// param = "param" IDENT "=" NUMBER [range]
+    /// range = "(" NUMBER ".." NUMBER ")"
+    /// ```
+    ///
+    /// The range must start on the same line as the declaration.
+    fn param(&mut self) -> Spanned<Ast<'src>> {
+        let mark = self.mark();
+        let doc = self.doc_comment();
+        self.start(Kind::Param);
+
+        // The keyword and name, which have already been checked.
+        self.advance();
+        let Some(&(Token::Ident(name), name_span)) = self.consume(Token::Ident("")) else {
+            unreachable!("a parameter declaration always has a name");
+        };
+
+        let default = self
+            .expect_param(Token::Equal)
+            .and_then(|_| self.expect_param(Token::Number("")));
+        let mut valid = default.is_some();
+        let mut range = None;
+
+        if valid && self.peek(Token::OpenParen) && !self.at_line_start() {
+            let mark = self.mark();
+            self.start(Kind::Range);
+            self.advance();
+            let min = self.expect_param(Token::Number(""));
+            let max = min
+                .and_then(|_| self.expect_param(Token::Range))
+                .and_then(|_| self.expect_param(Token::Number("")));
+            valid = max.is_some() && self.expect_param(Token::CloseParen).is_some();
+            self.finish();
+
+            if let (Some((Token::Number(min), _)), Some((Token::Number(max), _))) = (min, max) {
+                range = Some(((min, max), self.span_since(mark)));
+            }
+        }
+
+        if valid && !self.is_done() && !self.at_line_start() {
+            let span = self.here();
+            self.errors.at(span).malformed_param(&[]);
+            valid = false;
+        }
+
+        if !valid {
+            // Skip the rest of the line, until something that looks like the start of a new statement.
+            self.start(Kind::Skipped);
+            while !(self.is_done() || (self.at_line_start() && self.peek(STATEMENT_START))) {
+                self.advance();
+            }
+            self.finish();
+        }
+
+        self.finish();
+
+        let ast = match (valid, default) {
+            (true, Some((Token::Number(default), default_span))) => Ast::Param(Param {
+                name: (name, name_span),
+                default: (default, default_span),
+                range,
+                doc,
+            }),
+            _ => Ast::Invalid,
+        };
+
+        (ast, self.span_since(mark))
+    }
+
+    /// Is the current token the start of a parameter declaration? As `param` is not reserved, a definition may still be
+    /// named `param`.
+    fn at_param(&self) -> bool {
+        matches!(self.curr, Some((Token::Ident("param"), _)))
+            && matches!(self.next, Some((Token::Ident(_), _)))
+    }
+
+    /// Consume a token of a parameter declaration, reporting an error if it is missing.
+    fn expect_param(&mut self, token: Token) -> Option<Spanned<Token<'src>>> {
+        if let Some(token) = self.consume(token) {
+            return Some(*token);
+        }
+
+        let span = self.here();
+        self.errors.at(span).malformed_param(&[token]);
+        None
+    }
+
     /// ```abnf
     /// statement = [IDENT "="] pipeline
     /// ```
     fn statement(&mut self) -> Spanned<Ast<'src>> {
-        let target = self.pipeline();
+        let checkpoint = self.checkpoint();
+        let target = self.pipeline(true);
 
-        if matches!(target.0, Ast::Name(_)) && self.consume(Token::Equal).is_some() {
-            let def = self.pipeline();
+        // Only a bare name may be assigned to, not one followed by an empty argument list or wrapped in parentheses.
+        let is_name =
+            matches!(target.0, Ast::Name(_)) && matches!(self.prev, Some((Token::Ident(_), _)));
 
-            let span = target.1 + def.1;
-            (Ast::Assign(Box::new(target), Box::new(def)), span)
-        } else {
-            target
+        if !(is_name && self.peek(Token::Equal)) {
+            return target;
         }
+
+        self.start_at(checkpoint, Kind::Assign);
+        self.advance();
+        let value = self.pipeline(false);
+        self.finish();
+
+        let span = target.1 + value.1;
+        (Ast::Assign(Box::new(target), Box::new(value)), span)
     }
 
     /// ```abnf
     /// pipeline = [pipeline "->"] base-expr
     /// ```
-    fn pipeline(&mut self) -> Spanned<Ast<'src>> {
-        let mut expr = self.base_expr();
-
-        while self.consume(Token::Pipe).is_some() {
-            let next = self.base_expr();
+    ///
+    /// If `leading` is true, this pipeline begins a statement, and so may start with the name of a definition.
+    fn pipeline(&mut self, leading: bool) -> Spanned<Ast<'src>> {
+        let checkpoint = self.checkpoint();
+        let first = self.base_expr(leading);
+
+        if !self.peek(Token::Pipe) {
+            return first;
+        }
 
-            expr = match expr {
-                (Ast::Pipe(mut nodes), span) => {
-                    let span = span + next.1;
-                    nodes.push(next);
-                    (Ast::Pipe(nodes), span)
-                }
+        self.start_at(checkpoint, Kind::Pipe);
 
-                (ast, span) => {
-                    let new_span = span + next.1;
-                    (Ast::Pipe(vec![(ast, span), next]), new_span)
-                }
-            }
+        let mut nodes = vec![first];
+        while self.consume(Token::Pipe).is_some() {
+            nodes.push(self.base_expr(false));
         }
 
-        expr
+        self.finish();
+
+        let span = nodes
+            .iter()
+            .map(|(_, span)| *span)
+            .reduce(|a, b| a + b)
+            .unwrap();
+        (Ast::Pipe(nodes), span)
     }
 
     /// ```abnf
     /// base-expr  = IDENT [args]
-    /// base-expr =/ NUMBER / SYMBOL
+    /// base-expr =/ NUMBER / SYMBOL / STRING
     /// base-expr =/ args
     /// ```
-    fn base_expr(&mut self) -> Spanned<Ast<'src>> {
-        if let Some(tok) =
-            self.consume([Token::Ident(""), Token::Number(""), Token::Symbol("")].as_slice())
-        {
-            let name = match tok {
-                (Token::Ident(name), span) => (Ast::Name(name), *span),
-                (Token::Number(num), span) => return (Ast::Number(num), *span),
-                (Token::Symbol(sym), span) => return (Ast::Symbol(sym), *span),
-                _ => unreachable!(),
-            };
-
-            let args = self.args();
-            if args.is_empty() {
-                name
-            } else {
-                let span = args
-                    .iter()
-                    .map(|(_, span)| *span)
-                    .reduce(|a, b| a + b)
-                    .unwrap();
+    fn base_expr(&mut self, leading: bool) -> Spanned<Ast<'src>> {
+        let mark = self.mark();
 
-                (Ast::Init(Box::new(name), args), span)
-            }
-        } else {
-            let mut args = self.args();
+        if !self.peek(EXPR_START) || (!leading && self.at_statement_start()) {
+            let span = self.here();
+            self.errors.at(span).expected_expression(EXPR_START);
 
-            if args.len() == 1 {
-                args.remove(0)
-            } else {
-                let span = args
-                    .iter()
-                    .map(|(_, span)| *span)
-                    .reduce(|a, b| a + b)
-                    .or_else(|| self.prev.as_ref().map(|(_, span)| *span))
-                    .or_else(|| self.curr.as_ref().map(|(_, span)| *span))
-                    .unwrap();
-                (Ast::Tuple(args), span)
+            self.start(Kind::Error);
+            self.recover();
+            self.finish();
+            return (Ast::Invalid, self.span_since(mark));
+        }
+
+        if self.peek(Token::OpenParen) {
+            let mut items = self.args();
+            if items.len() == 1 {
+                return items.remove(0);
             }
+
+            return (Ast::Tuple(items), self.span_since(mark));
         }
+
+        let checkpoint = self.checkpoint();
+        self.advance();
+        let Some((token, span)) = self.prev else {
+            unreachable!("a token was just consumed");
+        };
+
+        let ast = match token {
+            Token::Ident(name) if self.peek(Token::OpenParen) => {
+                self.start_at(checkpoint, Kind::Init);
+                let args = self.args();
+                self.finish();
+
+                if args.is_empty() {
+                    return (Ast::Name(name), span);
+                }
+
+                let name = Box::new((Ast::Name(name), span));
+                return (Ast::Init(name, args), self.span_since(mark));
+            }
+
+            Token::Ident(name) => Ast::Name(name),
+            Token::Number(num) => Ast::Number(num),
+            Token::Symbol(sym) => Ast::Symbol(sym),
+
+            Token::String(literal) => match string_contents(literal) {
+                Some(contents) => Ast::String(contents),
+                None => {
+                    self.errors.at(span).unclosed_string();
+                    Ast::Invalid
+                }
+            },
+
+            _ => unreachable!("every token which may begin an expression is handled"),
+        };
+
+        (ast, span)
     }
 
     /// ```abnf
     /// args = "(" [statement *("," statement) [","]] ")"
     /// ```
+    ///
+    /// An argument list is ended early if an unindented definition is encountered, such that an unclosed group does
+    /// not swallow the rest of the program.
     fn args(&mut self) -> Vec<Spanned<Ast<'src>>> {
-        if let Some((_, opener)) = self.consume(Token::OpenParen) {
-            let opener = *opener;
-            let mut res = Vec::new();
-            let mut closed = false;
-
-            while !self.is_done() {
-                if self.consume(Token::CloseParen).is_some() {
-                    closed = true;
-                    break;
-                }
+        self.start(Kind::Group);
 
-                res.push(self.statement());
+        let Some((_, opener)) = self.consume(Token::OpenParen) else {
+            unreachable!("an argument list always starts with a parenthesis");
+        };
 
-                let _ = self.consume(Token::Comma);
+        let opener = *opener;
+        let mut items = Vec::new();
+        let mut closed = false;
+
+        self.depth += 1;
+
+        while !self.is_done() && !self.at_statement_start() {
+            if self.consume(Token::CloseParen).is_some() {
+                closed = true;
+                break;
             }
 
-            if !closed {
-                self.errors.at(opener).unclosed_paren();
+            items.push(self.statement());
+
+            if self.consume(Token::Comma).is_some()
+                || self.peek(Token::CloseParen)
+                || self.is_done()
+                || self.at_statement_start()
+            {
+                continue;
             }
 
-            res
-        } else {
-            vec![]
+            // A missing comma is likely a typo, so keep parsing the next argument as if it were there. Anything else
+            // is skipped.
+            let span = self.here();
+            self.errors.at(span).expected_separator(ARG_END);
+
+            if !self.peek(EXPR_START) {
+                self.start(Kind::Skipped);
+                self.advance();
+                self.recover();
+                self.finish();
+                let _ = self.consume(Token::Comma);
+            }
+        }
+
+        self.depth -= 1;
+
+        if !closed {
+            self.errors.at(opener).unclosed_paren();
         }
+
+        self.finish();
+        items
     }
 }
 
@@ -203,6 +539,7 @@ impl Matcher for Token<'_> {
             (Token::Ident(..), Token::Ident(..)) => true,
             (Token::Number(..), Token::Number(..)) => true,
             (Token::Symbol(..), Token::Symbol(..)) => true,
+            (Token::String(..), Token::String(..)) => true,
             (t, u) if t == u => true,
             _ => false,
         }
@@ -214,11 +551,3 @@ impl Matcher for &[Token<'_>] {
         self.iter().any(|tok| tok.matches(token))
     }
 }
-
-struct Not<T>(T);
-
-impl<T: Matcher> Matcher for Not<T> {
-    fn matches(&self, token: &Token) -> bool {
-        !self.0.matches(token)
-    }
-}
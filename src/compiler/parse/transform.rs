@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 use super::ast::Ast;
-use crate::compiler::tree::{Node, Pipeline, Program, Spanned};
+use crate::compiler::tree::{Node, Param, Pipeline, Program, Spanned};
 use crate::compiler::{Errors, Value};
 
 #[derive(Debug)]
@@ -17,6 +17,7 @@ impl<'a> Transformer<'a> {
     pub fn make_program<'src>(&mut self, ast: Vec<Spanned<Ast<'src>>>) -> Program<'src> {
         let mut defs = HashMap::new();
         let mut results = Vec::new();
+        let mut params: Vec<Spanned<Param>> = Vec::new();
 
         for ast in ast {
             match ast.0 {
@@ -44,13 +45,34 @@ impl<'a> Transformer<'a> {
                     }
                 }
 
+                // `pipeline -> return`
+                Ast::Pipe(mut nodes) if matches!(nodes.last(), Some((Ast::Name("return"), _))) => {
+                    nodes.pop();
+                    let value = self.make_pipeline((Ast::Pipe(nodes), ast.1));
+                    results.push(value);
+                }
+
+                Ast::Param(param) => {
+                    if params.iter().any(|(other, _)| other.name.0 == param.name.0) {
+                        self.errors.at(param.name.1).repeated_param(param.name.0);
+                    } else {
+                        params.push((param, ast.1));
+                    }
+                }
+
+                Ast::Invalid => {}
+
                 _ => {
                     self.errors.at(ast.1).expected_definition();
                 }
             }
         }
 
-        Program { defs, results }
+        Program {
+            defs,
+            results,
+            params,
+        }
     }
 
     fn make_pipeline<'src>(&mut self, ast: Spanned<Ast<'src>>) -> Spanned<Pipeline<'src>> {
@@ -71,6 +93,7 @@ impl<'a> Transformer<'a> {
                 Node::Invalid
             }
             Ast::Name(name) => Node::Name(name),
+            Ast::Invalid => Node::Invalid,
             Ast::Init(target, args) => {
                 let name = match *target {
                     (Ast::Name(name), span) => (name, span),
@@ -95,7 +118,12 @@ impl<'a> Transformer<'a> {
                 Node::Tuple(pipelines)
             }
 
-            Ast::Assign(..) | Ast::Number(..) | Ast::Pipe(..) | Ast::Symbol(..) => {
+            Ast::Assign(..)
+            | Ast::Number(..)
+            | Ast::Param(..)
+            | Ast::Pipe(..)
+            | Ast::String(..)
+            | Ast::Symbol(..) => {
                 self.errors.at(span).expected_node();
                 Node::Invalid
             }
@@ -154,6 +182,9 @@ impl<'a> Transformer<'a> {
         let value = match ast.0 {
             Ast::Number(num) => Value::Number(num),
             Ast::Symbol(sym) => Value::Symbol(sym),
+            Ast::String(contents) => Value::String(contents),
+            Ast::Name(name) => Value::Param(name),
+            Ast::Invalid => return None,
 
             _ => {
                 self.errors.at(span).expected_value();
@@ -5,26 +5,48 @@ use std::collections::{HashMap, HashSet};
 
 use self::cycles::Cycles;
 use super::graph::NodeId;
-use crate::compiler::graph::{Graph, Node};
-use crate::compiler::tree;
-use crate::compiler::Errors;
+use crate::compiler::graph::{self, Graph, Node};
+use crate::compiler::source::Span;
+use crate::compiler::tree::{self, Spanned};
+use crate::compiler::{Errors, Params, Value, DEFAULTS};
+use crate::nodes::{builtin, NodeInfo, ParamKind, TextureKind};
 
-pub fn resolve(program: tree::Program) -> Graph {
-    Resolver::resolve(program)
+pub fn resolve<'src>(program: tree::Program<'src>, errors: &mut Errors) -> Graph<'src> {
+    Resolver::resolve(program, &DEFAULTS, errors)
 }
 
-struct Resolver<'src> {
+/// Resolve a program, giving its parameters the values in `params` rather than their defaults.
+pub fn resolve_with_params<'src>(
+    program: tree::Program<'src>,
+    params: &'src Params,
+    errors: &mut Errors,
+) -> Graph<'src> {
+    Resolver::resolve(program, params, errors)
+}
+
+struct Resolver<'src, 'a> {
     graph: Graph<'src>,
+    errors: &'a mut Errors,
 
     cycles: HashSet<&'src str>,
+    defined: HashSet<&'src str>,
     names: HashMap<&'src str, Vec<NodeId>>,
+
+    /// The kind of texture each node outputs, where it is known.
+    kinds: HashMap<NodeId, TextureKind>,
+
+    /// The value of each declared parameter, or `None` if its declaration is invalid.
+    params: HashMap<&'src str, Option<Value<'src>>>,
 }
 
-impl<'src> Resolver<'src> {
-    pub fn resolve(mut program: tree::Program<'src>) -> Graph<'src> {
+impl<'src, 'a> Resolver<'src, 'a> {
+    pub fn resolve(
+        mut program: tree::Program<'src>,
+        params: &'src Params,
+        errors: &'a mut Errors,
+    ) -> Graph<'src> {
         let cycles = Cycles::find(&program);
 
-        let mut errors = Errors::new();
         let mut in_cycles = HashSet::new();
 
         let mut worklist = Vec::new();
@@ -49,70 +71,618 @@ impl<'src> Resolver<'src> {
 
         let mut resolver = Self {
             graph: Graph::new(),
+            errors,
             cycles: in_cycles,
+            defined: program.defs.keys().copied().collect(),
             names: HashMap::new(),
+            kinds: HashMap::new(),
+            params: HashMap::new(),
         };
 
+        for param in program.params {
+            let given = params.get(param.0.name.0);
+            resolver.resolve_param(param, given);
+        }
+
         for name in worklist {
             if let Some((_, (pipeline, _))) = program.defs.remove(&name) {
-                let node = resolver.resolve_pipeline(pipeline);
+                let node = resolver.resolve_pipeline(pipeline, vec![]);
+                resolver.graph.add_definition(name, node.clone());
                 resolver.names.insert(name, node);
             }
         }
 
+        for (pipeline, _) in program.results {
+            for id in resolver.resolve_pipeline(pipeline, vec![]) {
+                resolver.graph.add_output(id);
+            }
+        }
+
         resolver.graph
     }
 
-    fn resolve_pipeline(&mut self, pipeline: tree::Pipeline<'src>) -> Vec<NodeId> {
-        let mut result = vec![];
+    /// Find the value of a parameter, checking that both its declaration and any value it is given are valid.
+    fn resolve_param(
+        &mut self,
+        (param, span): Spanned<tree::Param<'src>>,
+        given: Option<&'src str>,
+    ) {
+        let (name, name_span) = param.name;
+        self.params.insert(name, None);
 
-        for node in pipeline.nodes {
-            let ids = self.resolve_node(node.0);
+        let Some(default) = Value::Number(param.default.0).number() else {
+            self.errors.at(param.default.1).expected_number();
+            return;
+        };
+
+        let range = match param.range {
+            Some(((min, max), range_span)) => {
+                match (Value::Number(min).number(), Value::Number(max).number()) {
+                    (Some(min), Some(max)) if min <= max => Some(((min, max), range_span)),
+                    (Some(_), Some(_)) => {
+                        self.errors.at(range_span).reversed_range();
+                        return;
+                    }
+                    _ => {
+                        self.errors.at(range_span).expected_number();
+                        return;
+                    }
+                }
+            }
+            None => None,
+        };
 
-            for from in result {
-                for to in ids.iter() {
-                    self.graph.add_edge(from, *to);
+        let mut record = graph::Param {
+            name: name.into(),
+            doc: param.doc.join("\n"),
+            default,
+            range: range.map(|(range, _)| range),
+            value: default,
+            span,
+        };
+
+        let (text, value) = match given {
+            Some(text) => match Value::Number(text).number() {
+                Some(value) if value.is_finite() => (text, value),
+                Some(_) => {
+                    self.errors
+                        .at(name_span)
+                        .param_override_not_finite(name, text);
+                    self.graph.add_param(record);
+                    return;
+                }
+                None => {
+                    self.errors
+                        .at(name_span)
+                        .param_override_not_number(name, text);
+                    self.graph.add_param(record);
+                    return;
                 }
+            },
+            None => (param.default.0, default),
+        };
+
+        if let Some(((min, max), range_span)) = range {
+            if !(min..=max).contains(&value) {
+                let at = if given.is_some() {
+                    range_span
+                } else {
+                    param.default.1
+                };
+                self.errors
+                    .at(at)
+                    .param_out_of_range(name, text, given.is_some());
+                self.graph.add_param(record);
+                return;
+            }
+        }
+
+        record.value = value;
+        self.params.insert(name, Some(Value::Number(text)));
+        self.graph.add_param(record);
+    }
+
+    /// Replace a reference to a parameter with the parameter's value. References to unknown or invalid parameters are
+    /// kept, and are never valid arguments.
+    fn substitute(&mut self, (value, span): Spanned<Value<'src>>) -> Spanned<Value<'src>> {
+        let Value::Param(name) = value else {
+            return (value, span);
+        };
+
+        match self.params.get(name) {
+            Some(Some(value)) => (value.clone(), span),
+            Some(None) => (Value::Param(name), span),
+            None => {
+                self.errors.at(span).unknown_param(name);
+                (Value::Param(name), span)
             }
+        }
+    }
+
+    fn resolve_pipeline(
+        &mut self,
+        pipeline: tree::Pipeline<'src>,
+        inputs: Vec<NodeId>,
+    ) -> Vec<NodeId> {
+        let mut result = inputs;
 
-            result = ids;
+        for node in pipeline.nodes {
+            result = self.resolve_node(node, result);
         }
 
         result
     }
 
-    fn resolve_node(&mut self, node: tree::Node<'src>) -> Vec<NodeId> {
+    fn resolve_node(
+        &mut self,
+        (node, span): Spanned<tree::Node<'src>>,
+        inputs: Vec<NodeId>,
+    ) -> Vec<NodeId> {
         match node {
             tree::Node::Init {
                 name,
                 named,
                 positional,
             } => {
+                let positional: Vec<_> = positional
+                    .into_iter()
+                    .map(|value| self.substitute(value))
+                    .collect();
+                let named: HashMap<_, _> = named
+                    .into_iter()
+                    .map(|(name, (name_span, value))| (name, (name_span, self.substitute(value))))
+                    .collect();
+
+                let info = builtin(name.0);
+                match info {
+                    Some(info) => {
+                        self.check_inputs(info, span, inputs.len());
+                        self.check_args(info, span, &positional, &named);
+                    }
+                    None if self.defined.contains(name.0) => {
+                        self.errors.at(span).args_on_definition(name.0)
+                    }
+                    None => self.errors.at(name.1).unknown_name(name.0),
+                }
+
                 let positional = positional.into_iter().map(|(value, _)| value).collect();
                 let named = named
                     .into_iter()
                     .map(|(name, (_, (value, _)))| (name, value));
+                let node = Node::with_args(name.0, positional, named);
 
-                vec![self
-                    .graph
-                    .add_node(Node::with_args(name.0, positional, named))]
+                match info {
+                    Some(info) => self.add_builtin(info, node, inputs, span),
+                    None => self.add_node(node, inputs, span),
+                }
             }
 
-            tree::Node::Invalid => vec![self.graph.add_node(Node::simple("invalid"))],
+            tree::Node::Invalid => self.add_node(Node::simple("invalid"), inputs, span),
 
             tree::Node::Name(name) if self.cycles.contains(&name) => {
-                vec![self.graph.add_node(Node::simple("invalid"))]
+                self.add_node(Node::simple("invalid"), inputs, span)
             }
 
             tree::Node::Name(name) => match self.names.get(&name) {
-                Some(ids) => ids.clone(),
-                None => vec![self.graph.add_node(Node::simple(name))],
+                Some(ids) => {
+                    if !inputs.is_empty() {
+                        self.errors.at(span).input_to_definition(name);
+                    }
+
+                    ids.clone()
+                }
+
+                None => match builtin(name) {
+                    Some(info) => {
+                        self.check_inputs(info, span, inputs.len());
+                        self.check_args(info, span, &[], &HashMap::new());
+                        self.add_builtin(info, Node::simple(name), inputs, span)
+                    }
+                    None => {
+                        if !self.defined.contains(name) {
+                            self.errors.at(span).unknown_name(name);
+                        }
+
+                        self.add_node(Node::simple(name), inputs, span)
+                    }
+                },
             },
 
             tree::Node::Tuple(pipes) => pipes
                 .into_iter()
-                .flat_map(|(pipe, _)| self.resolve_pipeline(pipe))
+                .flat_map(|(pipe, _)| self.resolve_pipeline(pipe, inputs.clone()))
                 .collect(),
         }
     }
+
+    fn add_node(&mut self, node: Node<'src>, inputs: Vec<NodeId>, span: Span) -> Vec<NodeId> {
+        let id = self.graph.add_node(node, inputs);
+        self.graph.add_span(id, span);
+        vec![id]
+    }
+
+    /// Add a built-in node, checking the kinds of texture it is given and converting inputs which need it. Nodes given
+    /// inputs of unknown kinds, or the wrong number of inputs, are added as they are.
+    fn add_builtin(
+        &mut self,
+        info: &NodeInfo,
+        node: Node<'src>,
+        inputs: Vec<NodeId>,
+        span: Span,
+    ) -> Vec<NodeId> {
+        let given: Option<Vec<_>> = inputs
+            .iter()
+            .map(|id| self.kinds.get(id).copied())
+            .collect();
+        let mut output = info.output;
+        let mut inputs = inputs;
+
+        if let Some(given) = given.filter(|given| given.len() == info.kind.inputs()) {
+            match info.check(&given) {
+                Ok((conversions, kind)) => {
+                    inputs = inputs
+                        .into_iter()
+                        .zip(conversions)
+                        .map(|(id, conversion)| match conversion {
+                            Some(conversion) => {
+                                let converted = Node::simple(conversion.node());
+                                let ids = self.add_node(converted, vec![id], span);
+                                self.kinds.insert(ids[0], conversion.to());
+                                ids[0]
+                            }
+                            None => id,
+                        })
+                        .collect();
+                    output = Some(kind);
+                }
+                Err(mismatch) => self.errors.at(span).wrong_kind(info.name, &mismatch),
+            }
+        }
+
+        let ids = self.add_node(node, inputs, span);
+        if let Some(kind) = output {
+            self.kinds.insert(ids[0], kind);
+        }
+
+        ids
+    }
+
+    /// Check that a built-in node is given as many inputs as it takes.
+    fn check_inputs(&mut self, info: &NodeInfo, span: Span, given: usize) {
+        let expected = info.kind.inputs();
+        if given != expected {
+            self.errors
+                .at(span)
+                .wrong_inputs(info.name, expected, given);
+        }
+    }
+
+    /// Check that the arguments given to a built-in node match its parameters.
+    fn check_args(
+        &mut self,
+        info: &NodeInfo,
+        span: Span,
+        positional: &[Spanned<Value<'src>>],
+        named: &HashMap<&'src str, (Span, Spanned<Value<'src>>)>,
+    ) {
+        if positional.len() > info.params.len() {
+            let extra = positional[info.params.len()..]
+                .iter()
+                .map(|(_, span)| *span)
+                .reduce(|a, b| a + b)
+                .unwrap();
+            self.errors
+                .at(extra)
+                .too_many_arguments(info.name, info.params.len());
+        }
+
+        let mut given: Vec<_> = info.params.iter().zip(positional.iter()).collect();
+
+        let mut named: Vec<_> = named.iter().collect();
+        named.sort_by_key(|(_, (span, _))| span.start);
+
+        for (name, (name_span, value)) in named {
+            match info.param(name) {
+                Some(param) if given.iter().any(|(given, _)| given.name == param.name) => {
+                    self.errors.at(*name_span).argument_repeated(name)
+                }
+
+                Some(param) => given.push((param, value)),
+                None => self.errors.at(*name_span).unknown_argument(info.name, name),
+            }
+        }
+
+        for param in info.params.iter() {
+            if param.default.is_none() && !given.iter().any(|(given, _)| given.name == param.name) {
+                self.errors.at(span).missing_argument(info.name, param.name);
+            }
+        }
+
+        for (param, (value, span)) in given {
+            match (param.kind, value) {
+                // Already reported when substituting.
+                (_, Value::Param(_)) => {}
+
+                (ParamKind::Number, Value::Number(_)) if value.number().is_some() => {}
+                (ParamKind::Number, _) => self.errors.at(*span).expected_number(),
+
+                (ParamKind::Positive, Value::Number(_)) => match value.number() {
+                    Some(number) if number > 0.0 => {}
+                    Some(_) => self.errors.at(*span).expected_positive(),
+                    None => self.errors.at(*span).expected_number(),
+                },
+                (ParamKind::Positive, _) => self.errors.at(*span).expected_number(),
+
+                (ParamKind::Symbol(options), Value::Symbol(sym)) if !options.contains(sym) => {
+                    self.errors.at(*span).unknown_symbol(sym, options)
+                }
+
+                (ParamKind::Symbol(_), Value::Symbol(_)) => {}
+                (ParamKind::Symbol(options), _) => self.errors.at(*span).expected_symbol(options),
+
+                (ParamKind::String, Value::String(_)) => {}
+                (ParamKind::String, _) => self.errors.at(*span).expected_string(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve, resolve_with_params};
+    use crate::compiler::parse::parse;
+    use crate::compiler::source::Sources;
+    use crate::compiler::token::lex;
+    use crate::compiler::{Errors, Params};
+
+    fn check(source: &str) -> (usize, Vec<String>) {
+        let mut sources = Sources::new();
+        let id = sources.add(source.into());
+
+        let mut errors = Errors::new();
+        let program = parse(&sources.get(&id).content, lex(&sources, id), &mut errors);
+        let graph = resolve(program, &mut errors);
+
+        // Definitions are resolved in no particular order, so neither are their errors.
+        let mut messages: Vec<_> = errors
+            .errors
+            .into_iter()
+            .map(|error| error.message)
+            .collect();
+        messages.sort();
+        (graph.outputs().len(), messages)
+    }
+
+    #[test]
+    fn resolve_readme_example() {
+        let source = "a = voronoi(50) -> invert\nb = voronoi(20)\nc = (a, b) -> math(Multiply)\n\n(c, random) -> mix -> return";
+        assert_eq!((1, vec![]), check(source));
+    }
+
+    #[test]
+    fn resolve_reports_unknown_names() {
+        let (_, errors) = check("a = voronio -> b\nc = a(2)");
+        assert_eq!(
+            vec![
+                "unexpected arguments",
+                "unknown name 'b'",
+                "unknown name 'voronio'",
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn resolve_checks_arguments() {
+        let (_, errors) = check(
+            "a = voronoi(1, Manhattan, 3)\nb = voronoi(metric = 2, points = 2, size = 3)\nc = (a, b) -> math(Power)\nd = (a, b) -> math\ne = a -> brighten(0)",
+        );
+
+        assert_eq!(
+            vec![
+                "'voronoi' has no parameter named 'size'",
+                "expected a positive number",
+                "expected a symbol",
+                "missing argument 'op' for 'math'",
+                "too many arguments",
+                "unknown symbol 'Power'",
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn resolve_checks_strings() {
+        let (_, errors) = check("a = image(3)\nb = image(\"a.png\", \"Nearest\")\nc = voronoi(\"5\")\nd = image(\"a\\\"b.png\")");
+
+        assert_eq!(
+            vec![
+                "expected a number",
+                "expected a string",
+                "expected a symbol"
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn resolve_checks_inputs() {
+        let (_, errors) = check(
+            "a = invert
+b = voronoi -> voronoi
+c = b -> mix
+d = random -> a",
+        );
+
+        assert_eq!(
+            vec![
+                "unexpected input",
+                "wrong number of inputs",
+                "wrong number of inputs",
+                "wrong number of inputs",
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn resolve_checks_kinds() {
+        let (_, errors) = check(
+            "a = cells -> gradient
+b = (cells, voronoi) -> math(Add)
+c = cells -> invert
+d = (simplex, voronoi -> gradient) -> mix
+e = (simplex, voronoi) -> dot3 -> grayscale -> gradient",
+        );
+
+        assert_eq!(vec!["wrong kind of texture"; 4], errors);
+    }
+
+    #[test]
+    fn resolve_converts_kinds() {
+        let mut sources = Sources::new();
+        let id = sources.add(
+            "(simplex -> gradient, (voronoi, simplex) -> over, (voronoi, voronoi(5)) -> mix) -> return"
+                .into(),
+        );
+
+        let mut errors = Errors::new();
+        let program = parse(&sources.get(&id).content, lex(&sources, id), &mut errors);
+        let graph = resolve(program, &mut errors);
+        assert!(errors.is_empty());
+
+        // Colours are only converted to scalars where nothing else is taken, and scalars mixed together stay scalars.
+        assert_eq!(
+            "%0 = simplex
+%1 = grayscale <- %0
+%2 = gradient <- %1
+%3 = voronoi
+%4 = broadcast <- %3
+%5 = over <- %4, %0
+%6 = voronoi(5)
+%7 = mix <- %3, %6
+return %2
+return %5
+return %7
+",
+            graph.to_string()
+        );
+    }
+
+    #[test]
+    fn resolve_substitutes_params() {
+        let source = "-- cells\nparam density = 20 (1..200)\nvoronoi(density) -> return";
+        let graph = |params: &Params| {
+            let mut sources = Sources::new();
+            let id = sources.add(source.into());
+
+            let mut errors = Errors::new();
+            let program = parse(&sources.get(&id).content, lex(&sources, id), &mut errors);
+            let graph = resolve_with_params(program, params, &mut errors);
+
+            let messages: Vec<_> = errors.errors.iter().map(|e| e.message.clone()).collect();
+            let node = graph.node(graph.outputs()[0]).to_string();
+            let params: Vec<_> = graph
+                .params()
+                .iter()
+                .map(|param| (param.name.clone(), param.doc.clone(), param.value))
+                .collect();
+            (node, params, messages)
+        };
+
+        let (node, params, errors) = graph(&Params::new());
+        assert_eq!("voronoi(20)", node);
+        assert_eq!(vec![("density".into(), "cells".into(), 20.0)], params);
+        assert!(errors.is_empty());
+
+        let (node, _, errors) = graph(&Params::new().with("density", 50.0));
+        assert_eq!("voronoi(50)", node);
+        assert!(errors.is_empty());
+
+        let (_, params, errors) = graph(&Params::new().with("density", 500.0));
+        assert_eq!(20.0, params[0].2);
+        assert_eq!(vec!["parameter out of range"], errors);
+
+        for value in ["nan", "inf", "-inf", "NaN"] {
+            let mut params = Params::new();
+            params.set("density", value);
+
+            let (_, params, errors) = graph(&params);
+            assert_eq!(20.0, params[0].2);
+            assert_eq!(vec!["expected a finite number"], errors);
+        }
+
+        let (_, _, errors) = graph(&Params::new().with("density", f64::INFINITY));
+        assert_eq!(vec!["expected a finite number"], errors);
+    }
+
+    #[test]
+    fn resolve_checks_params() {
+        let (_, errors) =
+            check("param a = 5 (1..3)\nparam b = 1 (3..1)\nparam a = 2\nc = voronoi(a) -> mix(d)");
+
+        assert_eq!(
+            vec![
+                "parameter 'a' declared more than once",
+                "parameter out of range",
+                "reversed range",
+                "unknown parameter 'd'",
+                "wrong number of inputs",
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn resolve_shares_equal_nodes() {
+        let mut sources = Sources::new();
+        let id = sources.add(
+            "a = voronoi -> invert
+b = simplex -> invert
+c = voronoi -> invert"
+                .into(),
+        );
+
+        let mut errors = Errors::new();
+        let program = parse(&sources.get(&id).content, lex(&sources, id), &mut errors);
+        let graph = resolve(program, &mut errors);
+
+        assert_eq!(4, graph.len());
+        assert_eq!(graph.definition("a"), graph.definition("c"));
+        assert_ne!(graph.definition("a"), graph.definition("b"));
+
+        let invert = graph.definition("a").unwrap()[0];
+        let spans: Vec<_> = graph
+            .spans(invert)
+            .iter()
+            .map(|span| sources.location(*span))
+            .collect();
+        assert_eq!(2, spans.len());
+        assert!(spans.contains(&"<input>:1:16".to_string()));
+        assert!(spans.contains(&"<input>:3:16".to_string()));
+    }
+
+    #[test]
+    fn hashes_survive_unrelated_edits() {
+        let hashes = |source: &str| {
+            let mut sources = Sources::new();
+            let id = sources.add(source.into());
+
+            let mut errors = Errors::new();
+            let program = parse(&sources.get(&id).content, lex(&sources, id), &mut errors);
+            let graph = resolve(program, &mut errors);
+
+            let hashes = graph.hashes();
+            let results: Vec<_> = graph.outputs().iter().map(|id| hashes[id]).collect();
+            results
+        };
+
+        let before =
+            hashes("(a, b) -> mix -> return\na -> invert -> return\na = voronoi\nb = simplex(2)");
+        let after =
+            hashes("(a, b) -> mix -> return\na -> invert -> return\nb = simplex(3)\na = voronoi");
+
+        assert_ne!(before[0], after[0]);
+        assert_eq!(before[1], after[1]);
+    }
 }
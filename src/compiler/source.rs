@@ -36,12 +36,37 @@ impl Add for Span {
 
 #[derive(Debug)]
 pub struct Source {
+    pub name: Option<String>,
     pub content: String,
 }
 
+impl Source {
+    pub fn with_name(self, name: impl Into<String>) -> Self {
+        Self {
+            name: Some(name.into()),
+            ..self
+        }
+    }
+
+    /// Get the one-based line and column of the given byte offset.
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let before = &self.content[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|at| at + 1).unwrap_or(0);
+        let column = before[line_start..].chars().count() + 1;
+        (line, column)
+    }
+
+    /// Get the text of the given one-based line, without its line break.
+    pub fn line(&self, line: usize) -> &str {
+        self.content.lines().nth(line - 1).unwrap_or("")
+    }
+}
+
 impl<S: Into<String>> From<S> for Source {
     fn from(content: S) -> Self {
         Self {
+            name: None,
             content: content.into(),
         }
     }
@@ -71,4 +96,12 @@ impl Sources {
     pub fn get(&self, id: &SourceId) -> &Source {
         self.sources.get(id).unwrap()
     }
+
+    /// Describe where a span starts, as `name:line:column`.
+    pub fn location(&self, span: Span) -> String {
+        let source = self.get(&span.file);
+        let name = source.name.as_deref().unwrap_or("<input>");
+        let (line, column) = source.position(span.start);
+        format!("{name}:{line}:{column}")
+    }
 }
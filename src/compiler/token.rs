@@ -2,7 +2,7 @@ use logos::Logos;
 
 use crate::compiler::source::{SourceId, Sources, Span};
 
-pub fn lex(sources: &Sources, id: SourceId) -> impl Iterator<Item = (Token, Span)> + '_ {
+pub fn lex(sources: &Sources, id: SourceId) -> impl Iterator<Item = (Token<'_>, Span)> + '_ {
     let source = sources.get(&id);
     Token::lexer(&source.content)
         .spanned()
@@ -20,6 +20,11 @@ pub enum Token<'src> {
     #[regex(r"[+\-]?[0-9][0-9_']*(\.[0-9][0-9_']*)?([eE][+\-]?[0-9][0-9_']*)?%?", |lex| lex.slice())]
     Number(&'src str),
 
+    /// A string literal, along with its quotes. Backslashes escape quotes and other backslashes. The closing quote may
+    /// be missing, which the parser reports.
+    #[regex(r#""([^"\\\n]|\\[^\n])*"?"#, |lex| lex.slice())]
+    String(&'src str),
+
     #[token("(")]
     OpenParen,
 
@@ -35,15 +40,67 @@ pub enum Token<'src> {
     #[token("->")]
     Pipe,
 
+    #[token("..")]
+    Range,
+
     #[regex(r"\s+", logos::skip)]
     #[regex(r"--[^\n]*", logos::skip)]
     #[error]
     Invalid,
 }
 
+impl Token<'_> {
+    /// Get a short, human readable description of the kind of this token.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Token::Ident(_) => "a name",
+            Token::Symbol(_) => "a symbol",
+            Token::Number(_) => "a number",
+            Token::String(_) => "a string",
+            Token::OpenParen => "'('",
+            Token::CloseParen => "')'",
+            Token::Comma => "','",
+            Token::Equal => "'='",
+            Token::Pipe => "'->'",
+            Token::Range => "'..'",
+            Token::Invalid => "an invalid token",
+        }
+    }
+}
+
+/// Get the contents of a string literal between its quotes, with any escapes left in, or `None` if the literal is not
+/// closed.
+pub fn string_contents(literal: &str) -> Option<&str> {
+    let contents = literal.strip_prefix('"')?.strip_suffix('"')?;
+
+    // The closing quote may itself be escaped.
+    let backslashes = contents.chars().rev().take_while(|c| *c == '\\').count();
+    (backslashes % 2 == 0).then_some(contents)
+}
+
+/// Get the text the contents of a string literal stand for.
+pub fn unescape(contents: &str) -> String {
+    let mut text = String::with_capacity(contents.len());
+    let mut chars = contents.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.extend(chars.next()),
+            c => text.push(c),
+        }
+    }
+
+    text
+}
+
+/// Get the contents of a string literal which stands for the given text.
+pub fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{lex, Token};
+    use super::{escape, lex, string_contents, unescape, Token};
     use crate::compiler::source::{Source, Sources};
 
     fn test_tokens(expected: &[Token], source: impl Into<Source>) {
@@ -75,19 +132,67 @@ mod tests {
 
     #[test]
     fn lex_other() {
-        let source = "(),=->ø";
+        let source = "(),=->..ø";
         let expected = &[
             Token::OpenParen,
             Token::CloseParen,
             Token::Comma,
             Token::Equal,
             Token::Pipe,
+            Token::Range,
             Token::Invalid,
         ];
 
         test_tokens(expected, source);
     }
 
+    #[test]
+    fn lex_range() {
+        let source = "(1..200) (0.5..1e3)";
+        let expected = &[
+            Token::OpenParen,
+            Token::Number("1"),
+            Token::Range,
+            Token::Number("200"),
+            Token::CloseParen,
+            Token::OpenParen,
+            Token::Number("0.5"),
+            Token::Range,
+            Token::Number("1e3"),
+            Token::CloseParen,
+        ];
+
+        test_tokens(expected, source);
+    }
+
+    #[test]
+    fn lex_string() {
+        let source = r#"image("a b.png") "say \"hi\"" "c:\\" "open"#;
+        let expected = &[
+            Token::Ident("image"),
+            Token::OpenParen,
+            Token::String(r#""a b.png""#),
+            Token::CloseParen,
+            Token::String(r#""say \"hi\"""#),
+            Token::String(r#""c:\\""#),
+            Token::String(r#""open"#),
+        ];
+
+        test_tokens(expected, source);
+    }
+
+    #[test]
+    fn strings_escape_quotes_and_backslashes() {
+        assert_eq!(Some("a b"), string_contents(r#""a b""#));
+        assert_eq!(Some(r#"c:\\"#), string_contents(r#""c:\\""#));
+        assert_eq!(None, string_contents(r#""open\""#));
+        assert_eq!(None, string_contents(r#"""#));
+
+        for text in ["plain", r#"say "hi""#, r"c:\dir\", r#"\""#] {
+            assert_eq!(text, unescape(&escape(text)));
+        }
+    }
+
     #[test]
     fn lex_comment() {
         let source = "a = voronoi\n-- hehe\na -> return";
@@ -9,6 +9,20 @@ pub type Spanned<T> = (T, Span);
 pub struct Program<'src> {
     pub defs: HashMap<&'src str, (Span, Spanned<Pipeline<'src>>)>,
     pub results: Vec<Spanned<Pipeline<'src>>>,
+
+    /// Every parameter, in the order they were declared.
+    pub params: Vec<Spanned<Param<'src>>>,
+}
+
+/// `param name = default (min..max)`
+#[derive(Debug)]
+pub struct Param<'src> {
+    pub name: Spanned<&'src str>,
+    pub default: Spanned<&'src str>,
+    pub range: Option<Spanned<(&'src str, &'src str)>>,
+
+    /// The lines of the comment directly above the declaration, without their dashes.
+    pub doc: Vec<&'src str>,
 }
 
 #[derive(Debug)]
@@ -1,21 +1,65 @@
+pub mod builder;
 pub mod compiler;
+pub mod lsp;
 pub mod nodes;
+pub mod program;
+pub mod render;
+pub mod repl;
 pub mod util;
 
 mod texture;
 
-pub use texture::{Pixel, Texture};
+use std::path::{Path, PathBuf};
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+pub use program::{render_str, Diagnostics, Program};
+pub use texture::{
+    Channel, Channels, Depth, Endian, Filter, Format, Histogram, Levels, Pixel, Precision, Raw,
+    Space, Stats, Texture,
+};
 pub use util::Metric;
 
 #[derive(Clone, Copy, Debug)]
 pub struct Config {
     pub width: usize,
     pub height: usize,
+
+    /// The seed random nodes draw from, or `None` to draw from entropy, making every render different.
+    pub seed: Option<u64>,
+
+    /// The precision textures are stored at as they are rendered.
+    pub precision: Precision,
 }
 
 impl Config {
     pub fn new(width: usize, height: usize) -> Self {
-        Self { width, height }
+        Self {
+            width,
+            height,
+            seed: None,
+            precision: Precision::default(),
+        }
+    }
+
+    pub fn with_seed(self, seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
+    pub fn with_precision(self, precision: Precision) -> Self {
+        Self { precision, ..self }
+    }
+
+    /// Get a random number generator for a node to draw from.
+    pub fn rng(&self) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        }
     }
 }
 
@@ -23,6 +67,19 @@ pub trait Node {
     fn init(&mut self, config: &Config) {
         #![allow(unused_variables)]
     }
+
+    /// Read anything the node needs from outside the graph, resolving relative paths against `dir`. Returns why it
+    /// could not, if it could not.
+    fn load(&mut self, dir: &Path) -> Result<(), String> {
+        #![allow(unused_variables)]
+        Ok(())
+    }
+
+    /// Get the files [`Node::load`] reads, relative to the directory it is given, so that they can be watched for
+    /// changes.
+    fn files(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
 }
 
 pub trait Generator: Node {
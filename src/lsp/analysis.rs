@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::compiler::parse::cst::{self, Element, Kind};
+use crate::compiler::parse::parse_with_cst;
+use crate::compiler::resolve::resolve;
+use crate::compiler::source::{Sources, Span};
+use crate::compiler::token::{lex, Token};
+use crate::compiler::tree::{Node, Pipeline, Program, Spanned};
+use crate::compiler::{Error, Errors, Value};
+use crate::nodes::builtin;
+
+/// Something in a document which a name refers to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Symbol {
+    /// A definition in the document.
+    Definition(String),
+
+    /// A parameter declared in the document.
+    Param(String),
+
+    /// A built-in node.
+    Builtin(String),
+
+    /// A named argument given to a built-in node.
+    Argument { node: String, param: String },
+}
+
+/// Everything the language server knows about a document.
+#[derive(Debug, Default)]
+pub struct Analysis {
+    pub errors: Vec<Error>,
+
+    /// The spans of the names of every definition.
+    pub definitions: HashMap<String, Range<usize>>,
+
+    /// The span of the name of every parameter where it is first declared, along with its doc comment.
+    pub params: HashMap<String, (Range<usize>, String)>,
+
+    /// Every name in the document, along with what it refers to.
+    pub symbols: Vec<(Range<usize>, Symbol)>,
+
+    /// The names of every initializer, along with the span of its argument list.
+    pub inits: Vec<(String, Range<usize>)>,
+}
+
+impl Analysis {
+    pub fn new(text: &str) -> Self {
+        let mut sources = Sources::new();
+        let id = sources.add(text.into());
+        let text = &sources.get(&id).content;
+
+        let mut errors = Errors::new();
+        let (program, cst) = parse_with_cst(text, id, lex(&sources, id), &mut errors);
+
+        let mut analysis = Self::default();
+        analysis.add_program(&program);
+        analysis.add_inits(text, &cst);
+
+        let _ = resolve(program, &mut errors);
+        analysis.errors = errors.errors;
+        analysis
+    }
+
+    /// Get the symbol under the given offset, if any.
+    pub fn symbol_at(&self, offset: usize) -> Option<(Range<usize>, &Symbol)> {
+        self.symbols
+            .iter()
+            .find(|(span, _)| span.start <= offset && offset <= span.end)
+            .map(|(span, symbol)| (span.clone(), symbol))
+    }
+
+    /// Get the span of the name of a definition or parameter where it is declared.
+    pub fn declaration(&self, symbol: &Symbol) -> Option<&Range<usize>> {
+        match symbol {
+            Symbol::Definition(name) => self.definitions.get(name),
+            Symbol::Param(name) => self.params.get(name).map(|(span, _)| span),
+            _ => None,
+        }
+    }
+
+    /// Get the spans of every mention of a definition or parameter, including its declaration, in the order they appear.
+    pub fn mentions(&self, symbol: &Symbol) -> Vec<Range<usize>> {
+        let mut mentions: Vec<_> = self
+            .symbols
+            .iter()
+            .filter(|(_, mention)| mention == symbol)
+            .map(|(span, _)| span.clone())
+            .collect();
+
+        mentions.sort_by_key(|span| span.start);
+        mentions
+    }
+
+    /// Get the name of the innermost initializer whose argument list contains the given offset.
+    pub fn init_at(&self, offset: usize) -> Option<&str> {
+        self.inits
+            .iter()
+            .filter(|(_, span)| span.start < offset && offset < span.end)
+            .min_by_key(|(_, span)| span.end - span.start)
+            .map(|(name, _)| name.as_str())
+    }
+
+    fn add_program(&mut self, program: &Program) {
+        for (param, _) in program.params.iter() {
+            let (name, span) = param.name;
+            self.params
+                .entry(name.to_string())
+                .or_insert_with(|| (range(span), param.doc.join("\n")));
+            self.symbols
+                .push((range(span), Symbol::Param(name.to_string())));
+        }
+
+        for (name, (span, _)) in program.defs.iter() {
+            self.definitions.insert(name.to_string(), range(*span));
+            self.symbols
+                .push((range(*span), Symbol::Definition(name.to_string())));
+        }
+
+        let pipelines = program
+            .defs
+            .values()
+            .map(|(_, pipeline)| pipeline)
+            .chain(program.results.iter());
+
+        for (pipeline, _) in pipelines {
+            self.add_pipeline(program, pipeline);
+        }
+    }
+
+    fn add_pipeline(&mut self, program: &Program, pipeline: &Pipeline) {
+        for (node, span) in pipeline.nodes.iter() {
+            match node {
+                Node::Name(name) => self.add_name(program, name, *span),
+
+                Node::Init {
+                    name,
+                    positional,
+                    named,
+                } => {
+                    self.add_name(program, name.0, name.1);
+
+                    for (param, (span, value)) in named.iter() {
+                        let symbol = Symbol::Argument {
+                            node: name.0.to_string(),
+                            param: param.to_string(),
+                        };
+
+                        self.symbols.push((range(*span), symbol));
+                        self.add_value(value);
+                    }
+
+                    for value in positional.iter() {
+                        self.add_value(value);
+                    }
+                }
+
+                Node::Tuple(pipelines) => {
+                    for (pipeline, _) in pipelines {
+                        self.add_pipeline(program, pipeline);
+                    }
+                }
+
+                Node::Invalid => {}
+            }
+        }
+    }
+
+    fn add_name(&mut self, program: &Program, name: &str, span: Span) {
+        let symbol = if program.defs.contains_key(name) {
+            Symbol::Definition(name.into())
+        } else if builtin(name).is_some() {
+            Symbol::Builtin(name.into())
+        } else {
+            return;
+        };
+
+        self.symbols.push((range(span), symbol));
+    }
+
+    fn add_value(&mut self, (value, span): &Spanned<Value>) {
+        if let Value::Param(name) = value {
+            if self.params.contains_key(*name) {
+                self.symbols
+                    .push((range(*span), Symbol::Param(name.to_string())));
+            }
+        }
+    }
+
+    fn add_inits(&mut self, text: &str, node: &cst::Node) {
+        if node.kind == Kind::Init {
+            let mut children = node.significant();
+
+            if let (
+                Some(Element::Token {
+                    token: Token::Ident(name),
+                    ..
+                }),
+                Some(Element::Node(group)),
+            ) = (children.next(), children.next())
+            {
+                let mut span = range(group.span);
+
+                // An argument list which is still being typed extends until the end of its line.
+                let closed = matches!(
+                    group.significant().last(),
+                    Some(Element::Token {
+                        token: Token::CloseParen,
+                        ..
+                    })
+                );
+
+                if !closed {
+                    let rest = &text[span.end..];
+                    span.end += rest.find('\n').unwrap_or(rest.len()) + 1;
+                }
+
+                self.inits.push((name.to_string(), span));
+            }
+        }
+
+        for child in node.children.iter() {
+            if let Element::Node(child) = child {
+                self.add_inits(text, child);
+            }
+        }
+    }
+}
+
+fn range(span: Span) -> Range<usize> {
+    span.start..span.end
+}
@@ -0,0 +1,104 @@
+use std::ops::Range;
+
+/// A position as the language server protocol counts it: a zero-based line, and a zero-based column in UTF-16 code
+/// units.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+#[derive(Debug)]
+pub struct Document {
+    pub text: String,
+    pub version: i64,
+}
+
+impl Document {
+    pub fn new(text: String, version: i64) -> Self {
+        Self { text, version }
+    }
+
+    /// Replace the given range of the document with new text, or the entire document if no range is given.
+    pub fn edit(&mut self, range: Option<Range<Position>>, text: &str) {
+        match range {
+            Some(range) => {
+                let start = self.offset(range.start);
+                let end = self.offset(range.end).max(start);
+                self.text.replace_range(start..end, text);
+            }
+
+            None => self.text = text.into(),
+        }
+    }
+
+    /// Get the byte offset of a position. Positions past the end of a line are clamped to the end of that line.
+    pub fn offset(&self, position: Position) -> usize {
+        let mut line_start = 0;
+        for _ in 0..position.line {
+            match self.text[line_start..].find('\n') {
+                Some(at) => line_start += at + 1,
+                None => return self.text.len(),
+            }
+        }
+
+        let line = &self.text[line_start..];
+        let line = &line[..line.find('\n').unwrap_or(line.len())];
+
+        let mut units = 0;
+        for (at, c) in line.char_indices() {
+            if units >= position.character {
+                return line_start + at;
+            }
+
+            units += c.len_utf16();
+        }
+
+        line_start + line.len()
+    }
+
+    /// Get the position of a byte offset.
+    pub fn position(&self, offset: usize) -> Position {
+        let before = &self.text[..offset.min(self.text.len())];
+        let line = before.matches('\n').count();
+        let line_start = before.rfind('\n').map(|at| at + 1).unwrap_or(0);
+        let character = before[line_start..].encode_utf16().count();
+
+        Position { line, character }
+    }
+
+    pub fn range(&self, range: Range<usize>) -> Range<Position> {
+        self.position(range.start)..self.position(range.end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Document, Position};
+
+    #[test]
+    fn positions_count_utf16() {
+        let doc = Document::new("a = ø\n-- 𝄞 x\nb".into(), 0);
+
+        let x = doc.text.find('x').unwrap();
+        let position = Position {
+            line: 1,
+            character: 6,
+        };
+
+        assert_eq!(position, doc.position(x));
+        assert_eq!(x, doc.offset(position));
+    }
+
+    #[test]
+    fn edits_apply_in_order() {
+        let mut doc = Document::new("a = voronoi\nb = a".into(), 0);
+
+        let at = |line, character| Position { line, character };
+        doc.edit(Some(at(0, 4)..at(0, 11)), "simplex(2)");
+        doc.edit(Some(at(1, 5)..at(1, 5)), " -> invert");
+        doc.edit(Some(at(1, 100)..at(1, 100)), "\n");
+
+        assert_eq!("a = simplex(2)\nb = a -> invert\n", doc.text);
+    }
+}
@@ -0,0 +1,717 @@
+//! A language server for cobble scripts, speaking the language server protocol over any pair of streams.
+
+mod analysis;
+mod document;
+mod rpc;
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+
+use logos::Logos;
+use serde_json::{json, Value};
+
+use crate::compiler::token::Token;
+use crate::compiler::Error;
+use crate::nodes::builtin::{builtin, NodeInfo, ParamKind, BUILTINS};
+
+use analysis::{Analysis, Symbol};
+use document::{Document, Position};
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Serve requests from `input` until the client asks the server to exit or the input ends.
+pub fn run<R: BufRead, W: Write>(mut input: R, mut output: W) -> io::Result<()> {
+    let mut server = Server {
+        output: &mut output,
+        documents: HashMap::new(),
+    };
+
+    while let Some(message) = rpc::read(&mut input)? {
+        let message = match message {
+            Ok(message) => message,
+
+            // The id of a malformed message cannot be known, so the error is sent without one.
+            Err(error) => {
+                let response = json!({
+                    "jsonrpc": "2.0",
+                    "id": null,
+                    "error": { "code": PARSE_ERROR, "message": error },
+                });
+
+                rpc::write(server.output, &response)?;
+                continue;
+            }
+        };
+
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+
+        if method == "exit" {
+            break;
+        }
+
+        match message.get("id") {
+            Some(id) => {
+                let response = match server.request(method, params) {
+                    Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                    Err((code, message)) => json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": code, "message": message },
+                    }),
+                };
+
+                rpc::write(server.output, &response)?;
+            }
+
+            None => server.notification(method, params)?,
+        }
+    }
+
+    Ok(())
+}
+
+struct Server<'a, W> {
+    output: &'a mut W,
+    documents: HashMap<String, Document>,
+}
+
+impl<W: Write> Server<'_, W> {
+    fn request(&mut self, method: &str, params: &Value) -> Result<Value, (i64, String)> {
+        match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": { "openClose": true, "change": 2 },
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "renameProvider": true,
+                    "completionProvider": { "triggerCharacters": ["(", ","] },
+                },
+                "serverInfo": { "name": "cobble", "version": env!("CARGO_PKG_VERSION") },
+            })),
+
+            "shutdown" => Ok(Value::Null),
+
+            "textDocument/hover" => self.at(params, hover),
+            "textDocument/definition" => self.at(params, definition),
+            "textDocument/references" => {
+                let declaration = params["context"]["includeDeclaration"]
+                    .as_bool()
+                    .unwrap_or(true);
+                self.at(params, |at| references(at, declaration))
+            }
+            "textDocument/completion" => self.at(params, completion),
+            "textDocument/rename" => {
+                let name = params["newName"].as_str().unwrap_or_default();
+                let at = self.locate(params)?;
+                check_name(&at, name).map_err(|message| (INVALID_PARAMS, message))?;
+                Ok(rename(&at, name).unwrap_or(Value::Null))
+            }
+
+            _ => Err((METHOD_NOT_FOUND, format!("unknown method `{method}`"))),
+        }
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> io::Result<()> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let version = params["textDocument"]["version"]
+            .as_i64()
+            .unwrap_or_default();
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents
+                    .insert(uri.into(), Document::new(text.into(), version));
+            }
+
+            "textDocument/didChange" => {
+                let Some(doc) = self.documents.get_mut(uri) else {
+                    return Ok(());
+                };
+
+                let changes = params["contentChanges"].as_array().into_iter().flatten();
+                for change in changes {
+                    let text = change["text"].as_str().unwrap_or_default();
+                    let range = change.get("range").and_then(|range| {
+                        Some(position(&range["start"])?..position(&range["end"])?)
+                    });
+
+                    doc.edit(range, text);
+                }
+
+                doc.version = version;
+            }
+
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                return self.publish(uri, Vec::new(), None);
+            }
+
+            _ => return Ok(()),
+        }
+
+        let doc = &self.documents[uri];
+        let diagnostics = Analysis::new(&doc.text)
+            .errors
+            .iter()
+            .map(|error| diagnostic(doc, error))
+            .collect();
+
+        self.publish(uri, diagnostics, Some(doc.version))
+    }
+
+    fn publish(
+        &mut self,
+        uri: &str,
+        diagnostics: Vec<Value>,
+        version: Option<i64>,
+    ) -> io::Result<()> {
+        let notification = json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "version": version, "diagnostics": diagnostics },
+        });
+
+        rpc::write(self.output, &notification)
+    }
+
+    /// Analyse the document a request refers to, and answer it using the position it refers to.
+    fn at(
+        &self,
+        params: &Value,
+        answer: impl FnOnce(&At) -> Option<Value>,
+    ) -> Result<Value, (i64, String)> {
+        let at = self.locate(params)?;
+        Ok(answer(&at).unwrap_or(Value::Null))
+    }
+
+    /// Analyse the document a request refers to, and find the position it refers to.
+    fn locate<'a>(&'a self, params: &'a Value) -> Result<At<'a>, (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let doc = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("unknown document `{uri}`")))?;
+        let position =
+            position(&params["position"]).ok_or((INVALID_PARAMS, "missing position".into()))?;
+
+        Ok(At {
+            uri,
+            doc,
+            analysis: Analysis::new(&doc.text),
+            offset: doc.offset(position),
+        })
+    }
+}
+
+/// A position in a document which a request refers to.
+struct At<'a> {
+    uri: &'a str,
+    doc: &'a Document,
+    analysis: Analysis,
+    offset: usize,
+}
+
+impl At<'_> {
+    /// Get the definition or parameter under the position, if any.
+    fn declared(&self) -> Option<&Symbol> {
+        match self.analysis.symbol_at(self.offset)? {
+            (_, symbol @ (Symbol::Definition(_) | Symbol::Param(_))) => Some(symbol),
+            _ => None,
+        }
+    }
+
+    fn location(&self, span: Range<usize>) -> Value {
+        json!({ "uri": self.uri, "range": range(self.doc, span) })
+    }
+}
+
+fn hover(at: &At) -> Option<Value> {
+    let (span, symbol) = at.analysis.symbol_at(at.offset)?;
+
+    let contents = match symbol {
+        Symbol::Builtin(name) => describe(builtin(name)?),
+
+        Symbol::Argument { node, param } => {
+            let param = builtin(node)?.param(param)?;
+            format!("`{}` — {}", param.name, param.doc)
+        }
+
+        Symbol::Definition(name) => {
+            let start = at.analysis.definitions.get(name)?.start;
+            let line = at.doc.text[start..].lines().next().unwrap_or_default();
+            format!("```cobble\n{}\n```", line.trim_end())
+        }
+
+        Symbol::Param(name) => {
+            let (span, doc) = at.analysis.params.get(name)?;
+            let start = at.doc.text[..span.start]
+                .rfind('\n')
+                .map_or(0, |ndx| ndx + 1);
+            let line = at.doc.text[start..].lines().next().unwrap_or_default();
+            match doc.as_str() {
+                "" => format!("```cobble\n{}\n```", line.trim_end()),
+                doc => format!("```cobble\n{}\n```\n\n{doc}", line.trim_end()),
+            }
+        }
+    };
+
+    Some(json!({
+        "contents": { "kind": "markdown", "value": contents },
+        "range": range(at.doc, span),
+    }))
+}
+
+fn definition(at: &At) -> Option<Value> {
+    let span = at.analysis.declaration(at.declared()?)?;
+    Some(at.location(span.clone()))
+}
+
+fn references(at: &At, declaration: bool) -> Option<Value> {
+    let symbol = at.declared()?;
+    let defined = at.analysis.declaration(symbol);
+
+    let references = at
+        .analysis
+        .mentions(symbol)
+        .into_iter()
+        .filter(|span| declaration || Some(span) != defined)
+        .map(|span| at.location(span))
+        .collect();
+
+    Some(Value::Array(references))
+}
+
+fn rename(at: &At, name: &str) -> Option<Value> {
+    let edits: Vec<_> = at
+        .analysis
+        .mentions(at.declared()?)
+        .into_iter()
+        .map(|span| json!({ "range": range(at.doc, span), "newText": name }))
+        .collect();
+
+    Some(json!({ "changes": { at.uri: edits } }))
+}
+
+fn completion(at: &At) -> Option<Value> {
+    let mut items = Vec::new();
+
+    if let Some(info) = at.analysis.init_at(at.offset).and_then(builtin) {
+        for param in info.params {
+            items.push(json!({
+                "label": param.name,
+                "kind": 5,
+                "detail": param.doc,
+                "insertText": format!("{} = ", param.name),
+            }));
+
+            if let ParamKind::Symbol(options) = param.kind {
+                for option in options {
+                    items.push(json!({ "label": option, "kind": 20, "detail": param.name }));
+                }
+            }
+        }
+
+        for (name, (_, doc)) in at.analysis.params.iter() {
+            items.push(json!({ "label": name, "kind": 21, "detail": doc }));
+        }
+    } else {
+        for info in BUILTINS {
+            items.push(json!({
+                "label": info.name,
+                "kind": 3,
+                "detail": info.doc,
+                "documentation": { "kind": "markdown", "value": describe(info) },
+            }));
+        }
+
+        for name in at.analysis.definitions.keys() {
+            items.push(json!({ "label": name, "kind": 6 }));
+        }
+    }
+
+    Some(Value::Array(items))
+}
+
+/// Check that the definition or parameter under a position may be renamed to the given name, without clashing with a
+/// built-in node or merging it into another definition or parameter.
+fn check_name(at: &At, name: &str) -> Result<(), String> {
+    let mut tokens = Token::lexer(name);
+    let declared = at.declared();
+
+    let renamed = match declared {
+        Some(Symbol::Param(_)) => Symbol::Param(name.into()),
+        _ => Symbol::Definition(name.into()),
+    };
+    let taken = at.analysis.declaration(&renamed).is_some() && declared != Some(&renamed);
+
+    match (tokens.next(), tokens.next()) {
+        (Some(Token::Ident(_)), None)
+            if matches!(renamed, Symbol::Definition(_))
+                && (builtin(name).is_some() || name == "return") =>
+        {
+            Err(format!("`{name}` is already the name of a built-in node"))
+        }
+
+        (Some(Token::Ident(_)), None) if taken => Err(format!("`{name}` is already defined")),
+
+        (Some(Token::Ident(_)), None) => Ok(()),
+
+        _ => Err(format!(
+            "`{name}` is not a valid name; names start with a lowercase letter"
+        )),
+    }
+}
+
+/// Describe a built-in node in markdown.
+fn describe(info: &NodeInfo) -> String {
+    let kind = match info.kind.inputs() {
+        0 => "generator".to_string(),
+        1 => "processor, taking one input".to_string(),
+        inputs => format!("combiner, taking {inputs} inputs"),
+    };
+
+    let mut text = format!(
+        "**{}** ({kind})\n\n{}\n\nKinds: `{}`",
+        info.name,
+        info.doc,
+        info.kinds()
+    );
+
+    if !info.params.is_empty() {
+        text.push_str("\n\nParameters:\n");
+    }
+
+    for param in info.params {
+        let default = match param.default {
+            Some(default) => format!(" = {default}"),
+            None => String::new(),
+        };
+
+        text.push_str(&format!("- `{}{default}` — {}", param.name, param.doc));
+
+        if let ParamKind::Symbol(options) = param.kind {
+            text.push_str(&format!(" (one of {})", options.join(", ")));
+        }
+
+        text.push('\n');
+    }
+
+    text
+}
+
+fn diagnostic(doc: &Document, error: &Error) -> Value {
+    let mut message = error.message.clone();
+
+    for note in error.notes.iter() {
+        message.push_str(&format!("\nnote: {note}"));
+    }
+
+    for help in error.helps.iter() {
+        message.push_str(&format!("\nhelp: {help}"));
+    }
+
+    if let Some(expected) = error.expected_list() {
+        message.push_str(&format!("\nexpected {expected}"));
+    }
+
+    json!({
+        "range": range(doc, error.at.start..error.at.end),
+        "severity": 1,
+        "source": "cobble",
+        "message": message,
+    })
+}
+
+fn position(value: &Value) -> Option<Position> {
+    Some(Position {
+        line: value["line"].as_u64()? as usize,
+        character: value["character"].as_u64()? as usize,
+    })
+}
+
+fn range(doc: &Document, span: Range<usize>) -> Value {
+    let range = doc.range(span);
+    json!({
+        "start": { "line": range.start.line, "character": range.start.character },
+        "end": { "line": range.end.line, "character": range.end.character },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::{rpc, run};
+
+    /// Run a session with the given messages, returning every message the server sent back.
+    fn session(messages: &[Value]) -> Vec<Value> {
+        let mut input = Vec::new();
+        for message in messages {
+            rpc::write(&mut input, message).unwrap();
+        }
+
+        let mut output = Vec::new();
+        run(input.as_slice(), &mut output).unwrap();
+
+        let mut output = output.as_slice();
+        std::iter::from_fn(|| rpc::read(&mut output).unwrap().map(Result::unwrap)).collect()
+    }
+
+    fn request(id: i64, method: &str, line: usize, character: usize) -> Value {
+        json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": {
+                "textDocument": { "uri": "file:///a.cbl" },
+                "position": { "line": line, "character": character },
+                "context": { "includeDeclaration": false },
+                "newName": "base",
+            },
+        })
+    }
+
+    fn result(responses: &[Value], id: i64) -> &Value {
+        let response = responses
+            .iter()
+            .find(|response| response["id"] == id)
+            .unwrap();
+        &response["result"]
+    }
+
+    #[test]
+    fn session_answers_requests() {
+        let open = json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {
+                "textDocument": {
+                    "uri": "file:///a.cbl",
+                    "version": 1,
+                    "text": "a = voronoi(metric = Manhattan)\nb = a -> invert\nb -> return\n",
+                },
+            },
+        });
+
+        let responses = session(&[
+            request(1, "initialize", 0, 0),
+            open,
+            request(2, "textDocument/hover", 0, 6),
+            request(3, "textDocument/definition", 1, 4),
+            request(4, "textDocument/references", 0, 0),
+            request(5, "textDocument/rename", 1, 4),
+            request(6, "textDocument/completion", 0, 12),
+            request(7, "textDocument/unknown", 0, 0),
+            json!({ "jsonrpc": "2.0", "method": "exit" }),
+        ]);
+
+        assert_eq!(
+            json!(true),
+            result(&responses, 1)["capabilities"]["hoverProvider"]
+        );
+
+        let diagnostics = &responses[1];
+        assert_eq!("textDocument/publishDiagnostics", diagnostics["method"]);
+        assert_eq!(json!([]), diagnostics["params"]["diagnostics"]);
+
+        let hover = result(&responses, 2)["contents"]["value"].as_str().unwrap();
+        assert!(hover.starts_with("**voronoi** (generator)"));
+
+        let range = |line, start, end| {
+            json!({
+                "start": { "line": line, "character": start },
+                "end": { "line": line, "character": end },
+            })
+        };
+
+        assert_eq!(range(0, 0, 1), result(&responses, 3)["range"]);
+        assert_eq!(
+            json!([{ "uri": "file:///a.cbl", "range": range(1, 4, 5) }]),
+            *result(&responses, 4)
+        );
+
+        let edits = result(&responses, 5)["changes"]["file:///a.cbl"]
+            .as_array()
+            .unwrap();
+        assert_eq!(2, edits.len());
+        assert!(edits.iter().all(|edit| edit["newText"] == "base"));
+
+        let labels: Vec<_> = result(&responses, 6)
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect();
+        assert!(labels.contains(&"points") && labels.contains(&"Chebyshev"));
+        assert!(!labels.contains(&"simplex"));
+
+        let unknown = responses
+            .iter()
+            .find(|response| response["id"] == 7)
+            .unwrap();
+        assert_eq!(-32601, unknown["error"]["code"]);
+    }
+
+    #[test]
+    fn edits_update_diagnostics() {
+        let responses = session(&[
+            json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didOpen",
+                "params": { "textDocument": { "uri": "file:///a.cbl", "version": 1, "text": "a = voronoi\n" } },
+            }),
+            json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didChange",
+                "params": {
+                    "textDocument": { "uri": "file:///a.cbl", "version": 2 },
+                    "contentChanges": [{
+                        "range": { "start": { "line": 0, "character": 4 }, "end": { "line": 0, "character": 11 } },
+                        "text": "voronio",
+                    }],
+                },
+            }),
+            request(1, "textDocument/rename", 0, 6),
+        ]);
+
+        let diagnostics = &responses[1]["params"];
+        assert_eq!(2, diagnostics["version"]);
+        assert_eq!(1, diagnostics["diagnostics"].as_array().unwrap().len());
+        assert_eq!(
+            json!({ "line": 0, "character": 4 }),
+            diagnostics["diagnostics"][0]["range"]["start"]
+        );
+
+        assert_eq!(Value::Null, *result(&responses, 1));
+    }
+
+    #[test]
+    fn params_are_symbols() {
+        let open = json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {
+                "textDocument": {
+                    "uri": "file:///a.cbl",
+                    "version": 1,
+                    "text": "-- cells across\nparam d = 5 (1..20)\na = voronoi(d)\nb = voronoi(points = d)\n",
+                },
+            },
+        });
+
+        let responses = session(&[
+            open,
+            request(1, "textDocument/hover", 2, 12),
+            request(2, "textDocument/definition", 3, 21),
+            request(3, "textDocument/references", 1, 6),
+            request(4, "textDocument/rename", 2, 12),
+            request(5, "textDocument/completion", 2, 12),
+        ]);
+
+        let hover = result(&responses, 1)["contents"]["value"].as_str().unwrap();
+        assert_eq!("```cobble\nparam d = 5 (1..20)\n```\n\ncells across", hover);
+
+        let range = |line, start, end| {
+            json!({
+                "start": { "line": line, "character": start },
+                "end": { "line": line, "character": end },
+            })
+        };
+
+        assert_eq!(range(1, 6, 7), result(&responses, 2)["range"]);
+        assert_eq!(
+            json!([
+                { "uri": "file:///a.cbl", "range": range(2, 12, 13) },
+                { "uri": "file:///a.cbl", "range": range(3, 21, 22) },
+            ]),
+            *result(&responses, 3)
+        );
+
+        let edits = result(&responses, 4)["changes"]["file:///a.cbl"]
+            .as_array()
+            .unwrap();
+        assert_eq!(3, edits.len());
+
+        let completions = result(&responses, 5).as_array().unwrap();
+        assert!(completions.iter().any(|item| item["label"] == "d"));
+    }
+
+    #[test]
+    fn malformed_messages_are_answered() {
+        let mut input = b"Content-Length: 9\r\n\r\n{\"id\": 1,".to_vec();
+        rpc::write(&mut input, &request(2, "shutdown", 0, 0)).unwrap();
+
+        let mut output = Vec::new();
+        run(input.as_slice(), &mut output).unwrap();
+
+        let mut output = output.as_slice();
+        let responses: Vec<_> =
+            std::iter::from_fn(|| rpc::read(&mut output).unwrap().map(Result::unwrap)).collect();
+
+        assert_eq!(-32700, responses[0]["error"]["code"]);
+        assert_eq!(Value::Null, responses[0]["id"]);
+        assert_eq!(Value::Null, *result(&responses, 2));
+    }
+
+    #[test]
+    fn messages_without_a_usable_length_are_answered() {
+        let mut input = Vec::new();
+        for header in [
+            "Content-Type: text/plain",
+            "Content-Length: nine",
+            "Content-Length: 99999999999",
+        ] {
+            input.extend(format!("{header}\r\n\r\n{{\"id\": 1}}").bytes());
+            rpc::write(&mut input, &request(2, "shutdown", 0, 0)).unwrap();
+        }
+
+        let mut output = Vec::new();
+        run(input.as_slice(), &mut output).unwrap();
+
+        let mut output = output.as_slice();
+        let responses: Vec<_> =
+            std::iter::from_fn(|| rpc::read(&mut output).unwrap().map(Result::unwrap)).collect();
+
+        let errors = responses
+            .iter()
+            .filter(|response| response["error"]["code"] == -32700);
+        assert_eq!(3, errors.count());
+        assert_eq!(
+            2,
+            responses
+                .iter()
+                .filter(|response| response["id"] == 2)
+                .count()
+        );
+    }
+
+    #[test]
+    fn rename_refuses_to_merge_definitions() {
+        let responses = session(&[
+            json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didOpen",
+                "params": { "textDocument": { "uri": "file:///a.cbl", "version": 1, "text": "a = voronoi
+base = simplex
+" } },
+            }),
+            request(1, "textDocument/rename", 0, 0),
+            request(2, "textDocument/rename", 1, 0),
+        ]);
+
+        let error = &responses
+            .iter()
+            .find(|response| response["id"] == 1)
+            .unwrap()["error"];
+        assert_eq!(-32602, error["code"]);
+        assert_eq!("`base` is already defined", error["message"]);
+
+        assert!(result(&responses, 2)["changes"].is_object());
+    }
+}
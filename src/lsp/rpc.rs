@@ -0,0 +1,63 @@
+use std::io::{self, BufRead, Write};
+
+use serde_json::Value;
+
+/// The longest content a message may have, well beyond any document the server would be sent.
+const MAX_LENGTH: usize = 64 << 20;
+
+/// Read a single message, or `None` if the input has ended. A message which cannot be understood, for having invalid
+/// JSON, or a missing or overlong length, is an error describing why, and the input is left where the next message can
+/// be read from.
+///
+/// Headers are searched for anywhere in a line. Content without a length is read as headers, and runs into the header
+/// of the next message, which is then still found.
+pub fn read<R: BufRead>(input: &mut R) -> io::Result<Option<Result<Value, String>>> {
+    let mut length = None;
+
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        // Lowering ASCII letters keeps every other byte where it was, so positions carry over to the line.
+        if let Some(at) = line.to_ascii_lowercase().rfind("content-length:") {
+            length = line[at + "content-length:".len()..]
+                .trim()
+                .parse::<usize>()
+                .ok();
+        }
+    }
+
+    let Some(length) = length else {
+        return Ok(Some(Err("missing or invalid content length".into())));
+    };
+
+    if length > MAX_LENGTH {
+        io::copy(
+            &mut io::Read::take(&mut *input, length as u64),
+            &mut io::sink(),
+        )?;
+        return Ok(Some(Err(format!(
+            "content of {length} bytes is longer than the limit of {MAX_LENGTH}"
+        ))));
+    }
+
+    let mut content = vec![0; length];
+    input.read_exact(&mut content)?;
+
+    Ok(Some(
+        serde_json::from_slice(&content).map_err(|error| error.to_string()),
+    ))
+}
+
+pub fn write<W: Write>(output: &mut W, message: &Value) -> io::Result<()> {
+    let content = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{content}", content.len())?;
+    output.flush()
+}
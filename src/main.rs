@@ -1,9 +1,1043 @@
-use anyhow::{anyhow, Result};
-use cobble::nodes::{LInvert, Math, Mix, Operation, Random, Voronoi};
-use cobble::{Combiner, Config, Generator, Metric, Node, Processor, Texture};
-use image::RgbaImage;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime};
+use std::{env, fs, io, thread};
+
+use anyhow::{anyhow, bail, Context, Result};
+use cobble::compiler::document::Document;
+use cobble::compiler::format::format;
+use cobble::compiler::graph::{Graph, NodeId};
+use cobble::compiler::parse::parse_cst;
+use cobble::compiler::source::{Source, SourceId, Sources};
+use cobble::compiler::token::lex;
+use cobble::compiler::{compile_with_params, Errors, Params};
+use cobble::nodes::{builtin, instantiate, LInvert, Math, Mix, Operation, Random, Voronoi};
+use cobble::render::{Cache, Policy, Renderer, Validation};
+use cobble::{
+    Channel, Channels, Combiner, Config, Depth, Endian, Format, Generator, Levels, Metric, Node,
+    Precision, Processor, Raw, Space, Texture,
+};
 
 fn main() -> Result<()> {
+    let args: Vec<_> = env::args().skip(1).collect();
+
+    match args.split_first() {
+        Some((command, rest)) if command == "fmt" => fmt(rest),
+        Some((command, rest)) if command == "render" => render(rest),
+        Some((command, rest)) if command == "graph" => graph(rest),
+        Some((command, rest)) if command == "params" => params(rest),
+        Some((command, rest)) if command == "stats" => stats(rest),
+        Some((command, _)) if command == "lsp" => lsp(),
+        Some((command, _)) if command == "repl" => repl(),
+        Some((command, _)) => bail!("unknown command '{command}'"),
+        None => demo(),
+    }
+}
+
+/// ```text
+/// cobble repl
+/// ```
+///
+/// Run an interactive session, in which definitions can be entered one at a time and previewed.
+fn repl() -> Result<()> {
+    let config = Config::new(256, 256);
+    Ok(cobble::repl::run(
+        io::stdin().lock(),
+        io::stdout().lock(),
+        config,
+    )?)
+}
+
+/// ```text
+/// cobble lsp
+/// ```
+///
+/// Run a language server, speaking the language server protocol over standard input and output.
+fn lsp() -> Result<()> {
+    Ok(cobble::lsp::run(io::stdin().lock(), io::stdout().lock())?)
+}
+
+/// ```text
+/// cobble fmt [--check] [FILE]...
+/// ```
+///
+/// Format the given files in place, or standard input to standard output if no files are given. With `--check`,
+/// nothing is written, but the command fails if any file is not already formatted.
+fn fmt(args: &[String]) -> Result<()> {
+    let check = args.iter().any(|arg| arg == "--check");
+    let files: Vec<_> = args.iter().filter(|arg| *arg != "--check").collect();
+
+    let mut sources = Sources::new();
+
+    if files.is_empty() {
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content)?;
+
+        let id = sources.add(Source::from(content).with_name("<stdin>"));
+        let formatted = format_source(&sources, id)?;
+        print!("{formatted}");
+        return Ok(());
+    }
+
+    let mut failed = false;
+
+    for file in files {
+        let content = fs::read_to_string(file)?;
+        let id = sources.add(Source::from(content).with_name(file));
+
+        let Ok(formatted) = format_source(&sources, id) else {
+            failed = true;
+            continue;
+        };
+
+        if formatted == sources.get(&id).content {
+            continue;
+        }
+
+        if check {
+            eprintln!("{file} is not formatted");
+            failed = true;
+        } else {
+            fs::write(file, formatted)?;
+        }
+    }
+
+    if failed {
+        bail!("formatting failed");
+    }
+
+    Ok(())
+}
+
+/// ```text
+/// cobble render [--watch] [--size SIZE] [--seed SEED] [--cache DIR] [--validate finite|range] [--sanitize zero|clamp]
+///               [--precision f64|f32|f16|u16] [--profile] [--trace] [--set NAME=VALUE]... [--output PATH]
+///               [--format png|png16|gray16|tiff|hdr|exr|r16|r32] [--levels clamp|normalize|keep]
+///               [--space linear|srgb] [--channel red|green|blue|alpha|luminance] [--endian little|big]
+///               [--remap MIN..MAX] FILE...
+/// ```
+///
+/// Render the results of each file, either a script or a graph document ending in `.json`, to an image next to it,
+/// named after the file, and numbered if it has several results. Each `--set` gives a parameter of the scripts a value.
+/// With `--watch`, the files are polled for changes, along with any files they read, like images, and only the results
+/// affected by a change are rendered again. A
+/// file which fails to compile is reported, and its previous output is left in place, as is a file which goes missing.
+/// When the number of results changes, they are all saved again and images no longer produced are removed.
+///
+/// Images are 8-bit PNGs unless `--format` chooses another format, or `--output` gives a single file somewhere else
+/// whose extension does. With `--levels`, values are clamped into `[0, 1]`, stretched so the lowest and highest colour
+/// values fill it, or kept as they are. Integer images are clamped and everything else is kept by default. Colour PNGs
+/// are encoded as sRGB and everything else is left linear, unless `--space` says otherwise.
+///
+/// The `r16` and `r32` formats are headerless heightmaps, of the luminance of each pixel unless `--channel` picks
+/// another channel, and little-endian unless `--endian` says otherwise. With `--remap`, heights from `MIN` to `MAX`
+/// cover the whole range of the heightmap, rather than heights from 0 to 1.
+///
+/// Rendered nodes are cached in memory, up to a gigabyte of the most recently used, and in the given directory if there
+/// is one. Random nodes are only cached when a seed is given.
+///
+/// Textures are stored as 32-bit floats while rendering, unless `--precision` chooses 64-bit or 16-bit floats, which
+/// halve the memory textures take but keep only about three significant digits, or 16-bit integers, which also clamp
+/// every value into `[0, 1]`.
+///
+/// With `--validate`, rendering fails at the first node which outputs an infinite or NaN value, or with `range`, any
+/// colour or scalar outside of `[0, 1]`. With `--sanitize`, such values are instead replaced with zero, or clamped, so
+/// the two cannot be combined. Vectors and labels are never clamped.
+///
+/// With `--profile`, a table of how long each node took is printed after each render. With `--trace`, the same is
+/// written next to each file as a Chrome trace, which `chrome://tracing` or Perfetto can open.
+fn render(args: &[String]) -> Result<()> {
+    let mut watch = false;
+    let mut size = 256;
+    let mut seed = None;
+    let mut precision = Precision::default();
+    let mut cache = Cache::new();
+    let mut validation = Validation::Off;
+    let mut profiling = Profiling::default();
+    let mut params = Params::new();
+    let mut saving = Saving::default();
+    let mut files = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--watch" => watch = true,
+            "--size" => {
+                let arg = args.next().ok_or_else(|| anyhow!("missing size"))?;
+                size = arg.parse()?;
+            }
+            "--seed" => {
+                let arg = args.next().ok_or_else(|| anyhow!("missing seed"))?;
+                seed = Some(arg.parse()?);
+            }
+            "--precision" => precision = parse_precision(args.next())?,
+            "--cache" => {
+                let arg = args
+                    .next()
+                    .ok_or_else(|| anyhow!("missing cache directory"))?;
+                cache = cache.with_dir(arg);
+            }
+            "--validate" => {
+                if matches!(validation, Validation::Sanitize(_)) {
+                    bail!("--validate and --sanitize cannot be combined");
+                }
+
+                validation = match args.next().map(String::as_str) {
+                    Some("finite") => Validation::Finite,
+                    Some("range") => Validation::Range,
+                    _ => bail!("expected 'finite' or 'range' after --validate"),
+                };
+            }
+            "--sanitize" => {
+                if matches!(validation, Validation::Finite | Validation::Range) {
+                    bail!("--validate and --sanitize cannot be combined");
+                }
+
+                validation = match args.next().map(String::as_str) {
+                    Some("zero") => Validation::Sanitize(Policy::Zero),
+                    Some("clamp") => Validation::Sanitize(Policy::Clamp),
+                    _ => bail!("expected 'zero' or 'clamp' after --sanitize"),
+                };
+            }
+            "--profile" => profiling.table = true,
+            "--trace" => profiling.trace = true,
+            "--set" => set_param(&mut params, args.next())?,
+            "--output" => {
+                let arg = args.next().ok_or_else(|| anyhow!("missing output path"))?;
+                saving.output = Some(PathBuf::from(arg));
+            }
+            "--format" => {
+                saving.format = Some(match args.next().map(String::as_str) {
+                    Some("png") => Format::Png8,
+                    Some("png16") => Format::Png16,
+                    Some("gray16") => Format::Gray16,
+                    Some("tiff") => Format::Tiff,
+                    Some("hdr") => Format::Hdr,
+                    Some("exr") => Format::Exr,
+                    Some("r16") => Format::R16,
+                    Some("r32") => Format::R32,
+                    _ => bail!(
+                        "expected 'png', 'png16', 'gray16', 'tiff', 'hdr', 'exr', 'r16' or 'r32' after --format"
+                    ),
+                });
+            }
+            "--levels" => {
+                saving.levels = Some(match args.next().map(String::as_str) {
+                    Some("clamp") => Levels::Clamp,
+                    Some("normalize") => Levels::Normalize,
+                    Some("keep") => Levels::Keep,
+                    _ => bail!("expected 'clamp', 'normalize' or 'keep' after --levels"),
+                });
+            }
+            "--space" => {
+                saving.space = Some(match args.next().map(String::as_str) {
+                    Some("linear") => Space::Linear,
+                    Some("srgb") => Space::Srgb,
+                    _ => bail!("expected 'linear' or 'srgb' after --space"),
+                });
+            }
+            "--channel" => {
+                saving.channel = match args.next().map(String::as_str) {
+                    Some("red") => Channel::Red,
+                    Some("green") => Channel::Green,
+                    Some("blue") => Channel::Blue,
+                    Some("alpha") => Channel::Alpha,
+                    Some("luminance") => Channel::Luminance,
+                    _ => bail!(
+                        "expected 'red', 'green', 'blue', 'alpha' or 'luminance' after --channel"
+                    ),
+                };
+            }
+            "--endian" => {
+                saving.endian = match args.next().map(String::as_str) {
+                    Some("little") => Endian::Little,
+                    Some("big") => Endian::Big,
+                    _ => bail!("expected 'little' or 'big' after --endian"),
+                };
+            }
+            "--remap" => {
+                let range = args.next().and_then(|arg| {
+                    let (min, max) = arg.split_once("..")?;
+                    Some((min.parse::<f64>().ok()?, max.parse::<f64>().ok()?))
+                });
+                let (min, max) = range.ok_or_else(|| anyhow!("expected MIN..MAX after --remap"))?;
+
+                // Checked here, so that a bad range is reported before anything is rendered.
+                if Raw::new(Depth::R16).with_range(min, max).is_none() {
+                    bail!("the range after --remap must have two different finite ends");
+                }
+                saving.range = Some((min, max));
+            }
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+
+    if files.is_empty() {
+        bail!("no files to render");
+    }
+
+    if let Some(output) = &saving.output {
+        if files.len() > 1 {
+            bail!("--output can only be used when rendering a single file");
+        }
+
+        if saving.format.is_none() && Format::from_path(output).is_none() {
+            bail!(
+                "cannot tell the format of {} from its extension, so it needs --format",
+                output.display()
+            );
+        }
+    }
+
+    let config = Config {
+        seed,
+        precision,
+        ..Config::new(size, size)
+    };
+    let mut renderer = Renderer::new(config)
+        .with_cache(cache)
+        .with_validation(validation);
+
+    if profiling.table || profiling.trace {
+        renderer = renderer.with_profiling();
+    }
+
+    let mut outputs: HashMap<PathBuf, Output> = HashMap::new();
+
+    loop {
+        let mut failed = false;
+
+        for file in files.iter() {
+            let output = outputs.entry(file.clone()).or_default();
+
+            // A file may be missing for a moment while an editor saves it, so report it and look again next time.
+            let modified = match fs::metadata(file).and_then(|metadata| metadata.modified()) {
+                Ok(modified) => modified,
+                Err(error) => {
+                    if !output.missing {
+                        eprintln!("could not read {}: {error}", file.display());
+                    }
+                    output.missing = true;
+                    output.modified = None;
+                    failed = true;
+                    continue;
+                }
+            };
+
+            output.missing = false;
+
+            let stale: HashSet<_> = output
+                .files
+                .iter()
+                .filter(|(path, seen)| modified_at(path) != **seen)
+                .map(|(path, _)| path.clone())
+                .collect();
+
+            if output.modified == Some(modified) && stale.is_empty() {
+                continue;
+            }
+
+            // Stale files are noted as seen even if the file fails to render, so that it is not tried again until the
+            // next change.
+            output.modified = Some(modified);
+            for path in stale.iter() {
+                output.files.insert(path.clone(), modified_at(path));
+            }
+
+            if let Err(error) = render_file(
+                &mut renderer,
+                file,
+                &params,
+                &saving,
+                output,
+                &stale,
+                profiling,
+            ) {
+                eprintln!("{error:#}");
+                failed = true;
+            }
+        }
+
+        if !watch {
+            if failed {
+                bail!("rendering failed");
+            }
+
+            return Ok(());
+        }
+
+        thread::sleep(Duration::from_millis(250));
+    }
+}
+
+/// How the time taken by each render is reported.
+#[derive(Clone, Copy, Debug, Default)]
+struct Profiling {
+    table: bool,
+    trace: bool,
+}
+
+/// Where and how rendered results are saved.
+#[derive(Debug, Default)]
+struct Saving {
+    output: Option<PathBuf>,
+    format: Option<Format>,
+    levels: Option<Levels>,
+    space: Option<Space>,
+
+    /// How raw heightmaps are written.
+    channel: Channel,
+    endian: Endian,
+    range: Option<(f64, f64)>,
+}
+
+impl Saving {
+    /// Get the path the result with the given index is saved to, and the format it is saved in.
+    fn path(&self, file: &Path, ndx: usize, results: usize) -> (PathBuf, Format) {
+        let format = self
+            .format
+            .or_else(|| self.output.as_deref().and_then(Format::from_path))
+            .unwrap_or(Format::Png8);
+
+        let base = match &self.output {
+            Some(output) => output.clone(),
+            None => file.with_extension(format.extension()),
+        };
+
+        if results == 1 {
+            return (base, format);
+        }
+
+        let stem = base.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = base.extension().unwrap_or_default().to_string_lossy();
+        (
+            base.with_file_name(format!("{stem}-{}.{extension}", ndx + 1)),
+            format,
+        )
+    }
+}
+
+/// What was last rendered from a file.
+#[derive(Debug, Default)]
+struct Output {
+    modified: Option<SystemTime>,
+
+    /// Whether the file could not be read when last looked at, so it is only reported once while it stays missing.
+    missing: bool,
+
+    /// The hash of each result of the file, as of the last time it was rendered.
+    hashes: Vec<u64>,
+
+    /// The files read by the nodes of the file, like images, and when each was modified as of the last time it was
+    /// rendered, or `None` if it was missing.
+    files: HashMap<PathBuf, Option<SystemTime>>,
+}
+
+fn modified_at(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+/// Find the files read by the nodes each result depends upon, such as images, resolved against the given directory.
+fn files_read(graph: &Graph, dir: &Path) -> Vec<HashSet<PathBuf>> {
+    let mut files: HashMap<NodeId, HashSet<PathBuf>> = HashMap::new();
+
+    for id in graph.ids() {
+        let node = graph.node(id);
+        let mut read: HashSet<_> = graph
+            .inputs(id)
+            .iter()
+            .flat_map(|input| files[input].iter().cloned())
+            .collect();
+
+        if builtin(node.name()).is_some_and(|info| info.external) {
+            let own = instantiate(node).map_or(vec![], |instance| instance.files());
+            read.extend(own.into_iter().map(|file| dir.join(file)));
+        }
+
+        files.insert(id, read);
+    }
+
+    graph.outputs().iter().map(|id| files[id].clone()).collect()
+}
+
+/// Render the results of a file which have changed since they were last rendered, or which read any of the given stale
+/// files.
+fn render_file(
+    renderer: &mut Renderer,
+    file: &Path,
+    params: &Params,
+    saving: &Saving,
+    output: &mut Output,
+    stale: &HashSet<PathBuf>,
+    profiling: Profiling,
+) -> Result<()> {
+    let mut sources = Sources::new();
+    let content = fs::read_to_string(file)?;
+    let id = sources.add(Source::from(content).with_name(file.display().to_string()));
+
+    let mut document = None;
+    let graph = load(file, &sources, id, params, &mut document)?;
+
+    let hashes = graph.hashes();
+    let hashes: Vec<_> = graph.outputs().iter().map(|id| hashes[id]).collect();
+
+    // Files are noted as they are before being read, so that a change made while rendering is seen next time.
+    let dir = file.parent().unwrap_or(Path::new(""));
+    let files = files_read(&graph, dir);
+    output.files = files
+        .iter()
+        .flatten()
+        .map(|path| (path.clone(), modified_at(path)))
+        .collect();
+
+    // Results are saved to paths numbered by their index only if there are several of them, so a change in their number
+    // moves every result and each has to be saved again.
+    let resized = output.hashes.len() != hashes.len();
+    let changed: Vec<_> = graph
+        .outputs()
+        .iter()
+        .zip(hashes.iter())
+        .enumerate()
+        .filter(|(ndx, (_, hash))| {
+            resized || output.hashes.get(*ndx) != Some(hash) || !files[*ndx].is_disjoint(stale)
+        })
+        .map(|(ndx, (id, _))| (ndx, *id))
+        .collect();
+
+    let ids: Vec<_> = changed.iter().map(|(_, id)| *id).collect();
+    renderer.set_dir(dir);
+    let textures = match renderer.render(&graph, &ids) {
+        Ok(textures) => textures,
+        Err(error) => {
+            eprintln!("{}", error.report(&sources));
+            bail!("{} failed to render", file.display());
+        }
+    };
+
+    for (ndx, id) in changed.iter() {
+        let (path, format) = saving.path(file, *ndx, graph.outputs().len());
+        let levels = saving.levels.unwrap_or(format.default_levels());
+
+        let depth = match format {
+            Format::R16 => Some(Depth::R16),
+            Format::R32 => Some(Depth::R32),
+            _ => None,
+        };
+
+        let saved = match depth {
+            Some(depth) => {
+                let (min, max) = saving.range.unwrap_or((0.0, 1.0));
+                let raw = Raw::new(depth)
+                    .with_channel(saving.channel)
+                    .with_endian(saving.endian)
+                    .with_range(min, max)
+                    .expect("the range was checked when given");
+                textures[id]
+                    .save_raw(&path, &raw, levels)
+                    .map_err(Into::into)
+            }
+            None => {
+                let space = saving.space.unwrap_or(format.default_space());
+                textures[id].save_as(&path, format, levels, space)
+            }
+        };
+        saved.with_context(|| format!("could not save {}", path.display()))?;
+    }
+
+    eprintln!(
+        "rendered {} of {} results of {}",
+        changed.len(),
+        hashes.len(),
+        file.display()
+    );
+
+    if resized {
+        remove_stale(file, saving, output.hashes.len(), hashes.len());
+    }
+
+    if let Some(profile) = renderer.take_profile() {
+        if profiling.table {
+            eprint!("{}", profile.table(&sources));
+        }
+
+        if profiling.trace {
+            let trace = profile.chrome_trace(&sources).to_string();
+            fs::write(file.with_extension("trace.json"), trace)?;
+        }
+    }
+
+    output.hashes = hashes;
+    Ok(())
+}
+
+/// Remove the files saved for the results of a file when it had a different number of results, which would otherwise be
+/// left behind looking current.
+fn remove_stale(file: &Path, saving: &Saving, before: usize, after: usize) {
+    let current: Vec<_> = (0..after)
+        .map(|ndx| saving.path(file, ndx, after).0)
+        .collect();
+
+    for ndx in 0..before {
+        let (path, _) = saving.path(file, ndx, before);
+        if current.contains(&path) || !path.exists() {
+            continue;
+        }
+
+        match fs::remove_file(&path) {
+            Ok(()) => eprintln!("removed {}, which is no longer produced", path.display()),
+            Err(error) => eprintln!("could not remove {}: {error}", path.display()),
+        }
+    }
+}
+
+/// ```text
+/// cobble graph [--format dot|svg|json] [--thumbnails DIR] [--size SIZE] [--seed SEED] [--set NAME=VALUE]... FILE
+/// ```
+///
+/// Print the graph a file resolves to in Graphviz's DOT language, as an SVG drawn by Graphviz's `dot`, which must be
+/// installed, or as a graph document which `render` and `graph` also accept in place of a script. With `--thumbnails`,
+/// every node is rendered at the given size, 64 by default, into the given directory, and shown in the graph.
+fn graph(args: &[String]) -> Result<()> {
+    let mut svg = false;
+    let mut json = false;
+    let mut thumbnails = None;
+    let mut size = 64;
+    let mut seed = None;
+    let mut params = Params::new();
+    let mut file = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                (svg, json) = match args.next().map(String::as_str) {
+                    Some("dot") => (false, false),
+                    Some("svg") => (true, false),
+                    Some("json") => (false, true),
+                    _ => bail!("expected 'dot', 'svg' or 'json' after --format"),
+                };
+            }
+            "--thumbnails" => {
+                let arg = args
+                    .next()
+                    .ok_or_else(|| anyhow!("missing thumbnail directory"))?;
+                thumbnails = Some(PathBuf::from(arg));
+            }
+            "--size" => {
+                let arg = args.next().ok_or_else(|| anyhow!("missing size"))?;
+                size = arg.parse()?;
+            }
+            "--seed" => {
+                let arg = args.next().ok_or_else(|| anyhow!("missing seed"))?;
+                seed = Some(arg.parse()?);
+            }
+            "--set" => set_param(&mut params, args.next())?,
+            _ if file.is_none() => file = Some(PathBuf::from(arg)),
+            _ => bail!("expected a single file"),
+        }
+    }
+
+    let Some(file) = file else {
+        bail!("no file to draw");
+    };
+
+    let mut sources = Sources::new();
+    let content = fs::read_to_string(&file)?;
+    let id = sources.add(Source::from(content).with_name(file.display().to_string()));
+
+    let mut document = None;
+    let graph = load(&file, &sources, id, &params, &mut document)?;
+
+    if json {
+        println!("{}", Document::from_graph(&graph).save());
+        return Ok(());
+    }
+
+    let mut paths = Vec::new();
+
+    if let Some(dir) = thumbnails {
+        let config = Config {
+            seed,
+            ..Config::new(size, size)
+        };
+
+        let ids: Vec<_> = graph.ids().collect();
+        let mut renderer = Renderer::new(config).with_dir(file.parent().unwrap_or(Path::new("")));
+        let textures = match renderer.render(&graph, &ids) {
+            Ok(textures) => textures,
+            Err(error) => {
+                eprintln!("{}", error.report(&sources));
+                bail!("{} failed to render", file.display());
+            }
+        };
+
+        fs::create_dir_all(&dir)?;
+        for (id, texture) in textures {
+            let path = dir.join(format!("n{}.png", id.index()));
+            texture.to_image().save(&path)?;
+            paths.push((id, path));
+        }
+    }
+
+    let dot = graph
+        .dot()
+        .with_sources(&sources)
+        .with_thumbnails(paths)
+        .to_string();
+
+    if !svg {
+        print!("{dot}");
+        return Ok(());
+    }
+
+    let mut child = Command::new("dot")
+        .arg("-Tsvg")
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("could not run graphviz's dot")?;
+
+    child
+        .stdin
+        .take()
+        .expect("piped stdin")
+        .write_all(dot.as_bytes())?;
+
+    if !child.wait()?.success() {
+        bail!("graphviz's dot failed");
+    }
+
+    Ok(())
+}
+
+/// ```text
+/// cobble params [--json] [--set NAME=VALUE]... FILE
+/// ```
+///
+/// List the parameters a script declares, along with their values, ranges and documentation. With `--json`, they are
+/// printed as an array of objects instead, for other tools to read.
+fn params(args: &[String]) -> Result<()> {
+    let mut json = false;
+    let mut params = Params::new();
+    let mut file = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--set" => set_param(&mut params, args.next())?,
+            _ if file.is_none() => file = Some(PathBuf::from(arg)),
+            _ => bail!("expected a single file"),
+        }
+    }
+
+    let Some(file) = file else {
+        bail!("no file to list the parameters of");
+    };
+
+    let mut sources = Sources::new();
+    let content = fs::read_to_string(&file)?;
+    let id = sources.add(Source::from(content).with_name(file.display().to_string()));
+
+    let mut document = None;
+    let graph = load(&file, &sources, id, &params, &mut document)?;
+
+    if json {
+        let params: Vec<_> = graph
+            .params()
+            .iter()
+            .map(|param| {
+                serde_json::json!({
+                    "name": param.name,
+                    "doc": param.doc,
+                    "default": param.default,
+                    "min": param.range.map(|(min, _)| min),
+                    "max": param.range.map(|(_, max)| max),
+                    "value": param.value,
+                })
+            })
+            .collect();
+
+        println!("{}", serde_json::to_string_pretty(&params)?);
+        return Ok(());
+    }
+
+    for param in graph.params() {
+        for line in param.doc.lines() {
+            println!("-- {line}");
+        }
+
+        print!("{} = {}", param.name, param.value);
+        if let Some((min, max)) = param.range {
+            print!(" ({min}..{max})");
+        }
+        println!();
+    }
+
+    Ok(())
+}
+
+/// ```text
+/// cobble stats [--json] [--bins BINS] [--size SIZE] [--seed SEED] [--precision f64|f32|f16|u16] [--set NAME=VALUE]... FILE
+/// ```
+///
+/// Render the results of a file and print the minimum, maximum, mean and variance of each channel they store, along
+/// with a histogram of each channel over its own range, in 16 bins by default. Values which are not finite
+/// are counted instead. With `--json`, everything is printed as an array of objects instead, for other tools to read.
+fn stats(args: &[String]) -> Result<()> {
+    let mut json = false;
+    let mut bins = 16;
+    let mut size = 256;
+    let mut seed = None;
+    let mut precision = Precision::default();
+    let mut params = Params::new();
+    let mut file = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--bins" => {
+                let arg = args.next().ok_or_else(|| anyhow!("missing bin count"))?;
+                bins = arg.parse()?;
+                if bins == 0 {
+                    bail!("expected at least one bin");
+                }
+            }
+            "--size" => {
+                let arg = args.next().ok_or_else(|| anyhow!("missing size"))?;
+                size = arg.parse()?;
+            }
+            "--seed" => {
+                let arg = args.next().ok_or_else(|| anyhow!("missing seed"))?;
+                seed = Some(arg.parse()?);
+            }
+            "--precision" => precision = parse_precision(args.next())?,
+            "--set" => set_param(&mut params, args.next())?,
+            _ if file.is_none() => file = Some(PathBuf::from(arg)),
+            _ => bail!("expected a single file"),
+        }
+    }
+
+    let Some(file) = file else {
+        bail!("no file to compute the statistics of");
+    };
+
+    let mut sources = Sources::new();
+    let content = fs::read_to_string(&file)?;
+    let id = sources.add(Source::from(content).with_name(file.display().to_string()));
+
+    let mut document = None;
+    let graph = load(&file, &sources, id, &params, &mut document)?;
+
+    let config = Config {
+        seed,
+        precision,
+        ..Config::new(size, size)
+    };
+    let mut renderer = Renderer::new(config).with_dir(file.parent().unwrap_or(Path::new("")));
+    let textures = match renderer.render(&graph, graph.outputs()) {
+        Ok(textures) => textures,
+        Err(error) => {
+            eprintln!("{}", error.report(&sources));
+            bail!("{} failed to render", file.display());
+        }
+    };
+
+    let mut results = Vec::new();
+    for (ndx, id) in graph.outputs().iter().enumerate() {
+        let texture = &textures[id];
+        let stats = texture.stats();
+        let histogram = texture.histogram(bins);
+
+        // Gray textures read back their value in every colour channel, so only the channels they store are shown.
+        let channels: &[(&str, usize)] = match texture.channels() {
+            Channels::Gray => &[("value", 0)],
+            Channels::GrayAlpha => &[("value", 0), ("alpha", 3)],
+            Channels::Rgba => &[("red", 0), ("green", 1), ("blue", 2), ("alpha", 3)],
+        };
+
+        let name = match graph.output_name(ndx) {
+            Some(name) => format!("result {} ({name})", ndx + 1),
+            None => format!("result {}", ndx + 1),
+        };
+
+        if json {
+            let channels: Vec<_> = channels
+                .iter()
+                .map(|&(channel, ch)| {
+                    serde_json::json!({
+                        "channel": channel,
+                        "min": finite(stats.min.0[ch]),
+                        "max": finite(stats.max.0[ch]),
+                        "mean": finite(stats.mean.0[ch]),
+                        "variance": finite(stats.variance.0[ch]),
+                        "non_finite": stats.non_finite[ch],
+                        "histogram": {
+                            "min": histogram.min[ch],
+                            "max": histogram.max[ch],
+                            "counts": histogram.counts[ch],
+                        },
+                    })
+                })
+                .collect();
+
+            results.push(serde_json::json!({
+                "result": ndx + 1,
+                "name": graph.output_name(ndx),
+                "channels": channels,
+            }));
+            continue;
+        }
+
+        println!("{name}");
+        println!(
+            "  {:<8}{:>12}{:>12}{:>12}{:>12}{:>12}",
+            "channel", "min", "max", "mean", "variance", "non-finite"
+        );
+        for &(channel, ch) in channels {
+            println!(
+                "  {channel:<8}{:>12.6}{:>12.6}{:>12.6}{:>12.6}{:>12}",
+                stats.min.0[ch],
+                stats.max.0[ch],
+                stats.mean.0[ch],
+                stats.variance.0[ch],
+                stats.non_finite[ch]
+            );
+        }
+
+        println!("  histogram");
+        for &(channel, ch) in channels {
+            println!(
+                "  {channel:<8}{:>12.6} {} {:.6}",
+                histogram.min[ch],
+                sparkline(&histogram.counts[ch]),
+                histogram.max[ch]
+            );
+        }
+        println!();
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    }
+
+    Ok(())
+}
+
+/// Turn a value into JSON, as `null` if it is not finite, which JSON cannot represent.
+fn finite(value: f64) -> serde_json::Value {
+    match value.is_finite() {
+        true => value.into(),
+        false => serde_json::Value::Null,
+    }
+}
+
+/// Draw counts as a row of bars, each as tall as its count relative to the largest. Non-zero counts always get at
+/// least the shortest bar, so rare values still show up.
+fn sparkline(counts: &[usize]) -> String {
+    const BARS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+    counts
+        .iter()
+        .map(|&count| match count {
+            0 => BARS[0],
+            count => BARS[(count * (BARS.len() - 1)).div_ceil(max)],
+        })
+        .collect()
+}
+
+/// Add the value of a parameter given as `NAME=VALUE`.
+fn set_param(params: &mut Params, arg: Option<&String>) -> Result<()> {
+    let Some((name, value)) = arg.and_then(|arg| arg.split_once('=')) else {
+        bail!("expected NAME=VALUE after --set");
+    };
+
+    params.set(name.trim(), value.trim());
+    Ok(())
+}
+
+/// Parse how textures are stored while rendering, given as `f64`, `f32`, `f16` or `u16`.
+fn parse_precision(arg: Option<&String>) -> Result<Precision> {
+    match arg.map(String::as_str) {
+        Some("f64") => Ok(Precision::F64),
+        Some("f32") => Ok(Precision::F32),
+        Some("f16") => Ok(Precision::F16),
+        Some("u16") => Ok(Precision::U16),
+        _ => bail!("expected 'f64', 'f32', 'f16' or 'u16' after --precision"),
+    }
+}
+
+/// Compile a file, or load it as a graph document if it ends in `.json`, reporting any errors. Values for parameters
+/// the script does not declare are errors, as are any values at all for documents, which have no parameters.
+fn load<'a>(
+    file: &Path,
+    sources: &'a Sources,
+    id: SourceId,
+    params: &'a Params,
+    document: &'a mut Option<Document>,
+) -> Result<Graph<'a>> {
+    if file.extension().is_some_and(|ext| ext == "json") {
+        if !params.is_empty() {
+            bail!(
+                "{} is a graph document, which has no parameters",
+                file.display()
+            );
+        }
+
+        let loaded = Document::load(&sources.get(&id).content)
+            .with_context(|| format!("{} failed to load", file.display()))?;
+        let graph = document
+            .insert(loaded)
+            .graph()
+            .with_context(|| format!("{} failed to load", file.display()))?;
+        return Ok(graph);
+    }
+
+    let mut errors = Errors::new();
+    let graph = compile_with_params(sources, id, params, &mut errors);
+
+    if !errors.is_empty() {
+        for error in errors.errors.iter() {
+            eprintln!("{}", error.report(sources));
+        }
+
+        bail!("{} failed to compile", file.display());
+    }
+
+    if let Some(name) = params
+        .names()
+        .find(|name| !graph.params().iter().any(|param| param.name == *name))
+    {
+        bail!("{} has no parameter named '{name}'", file.display());
+    }
+
+    Ok(graph)
+}
+
+/// Format a single source, reporting any syntax errors.
+fn format_source(sources: &Sources, id: SourceId) -> Result<String> {
+    let mut errors = Errors::new();
+    let text = &sources.get(&id).content;
+    let cst = parse_cst(text, id, lex(sources, id), &mut errors);
+
+    if !errors.is_empty() {
+        for error in errors.errors.iter() {
+            eprintln!("{}", error.report(sources));
+        }
+
+        bail!("cannot format a program with syntax errors");
+    }
+
+    Ok(format(&cst))
+}
+
+/// Render the example texture.
+fn demo() -> Result<()> {
     let config = Config::new(256, 256);
 
     let mut smooth = Voronoi::new(50).with_metric(Metric::Euclidian);
@@ -33,14 +1067,7 @@ fn main() -> Result<()> {
     random.render(&config, &mut buf2);
     mix2.render(&config, (&buf1, &buf2), &mut buf3);
 
-    let img = RgbaImage::from_vec(
-        config.width as u32,
-        config.height as u32,
-        buf3.pixels().flat_map(|pix| pix.as_rgba8_sat()).collect(),
-    )
-    .ok_or_else(|| anyhow!("incorrect image dimensions"))?;
-
-    img.save("test.png")?;
+    buf3.to_image().save("test.png")?;
 
     Ok(())
 }
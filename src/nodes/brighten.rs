@@ -15,9 +15,9 @@ impl Node for Brightener {}
 
 impl Processor for Brightener {
     fn render(&mut self, _config: &Config, input: &Texture, target: &mut Texture) {
-        for (pixel, x, y) in target.pixels_mut_absolute() {
+        target.fill_absolute(|x, y| {
             let input = input.at_absolute(x, y);
-            *pixel = input.powf(1.0 / self.factor);
-        }
+            input.powf(1.0 / self.factor)
+        });
     }
 }
@@ -0,0 +1,13 @@
+use crate::{Config, Node, Processor, Texture};
+
+/// Spreads a scalar texture across every colour channel. Scalars are already read as gray, so this only changes how the
+/// output is stored and what the compiler takes it to be.
+pub struct Broadcast;
+
+impl Node for Broadcast {}
+
+impl Processor for Broadcast {
+    fn render(&mut self, _config: &Config, input: &Texture, target: &mut Texture) {
+        target.fill_absolute(|x, y| input.at_absolute(x, y));
+    }
+}
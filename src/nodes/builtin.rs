@@ -0,0 +1,648 @@
+//! Descriptions of the nodes available to scripts.
+//!
+//! Every node reads and writes linear values, so blends and arithmetic behave like light does. Nodes which depend on
+//! how colours appear, rather than on how much light they stand for, say so in their documentation.
+//!
+//! Alpha is straight, and says how much of a pixel is covered. Generators are opaque, except for images with
+//! transparency, and processors keep the alpha of their input. Combiners which are not about compositing only cover
+//! what both of their inputs cover.
+//!
+//! Every node also says which [kinds](TextureKind) of texture it takes and makes, which the compiler checks. Scalars
+//! and colours are converted into each other where a node only takes one of them, by nodes the compiler inserts.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::compiler::graph;
+use crate::compiler::Value;
+use crate::nodes::{
+    Brightener, Broadcast, Cells, Dot, Dot3, GeoMix, Gradient, Grayscale, Image, LInvert, Math,
+    Mix, Operation, Over, Random, Simplex, Voronoi,
+};
+use crate::{Channels, Combiner, Filter, Generator, Metric, Precision, Processor, Space};
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum NodeKind {
+    /// Produces a texture from nothing.
+    Generator,
+
+    /// Transforms a single texture.
+    Processor,
+
+    /// Combines two textures into one.
+    Combiner,
+}
+
+impl NodeKind {
+    /// Get the number of inputs nodes of this kind take.
+    pub fn inputs(&self) -> usize {
+        match self {
+            NodeKind::Generator => 0,
+            NodeKind::Processor => 1,
+            NodeKind::Combiner => 2,
+        }
+    }
+}
+
+/// What the pixels of a texture stand for. Every texture is rendered as RGBA pixels, but each node says which kinds of
+/// texture it takes and makes, so that textures are not used as something they are not.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TextureKind {
+    /// The same value in every colour channel, like a distance or a height.
+    Scalar,
+
+    /// A colour in the RGB channels.
+    Colour,
+
+    /// A 2D vector in the red and green channels, like a gradient or a flow.
+    Vector,
+
+    /// The same whole number in every colour channel, identifying the region a pixel belongs to.
+    Label,
+}
+
+impl TextureKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextureKind::Scalar => "scalar",
+            TextureKind::Colour => "colour",
+            TextureKind::Vector => "vector",
+            TextureKind::Label => "label",
+        }
+    }
+
+    /// Get the precision textures of this kind are stored at when the given precision is asked for. Labels must stay
+    /// whole numbers, so are never stored at less than 32 bits.
+    pub fn precision(&self, precision: Precision) -> Precision {
+        match self {
+            TextureKind::Label if precision.bytes() < 4 => Precision::F32,
+            _ => precision,
+        }
+    }
+}
+
+impl fmt::Display for TextureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A conversion between kinds of texture which is safe to make without being asked for.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Conversion {
+    /// A scalar used as a colour becomes gray.
+    Broadcast,
+
+    /// A colour used as a scalar becomes its luminance.
+    Luminance,
+}
+
+impl Conversion {
+    /// Find the conversion from one kind to another, if there is one.
+    pub fn between(from: TextureKind, to: TextureKind) -> Option<Self> {
+        match (from, to) {
+            (TextureKind::Scalar, TextureKind::Colour) => Some(Conversion::Broadcast),
+            (TextureKind::Colour, TextureKind::Scalar) => Some(Conversion::Luminance),
+            _ => None,
+        }
+    }
+
+    /// Get the kind of texture this conversion makes.
+    pub fn to(&self) -> TextureKind {
+        match self {
+            Conversion::Broadcast => TextureKind::Colour,
+            Conversion::Luminance => TextureKind::Scalar,
+        }
+    }
+
+    /// Get the name of the built-in node which makes this conversion.
+    pub fn node(&self) -> &'static str {
+        match self {
+            Conversion::Broadcast => "broadcast",
+            Conversion::Luminance => "grayscale",
+        }
+    }
+}
+
+/// Why a node cannot take the kinds of texture it was given.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Mismatch {
+    /// An input is of a kind the node does not take, and cannot be converted into one it does.
+    Input {
+        input: usize,
+        given: TextureKind,
+        expected: &'static [TextureKind],
+    },
+
+    /// The inputs are of kinds which cannot be converted into a single kind, as nodes whose output is of the same kind
+    /// as their inputs need.
+    Inputs(TextureKind, TextureKind),
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ParamKind {
+    Number,
+
+    /// A number greater than zero.
+    Positive,
+
+    /// One of the given symbols.
+    Symbol(&'static [&'static str]),
+
+    String,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct ParamInfo {
+    pub name: &'static str,
+    pub kind: ParamKind,
+
+    /// The value used if the argument is not given, or `None` if it is required.
+    pub default: Option<&'static str>,
+
+    pub doc: &'static str,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct NodeInfo {
+    pub name: &'static str,
+    pub kind: NodeKind,
+
+    /// The kinds of texture every input may be. Inputs of other kinds are converted if they safely can be.
+    pub inputs: &'static [TextureKind],
+
+    /// The kind of texture this node outputs, or `None` if it outputs the same kind as its inputs.
+    pub output: Option<TextureKind>,
+
+    /// The parameters of this node, in the order they may be given positionally.
+    pub params: &'static [ParamInfo],
+
+    /// Whether this node draws random numbers, so that its output depends on the seed and not just its arguments and
+    /// inputs.
+    pub random: bool,
+
+    /// Whether this node reads from outside the graph, such as from a file, so that its output may change without its
+    /// arguments or inputs changing.
+    pub external: bool,
+
+    /// The channels the output of this node is stored with. Nodes whose output is always gray store fewer channels.
+    pub channels: Channels,
+
+    pub doc: &'static str,
+}
+
+impl NodeInfo {
+    pub fn param(&self, name: &str) -> Option<&'static ParamInfo> {
+        self.params.iter().find(|param| param.name == name)
+    }
+
+    /// Describe the kinds of texture this node takes and makes, such as `colour | scalar -> scalar`. Nodes which output
+    /// the kind of their inputs are described as outputting the `same`.
+    pub fn kinds(&self) -> String {
+        let output = self.output.map_or("same", |kind| kind.name());
+        let inputs: Vec<_> = self.inputs.iter().map(TextureKind::name).collect();
+
+        match self.kind.inputs() {
+            0 => output.to_string(),
+            _ => format!("{} -> {output}", inputs.join(" | ")),
+        }
+    }
+
+    /// Check the kinds of texture given to this node, finding the conversion each input needs, if any, and the kind of
+    /// texture the node then outputs. Nodes which output the same kind as their inputs only ever broadcast scalars to
+    /// colours to make their inputs agree, so that no information is lost.
+    pub fn check(
+        &self,
+        given: &[TextureKind],
+    ) -> Result<(Vec<Option<Conversion>>, TextureKind), Mismatch> {
+        let convert = |input: usize, given: TextureKind, to: &[TextureKind]| {
+            if to.contains(&given) {
+                return Ok((given, None));
+            }
+
+            to.iter()
+                .find_map(|to| Some((*to, Some(Conversion::between(given, *to)?))))
+                .ok_or(Mismatch::Input {
+                    input,
+                    given,
+                    expected: self.inputs,
+                })
+        };
+
+        let output =
+            match self.output {
+                Some(output) => output,
+                None => {
+                    let joined = given.iter().copied().try_fold(None, |joined, kind| {
+                        match (joined, kind) {
+                            (None, kind) => Ok(Some(kind)),
+                            (Some(joined), kind) if joined == kind => Ok(Some(kind)),
+                            (Some(TextureKind::Scalar), TextureKind::Colour)
+                            | (Some(TextureKind::Colour), TextureKind::Scalar) => {
+                                Ok(Some(TextureKind::Colour))
+                            }
+                            (Some(joined), kind) => Err(Mismatch::Inputs(joined, kind)),
+                        }
+                    })?;
+
+                    let joined =
+                        joined.expect("nodes which output the kind of their inputs take inputs");
+                    convert(0, joined, self.inputs)?.0
+                }
+            };
+
+        let target: &[TextureKind] = match self.output {
+            Some(_) => self.inputs,
+            None => &[output],
+        };
+
+        let conversions = given
+            .iter()
+            .enumerate()
+            .map(|(input, given)| Ok(convert(input, *given, target)?.1))
+            .collect::<Result<_, _>>()?;
+
+        Ok((conversions, output))
+    }
+
+    /// Get the value of an argument given to a node, falling back to the parameter's default.
+    pub fn arg<'a>(&self, node: &graph::Node<'a>, name: &str) -> Option<Value<'a>> {
+        let ndx = self.params.iter().position(|param| param.name == name)?;
+        let param = &self.params[ndx];
+
+        let given = node.positional().get(ndx).or_else(|| {
+            node.named()
+                .iter()
+                .find(|(arg, _)| *arg == name)
+                .map(|(_, value)| value)
+        });
+
+        match (given, param.kind, param.default) {
+            (Some(value), _, _) => Some(value.clone()),
+            (None, ParamKind::Number | ParamKind::Positive, Some(default)) => {
+                Some(Value::Number(default))
+            }
+            (None, ParamKind::Symbol(_), Some(default)) => Some(Value::Symbol(default)),
+            (None, ParamKind::String, Some(default)) => Some(Value::String(default)),
+            (None, _, None) => None,
+        }
+    }
+}
+
+/// A node ready to be rendered.
+pub enum Instance {
+    Generator(Box<dyn Generator>),
+    Processor(Box<dyn Processor>),
+    Combiner(Box<dyn Combiner>),
+}
+
+impl Instance {
+    /// Get the files the node reads when it is loaded, relative to the directory it is loaded from.
+    pub fn files(&self) -> Vec<PathBuf> {
+        match self {
+            Instance::Generator(node) => node.files(),
+            Instance::Processor(node) => node.files(),
+            Instance::Combiner(node) => node.files(),
+        }
+    }
+}
+
+const METRICS: &[&str] = &["Euclidian", "EuclidianSquared", "Manhattan", "Chebyshev"];
+const OPERATIONS: &[&str] = &["Add", "Multiply", "Subtract", "Divide"];
+const FILTERS: &[&str] = &["Nearest", "Bilinear", "Bicubic", "Lanczos"];
+const SPACES: &[&str] = &["Linear", "Srgb"];
+
+pub const BUILTINS: &[NodeInfo] = &[
+    NodeInfo {
+        name: "voronoi",
+        kind: NodeKind::Generator,
+        inputs: &[],
+        output: Some(TextureKind::Scalar),
+        params: &[
+            ParamInfo {
+                name: "points",
+                kind: ParamKind::Number,
+                default: Some("20"),
+                doc: "the number of randomly placed cell centres",
+            },
+            ParamInfo {
+                name: "metric",
+                kind: ParamKind::Symbol(METRICS),
+                default: Some("Euclidian"),
+                doc: "how distances to the cell centres are measured",
+            },
+        ],
+        random: true,
+        external: false,
+        channels: Channels::Gray,
+        doc: "the distance to the nearest of a set of random points, normalized to the `[0, 1]` range",
+    },
+    NodeInfo {
+        name: "cells",
+        kind: NodeKind::Generator,
+        inputs: &[],
+        output: Some(TextureKind::Label),
+        params: &[
+            ParamInfo {
+                name: "points",
+                kind: ParamKind::Number,
+                default: Some("20"),
+                doc: "the number of randomly placed cell centres",
+            },
+            ParamInfo {
+                name: "metric",
+                kind: ParamKind::Symbol(METRICS),
+                default: Some("Euclidian"),
+                doc: "how distances to the cell centres are measured",
+            },
+        ],
+        random: true,
+        external: false,
+        channels: Channels::Gray,
+        doc: "the index of the nearest of a set of random points, counting from zero",
+    },
+    NodeInfo {
+        name: "simplex",
+        kind: NodeKind::Generator,
+        inputs: &[],
+        output: Some(TextureKind::Colour),
+        params: &[ParamInfo {
+            name: "scale",
+            kind: ParamKind::Number,
+            default: Some("1"),
+            doc: "the frequency of the noise",
+        }],
+        random: true,
+        external: false,
+        channels: Channels::Rgba,
+        doc: "fractal simplex noise, with a separate layer for each colour channel",
+    },
+    NodeInfo {
+        name: "random",
+        kind: NodeKind::Generator,
+        inputs: &[],
+        output: Some(TextureKind::Colour),
+        params: &[],
+        random: true,
+        external: false,
+        channels: Channels::Rgba,
+        doc: "uniform white noise in every colour channel, fully opaque",
+    },
+    NodeInfo {
+        name: "image",
+        kind: NodeKind::Generator,
+        inputs: &[],
+        output: Some(TextureKind::Colour),
+        params: &[
+            ParamInfo {
+                name: "path",
+                kind: ParamKind::String,
+                default: None,
+                doc: "the PNG, JPEG or raw heightmap file to read, relative to the script",
+            },
+            ParamInfo {
+                name: "filter",
+                kind: ParamKind::Symbol(FILTERS),
+                default: Some("Bilinear"),
+                doc: "how the image is resampled to the size being rendered",
+            },
+            ParamInfo {
+                name: "space",
+                kind: ParamKind::Symbol(SPACES),
+                default: Some("Srgb"),
+                doc: "the colour space the file is stored in, which is converted to linear as it is read",
+            },
+        ],
+        random: false,
+        external: true,
+        channels: Channels::Rgba,
+        doc: "an image read from a file, resampled to the size being rendered with wrap-around filtering",
+    },
+    NodeInfo {
+        name: "invert",
+        kind: NodeKind::Processor,
+        inputs: &[TextureKind::Colour, TextureKind::Scalar],
+        output: None,
+        params: &[],
+        random: false,
+        external: false,
+        channels: Channels::Rgba,
+        doc: "inverts the lightness of a texture as it appears in sRGB, keeping its hue and saturation",
+    },
+    NodeInfo {
+        name: "grayscale",
+        kind: NodeKind::Processor,
+        inputs: &[TextureKind::Colour, TextureKind::Scalar],
+        output: Some(TextureKind::Scalar),
+        params: &[],
+        random: false,
+        external: false,
+        channels: Channels::GrayAlpha,
+        doc: "converts a texture to the gray of the same luminance, which is inserted wherever a colour is used as a scalar",
+    },
+    NodeInfo {
+        name: "broadcast",
+        kind: NodeKind::Processor,
+        inputs: &[TextureKind::Scalar],
+        output: Some(TextureKind::Colour),
+        params: &[],
+        random: false,
+        external: false,
+        channels: Channels::Rgba,
+        doc: "the gray colour of a scalar texture, which is inserted wherever a scalar is used as a colour",
+    },
+    NodeInfo {
+        name: "gradient",
+        kind: NodeKind::Processor,
+        inputs: &[TextureKind::Scalar],
+        output: Some(TextureKind::Vector),
+        params: &[],
+        random: false,
+        external: false,
+        channels: Channels::Rgba,
+        doc: "how fast a scalar texture grows along each axis, per unit of the texture's width and height",
+    },
+    NodeInfo {
+        name: "brighten",
+        kind: NodeKind::Processor,
+        inputs: &[TextureKind::Colour, TextureKind::Scalar],
+        output: None,
+        params: &[ParamInfo {
+            name: "factor",
+            kind: ParamKind::Positive,
+            default: None,
+            doc: "how much to brighten by; each channel is raised to the power of one over this",
+        }],
+        random: false,
+        external: false,
+        channels: Channels::Rgba,
+        doc: "brightens a texture by a gamma-like curve",
+    },
+    NodeInfo {
+        name: "math",
+        kind: NodeKind::Combiner,
+        inputs: &[TextureKind::Colour, TextureKind::Scalar, TextureKind::Vector],
+        output: None,
+        params: &[ParamInfo {
+            name: "op",
+            kind: ParamKind::Symbol(OPERATIONS),
+            default: None,
+            doc: "the operation to apply to each pair of pixels",
+        }],
+        random: false,
+        external: false,
+        channels: Channels::Rgba,
+        doc: "combines the colours of two textures pixel by pixel with an arithmetic operation, covering only what both cover",
+    },
+    NodeInfo {
+        name: "mix",
+        kind: NodeKind::Combiner,
+        inputs: &[TextureKind::Colour, TextureKind::Scalar, TextureKind::Vector],
+        output: None,
+        params: &[ParamInfo {
+            name: "factor",
+            kind: ParamKind::Number,
+            default: Some("0.5"),
+            doc: "how much of the second texture to use",
+        }],
+        random: false,
+        external: false,
+        channels: Channels::Rgba,
+        doc: "linearly interpolates between two textures, blending their light physically and their alpha by coverage",
+    },
+    NodeInfo {
+        name: "geomix",
+        kind: NodeKind::Combiner,
+        inputs: &[TextureKind::Colour, TextureKind::Scalar],
+        output: None,
+        params: &[ParamInfo {
+            name: "factor",
+            kind: ParamKind::Number,
+            default: Some("0.5"),
+            doc: "how much of the second texture to use",
+        }],
+        random: false,
+        external: false,
+        channels: Channels::Rgba,
+        doc: "geometrically interpolates between two textures",
+    },
+    NodeInfo {
+        name: "over",
+        kind: NodeKind::Combiner,
+        inputs: &[TextureKind::Colour],
+        output: Some(TextureKind::Colour),
+        params: &[],
+        random: false,
+        external: false,
+        channels: Channels::Rgba,
+        doc: "composites the first texture over the second by its alpha",
+    },
+    NodeInfo {
+        name: "dot",
+        kind: NodeKind::Combiner,
+        inputs: &[TextureKind::Colour],
+        output: Some(TextureKind::Scalar),
+        params: &[],
+        random: false,
+        external: false,
+        channels: Channels::GrayAlpha,
+        doc: "the dot product of two textures treated as normal maps, as a grayscale texture",
+    },
+    NodeInfo {
+        name: "dot3",
+        kind: NodeKind::Combiner,
+        inputs: &[TextureKind::Colour],
+        output: Some(TextureKind::Colour),
+        params: &[],
+        random: false,
+        external: false,
+        channels: Channels::Rgba,
+        doc: "the per-channel product of two textures treated as normal maps",
+    },
+];
+
+/// Look up the description of a built-in node by its script name.
+pub fn builtin(name: &str) -> Option<&'static NodeInfo> {
+    BUILTINS.iter().find(|info| info.name == name)
+}
+
+/// Create the node a graph node describes, or `None` if it is not a built-in node or its arguments are invalid.
+pub fn instantiate(node: &graph::Node) -> Option<Instance> {
+    let info = builtin(node.name())?;
+    let number = |name| info.arg(node, name)?.number();
+    let symbol = |name| match info.arg(node, name)? {
+        Value::Symbol(sym) => Some(sym),
+        Value::Number(_) | Value::String(_) | Value::Param(_) => None,
+    };
+
+    let metric = || match symbol("metric")? {
+        "Euclidian" => Some(Metric::Euclidian),
+        "EuclidianSquared" => Some(Metric::EuclidianSquared),
+        "Manhattan" => Some(Metric::Manhattan),
+        "Chebyshev" => Some(Metric::Chebyshev),
+        _ => None,
+    };
+
+    let instance = match info.name {
+        "voronoi" => {
+            let points = number("points")?.max(0.0) as usize;
+            Instance::Generator(Box::new(Voronoi::new(points).with_metric(metric()?)))
+        }
+
+        "cells" => {
+            let points = number("points")?.max(0.0) as usize;
+            Instance::Generator(Box::new(Cells::new(points).with_metric(metric()?)))
+        }
+
+        "simplex" => Instance::Generator(Box::new(Simplex::new(number("scale")?))),
+        "random" => Instance::Generator(Box::new(Random)),
+        "image" => {
+            let filter = match symbol("filter")? {
+                "Nearest" => Filter::Nearest,
+                "Bilinear" => Filter::Bilinear,
+                "Bicubic" => Filter::Bicubic,
+                "Lanczos" => Filter::Lanczos,
+                _ => return None,
+            };
+
+            let space = match symbol("space")? {
+                "Linear" => Space::Linear,
+                "Srgb" => Space::Srgb,
+                _ => return None,
+            };
+
+            let path = info.arg(node, "path")?.string()?;
+            Instance::Generator(Box::new(
+                Image::new(path).with_filter(filter).with_space(space),
+            ))
+        }
+
+        "invert" => Instance::Processor(Box::new(LInvert)),
+        "grayscale" => Instance::Processor(Box::new(Grayscale)),
+        "broadcast" => Instance::Processor(Box::new(Broadcast)),
+        "gradient" => Instance::Processor(Box::new(Gradient)),
+        "brighten" => Instance::Processor(Box::new(Brightener::new(number("factor")?))),
+
+        "math" => {
+            let op = match symbol("op")? {
+                "Add" => Operation::Add,
+                "Multiply" => Operation::Multiply,
+                "Subtract" => Operation::Subtract,
+                "Divide" => Operation::Divide,
+                _ => return None,
+            };
+
+            Instance::Combiner(Box::new(Math::new(op)))
+        }
+
+        "mix" => Instance::Combiner(Box::new(Mix::new(number("factor")?))),
+        "geomix" => Instance::Combiner(Box::new(GeoMix::new(number("factor")?))),
+        "over" => Instance::Combiner(Box::new(Over)),
+        "dot" => Instance::Combiner(Box::new(Dot)),
+        "dot3" => Instance::Combiner(Box::new(Dot3)),
+
+        _ => return None,
+    };
+
+    Some(instance)
+}
@@ -0,0 +1,53 @@
+use rand::Rng;
+
+use crate::util::Metric;
+use crate::{Config, Generator, Node, Pixel, Texture};
+
+/// Labels every pixel with the index of the nearest of a set of random points, dividing the texture into cells like
+/// those whose edges [`Voronoi`](super::Voronoi) measures the distance to.
+pub struct Cells {
+    num_points: usize,
+    points: Vec<(f64, f64)>,
+    metric: Metric,
+}
+
+impl Cells {
+    pub fn new(num_points: usize) -> Self {
+        Self {
+            num_points,
+            points: vec![],
+            metric: Metric::Euclidian,
+        }
+    }
+
+    pub fn with_metric(self, metric: Metric) -> Self {
+        Self { metric, ..self }
+    }
+}
+
+impl Node for Cells {
+    fn init(&mut self, config: &Config) {
+        let mut rng = config.rng();
+        self.points = (0..self.num_points).map(|_| rng.gen()).collect();
+    }
+}
+
+impl Generator for Cells {
+    fn render(&mut self, _config: &Config, target: &mut Texture) {
+        let aspect = target.aspect();
+        let (w, h) = (target.width() as f64, target.height() as f64);
+
+        target.fill_absolute(|x, y| {
+            let at = (x as f64 / w, y as f64 / h);
+            let nearest = self
+                .points
+                .iter()
+                .map(|point| self.metric.min_dist_in(at, *point, aspect))
+                .enumerate()
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map_or(0, |(ndx, _)| ndx);
+
+            Pixel::splat_rgb(nearest as f64)
+        });
+    }
+}
@@ -1,5 +1,8 @@
 use crate::{Node, Pixel, Processor, Texture};
 
+/// Convolves a texture with a kernel. Normalized kernels average premultiplied pixels, including their alpha, while
+/// other kernels, such as for sharpening or finding edges, filter the colour channels and keep the alpha of the pixel
+/// at the centre.
 pub struct Convolve<const W: usize, const H: usize> {
     by: [[f64; W]; H],
     normalize: bool,
@@ -32,7 +35,7 @@ impl<const W: usize, const H: usize> Processor for Convolve<W, H> {
             1.0
         };
 
-        for (pixel, x, y) in target.pixels_mut_absolute() {
+        target.fill_absolute(|x, y| {
             let mut sum = Pixel::ZERO;
 
             for offy in 0..H {
@@ -45,11 +48,19 @@ impl<const W: usize, const H: usize> Processor for Convolve<W, H> {
                     let x = x as isize + offx;
                     let y = y as isize + offy;
 
-                    sum += scale * input.at_absolute_wrap(x, y);
+                    let input = input.at_absolute_wrap(x, y);
+                    sum += scale
+                        * match self.normalize {
+                            true => input.premultiply(),
+                            false => input,
+                        };
                 }
             }
 
-            *pixel = sum * weight;
-        }
+            match self.normalize {
+                true => (sum * weight).unpremultiply(),
+                false => sum.with_alpha(input.at_absolute(x, y).alpha()),
+            }
+        });
     }
 }
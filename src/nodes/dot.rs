@@ -14,15 +14,16 @@ impl Combiner for Dot {
             (v + 1.0) * 0.5
         }
 
-        for (pixel, x, y) in target.pixels_mut_absolute() {
-            let [ar, ag, ab] = inputs.0.at_absolute(x, y).as_rgb();
-            let [br, bg, bb] = inputs.1.at_absolute(x, y).as_rgb();
+        target.fill_absolute(|x, y| {
+            let (a, b) = (inputs.0.at_absolute(x, y), inputs.1.at_absolute(x, y));
+            let [ar, ag, ab] = a.as_rgb();
+            let [br, bg, bb] = b.as_rgb();
 
             let (ar, ag, ab) = (s(ar), s(ag), s(ab));
             let (br, bg, bb) = (s(br), s(bg), s(bb));
 
             let dot = ar * br + ag * bg + ab * bb;
-            *pixel = Pixel::splat_rgb(i(dot / 3.0));
-        }
+            Pixel::splat_rgb(i(dot / 3.0)).with_alpha(a.alpha() * b.alpha())
+        });
     }
 }
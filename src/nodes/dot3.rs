@@ -14,15 +14,17 @@ impl Combiner for Dot3 {
             (v + 1.0) * 0.5
         }
 
-        for (pixel, x, y) in target.pixels_mut_absolute() {
-            let [ar, ag, ab] = inputs.0.at_absolute(x, y).as_rgb();
-            let [br, bg, bb] = inputs.1.at_absolute(x, y).as_rgb();
+        target.fill_absolute(|x, y| {
+            let (a, b) = (inputs.0.at_absolute(x, y), inputs.1.at_absolute(x, y));
+            let alpha = a.alpha() * b.alpha();
+            let [ar, ag, ab] = a.as_rgb();
+            let [br, bg, bb] = b.as_rgb();
 
             let (ar, ag, ab) = (s(ar), s(ag), s(ab));
             let (br, bg, bb) = (s(br), s(bg), s(bb));
 
             let (r, g, b) = (ar * br, ag * bg, ab * bb);
-            *pixel = Pixel::from_rgb(i(r), i(g), i(b));
-        }
+            Pixel::from_rgb(i(r), i(g), i(b)).with_alpha(alpha)
+        });
     }
 }
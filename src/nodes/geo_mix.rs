@@ -17,11 +17,11 @@ impl Combiner for GeoMix {
         let t = 2.0 * self.factor;
         let i = 2.0 - t;
 
-        for (pixel, x, y) in target.pixels_mut_absolute() {
+        target.fill_absolute(|x, y| {
             let a = inputs.0.at_absolute(x, y);
             let b = inputs.1.at_absolute(x, y);
 
-            *pixel = (a.powf(i) * b.powf(t)).sqrt();
-        }
+            (a.powf(i) * b.powf(t)).sqrt()
+        });
     }
 }
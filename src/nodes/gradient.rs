@@ -0,0 +1,27 @@
+use crate::{Config, Node, Pixel, Processor, Texture};
+
+/// The gradient of a scalar texture by central differences, wrapping around its edges. The red and green channels say
+/// how fast the value grows per local unit along each axis.
+pub struct Gradient;
+
+impl Node for Gradient {}
+
+impl Processor for Gradient {
+    fn render(&mut self, _config: &Config, input: &Texture, target: &mut Texture) {
+        let (w, h) = (input.width() as f64, input.height() as f64);
+        let value = |x, y| input.at_absolute_wrap(x, y).0[0];
+
+        target.fill_absolute(|x, y| {
+            let (x, y) = (x as isize, y as isize);
+            let dx = (value(x + 1, y) - value(x - 1, y)) * w / 2.0;
+            let dy = (value(x, y + 1) - value(x, y - 1)) * h / 2.0;
+
+            Pixel::from_rgba(
+                dx,
+                dy,
+                0.0,
+                input.at_absolute(x as usize, y as usize).alpha(),
+            )
+        });
+    }
+}
@@ -0,0 +1,177 @@
+use std::path::{Path, PathBuf};
+
+use crate::{Config, Depth, Filter, Format, Generator, Node, Pixel, Raw, Space, Texture};
+
+/// An image read from a file, resampled to the size being rendered. Files ending in `.r16`, `.raw` or `.r32` are read as
+/// square little-endian heightmaps, which are always linear.
+pub struct Image {
+    path: PathBuf,
+    filter: Filter,
+    space: Space,
+    image: Option<Texture>,
+}
+
+impl Image {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            filter: Filter::Bilinear,
+            space: Space::Srgb,
+            image: None,
+        }
+    }
+
+    pub fn with_filter(self, filter: Filter) -> Self {
+        Self { filter, ..self }
+    }
+
+    /// Treat the file as encoded in the given space, rather than as sRGB, converting its colours to linear values as it
+    /// is read.
+    pub fn with_space(self, space: Space) -> Self {
+        Self { space, ..self }
+    }
+}
+
+impl Node for Image {
+    fn load(&mut self, dir: &Path) -> Result<(), String> {
+        let path = dir.join(&self.path);
+
+        let depth = match Format::from_path(&path) {
+            Some(Format::R16) => Some(Depth::R16),
+            Some(Format::R32) => Some(Depth::R32),
+            _ => None,
+        };
+
+        if let Some(depth) = depth {
+            let image = Raw::new(depth)
+                .load(&path, None)
+                .map_err(|error| format!("could not read '{}': {error}", path.display()))?;
+            self.image = Some(image);
+            return Ok(());
+        }
+
+        let image = image::open(&path)
+            .map_err(|error| format!("could not read '{}': {error}", path.display()))?
+            .into_rgba32f();
+
+        if image.width() == 0 || image.height() == 0 {
+            return Err(format!("'{}' is empty", path.display()));
+        }
+
+        let pixels = image
+            .pixels()
+            .map(|pixel| Pixel(pixel.0.map(f64::from)).decode(self.space))
+            .collect();
+
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        self.image = Texture::from_pixels(width, height, pixels);
+        Ok(())
+    }
+
+    fn files(&self) -> Vec<PathBuf> {
+        vec![self.path.clone()]
+    }
+}
+
+impl Generator for Image {
+    fn render(&mut self, _config: &Config, target: &mut Texture) {
+        if let Some(image) = &self.image {
+            image.resample_into(target, self.filter);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::Image;
+    use crate::{Config, Depth, Filter, Generator, Node, Pixel, Raw, Space, Texture};
+
+    /// A directory holding `gradient.png`, a 4×2 image whose red channel rises from left to right and whose green
+    /// channel rises from top to bottom. The directory is removed when this is dropped.
+    pub(crate) struct Fixtures(PathBuf);
+
+    impl Fixtures {
+        pub(crate) fn new() -> Self {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let count = COUNT.fetch_add(1, Ordering::Relaxed);
+            let dir = std::env::temp_dir()
+                .join(format!("cobble-fixtures-{}-{count}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+
+            let image = image::RgbaImage::from_fn(4, 2, |x, y| {
+                image::Rgba([x as u8 * 85, y as u8 * 255, 0, 255])
+            });
+            image.save(dir.join("gradient.png")).unwrap();
+
+            Self(dir)
+        }
+
+        pub(crate) fn dir(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for Fixtures {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Load an image from the fixtures, and render it at the given size.
+    fn render(fixtures: &Fixtures, image: Image, width: usize, height: usize) -> Texture {
+        let mut image = image.with_filter(Filter::Nearest);
+        image.load(fixtures.dir()).unwrap();
+
+        let mut target = Texture::new(width, height);
+        image.render(&Config::new(width, height), &mut target);
+        target
+    }
+
+    #[test]
+    fn images_are_read_as_linear() {
+        let fixtures = Fixtures::new();
+        let image = Image::new("gradient.png").with_space(Space::Linear);
+        let texture = render(&fixtures, image, 4, 2);
+
+        assert!((texture.at_absolute(1, 0).0[0] - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!([0.0, 0.0, 1.0], texture.at_absolute(1, 0).0[1..]);
+        assert_eq!([1.0, 1.0, 0.0, 1.0], texture.at_absolute(3, 1).0);
+    }
+
+    #[test]
+    fn images_are_converted_from_srgb() {
+        let fixtures = Fixtures::new();
+        let texture = render(&fixtures, Image::new("gradient.png"), 4, 2);
+
+        let red = texture.at_absolute(1, 0).0[0];
+        assert!((red - 0.0908).abs() < 0.001, "{red}");
+    }
+
+    #[test]
+    fn raw_heightmaps_are_read() {
+        let fixtures = Fixtures::new();
+        let heights = Texture::from_pixels(
+            2,
+            2,
+            (0..4).map(|n| Pixel::splat_rgb(n as f64 / 3.0)).collect(),
+        )
+        .unwrap();
+        Raw::new(Depth::R16)
+            .save(&heights, fixtures.dir().join("heights.r16"))
+            .unwrap();
+
+        let texture = render(&fixtures, Image::new("heights.r16"), 2, 2);
+        assert!((texture.at_absolute(1, 1).0[0] - 1.0).abs() < 1e-4);
+        assert!((texture.at_absolute(1, 0).0[1] - 1.0 / 3.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn missing_files_are_reported() {
+        let fixtures = Fixtures::new();
+        let error = Image::new("missing.png").load(fixtures.dir()).unwrap_err();
+        assert!(error.starts_with("could not read"), "{error}");
+    }
+}
@@ -1,15 +1,17 @@
 use colorsys::{Hsl, Rgb, RgbRatio};
 
-use crate::{Config, Node, Pixel, Processor, Texture};
+use crate::{Config, Node, Pixel, Processor, Space, Texture};
 
+/// Inverts lightness in HSL, which is defined on sRGB values, so each pixel is encoded as sRGB first and decoded again
+/// afterwards.
 pub struct LInvert;
 
 impl Node for LInvert {}
 
 impl Processor for LInvert {
     fn render(&mut self, _config: &Config, input: &Texture, target: &mut Texture) {
-        for (pixel, x, y) in target.pixels_mut_absolute() {
-            let input = input.at_absolute(x, y);
+        target.fill_absolute(|x, y| {
+            let input = input.at_absolute(x, y).encode(Space::Srgb);
 
             let rgb = RgbRatio::from(input.0);
             let rgb = Rgb::from(rgb);
@@ -20,7 +22,7 @@ impl Processor for LInvert {
 
             let rgb = Rgb::from(hsl);
             let rgb: [f64; 4] = rgb.as_ratio().into();
-            *pixel = Pixel(rgb)
-        }
+            Pixel(rgb).decode(Space::Srgb)
+        });
     }
 }
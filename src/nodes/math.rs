@@ -7,6 +7,8 @@ pub enum Operation {
     Divide,
 }
 
+/// Combines the colour channels of two textures. The result only covers what both inputs cover, so its alpha is the
+/// product of theirs.
 pub struct Math {
     fun: fn(&Texture, &Texture, &mut Texture),
 }
@@ -38,37 +40,37 @@ impl Combiner for Math {
 }
 
 fn add(a: &Texture, b: &Texture, res: &mut Texture) {
-    for (pixel, x, y) in res.pixels_mut_absolute() {
+    res.fill_absolute(|x, y| {
         let a = a.at_absolute(x, y);
         let b = b.at_absolute(x, y);
 
-        *pixel = a + b;
-    }
+        (a + b).with_alpha(a.alpha() * b.alpha())
+    });
 }
 
 fn mul(a: &Texture, b: &Texture, res: &mut Texture) {
-    for (pixel, x, y) in res.pixels_mut_absolute() {
+    res.fill_absolute(|x, y| {
         let a = a.at_absolute(x, y);
         let b = b.at_absolute(x, y);
 
-        *pixel = a * b;
-    }
+        (a * b).with_alpha(a.alpha() * b.alpha())
+    });
 }
 
 fn sub(a: &Texture, b: &Texture, res: &mut Texture) {
-    for (pixel, x, y) in res.pixels_mut_absolute() {
+    res.fill_absolute(|x, y| {
         let a = a.at_absolute(x, y);
         let b = b.at_absolute(x, y);
 
-        *pixel = a - b;
-    }
+        (a - b).with_alpha(a.alpha() * b.alpha())
+    });
 }
 
 fn div(a: &Texture, b: &Texture, res: &mut Texture) {
-    for (pixel, x, y) in res.pixels_mut_absolute() {
+    res.fill_absolute(|x, y| {
         let a = a.at_absolute(x, y);
         let b = b.at_absolute(x, y);
 
-        *pixel = a / b;
-    }
+        (a / b).with_alpha(a.alpha() * b.alpha())
+    });
 }
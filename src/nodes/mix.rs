@@ -1,5 +1,6 @@
 use crate::{Combiner, Config, Node, Texture};
 
+/// Interpolates between premultiplied pixels, so a transparent pixel contributes its coverage but not its colour.
 pub struct Mix {
     factor: f64,
 }
@@ -17,11 +18,11 @@ impl Combiner for Mix {
         let t = self.factor;
         let i = 1.0 - t;
 
-        for (pixel, x, y) in target.pixels_mut_absolute() {
+        target.fill_absolute(|x, y| {
             let a = inputs.0.at_absolute(x, y);
             let b = inputs.1.at_absolute(x, y);
 
-            *pixel = i * a + t * b;
-        }
+            (i * a.premultiply() + t * b.premultiply()).unpremultiply()
+        });
     }
 }
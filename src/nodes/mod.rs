@@ -1,27 +1,42 @@
 pub mod blur;
 pub mod brighten;
+pub mod broadcast;
+pub mod builtin;
+pub mod cells;
 pub mod convolve;
 pub mod dot;
 pub mod dot3;
 pub mod geo_mix;
+pub mod gradient;
 pub mod grayscale;
+pub mod image;
 pub mod linvert;
 pub mod math;
 pub mod mix;
+pub mod over;
 pub mod random;
 pub mod simplex;
 pub mod voronoi;
 
 pub use blur::{blur_sigma, inverse_blur_sigma};
 pub use brighten::Brightener;
+pub use broadcast::Broadcast;
+pub use builtin::{
+    builtin, instantiate, Conversion, Instance, Mismatch, NodeInfo, NodeKind, ParamInfo, ParamKind,
+    TextureKind, BUILTINS,
+};
+pub use cells::Cells;
 pub use convolve::Convolve;
 pub use dot::Dot;
 pub use dot3::Dot3;
 pub use geo_mix::GeoMix;
+pub use gradient::Gradient;
 pub use grayscale::Grayscale;
+pub use image::Image;
 pub use linvert::LInvert;
 pub use math::{Math, Operation};
 pub use mix::Mix;
+pub use over::Over;
 pub use random::Random;
 pub use simplex::Simplex;
 pub use voronoi::Voronoi;
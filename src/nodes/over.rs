@@ -0,0 +1,17 @@
+use crate::{Combiner, Config, Node, Texture};
+
+/// Composites the first texture over the second, as though it were painted on top of it.
+pub struct Over;
+
+impl Node for Over {}
+
+impl Combiner for Over {
+    fn render(&mut self, _config: &Config, inputs: (&Texture, &Texture), target: &mut Texture) {
+        target.fill_absolute(|x, y| {
+            let above = inputs.0.at_absolute(x, y);
+            let below = inputs.1.at_absolute(x, y);
+
+            above.over(below)
+        });
+    }
+}
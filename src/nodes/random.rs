@@ -1,16 +1,14 @@
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
-use crate::{Config, Generator, Node, Texture};
+use crate::{Config, Generator, Node, Pixel, Texture};
 
 pub struct Random;
 
 impl Node for Random {}
 
 impl Generator for Random {
-    fn render(&mut self, _config: &Config, target: &mut Texture) {
-        let mut rng = thread_rng();
-        for pixel in target.pixels_mut() {
-            pixel.0 = rng.gen();
-        }
+    fn render(&mut self, config: &Config, target: &mut Texture) {
+        let mut rng = config.rng();
+        target.fill_absolute(|_, _| Pixel::from_rgb(rng.gen(), rng.gen(), rng.gen()));
     }
 }
@@ -1,7 +1,7 @@
 use std::f64::consts::TAU;
 
 use noise::{Fbm, NoiseFn, OpenSimplex};
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
 use crate::{Config, Generator, Node, Pixel, Texture};
 
@@ -12,10 +12,8 @@ pub struct Simplex {
 
 impl Simplex {
     pub fn new(scale: f64) -> Self {
-        let seed = thread_rng().gen_range(0..10000);
-
         Self {
-            noise: Fbm::new(seed),
+            noise: Fbm::new(0),
             scale,
         }
     }
@@ -27,7 +25,12 @@ impl Default for Simplex {
     }
 }
 
-impl Node for Simplex {}
+impl Node for Simplex {
+    fn init(&mut self, config: &Config) {
+        let seed = config.rng().gen_range(0..10000);
+        self.noise = Fbm::new(seed);
+    }
+}
 
 impl Generator for Simplex {
     fn render(&mut self, _config: &Config, target: &mut Texture) {
@@ -35,12 +38,15 @@ impl Generator for Simplex {
             (v + 1.0) / 2.0
         }
 
-        let s = self.scale;
+        // Each axis is wrapped around a circle in its own plane, whose circumference is in proportion to the side of the
+        // texture, so that the noise tiles without being stretched.
+        let (sx, sy) = target.aspect();
+        let (sx, sy) = (self.scale * sx, self.scale * sy);
 
-        for (pixel, x, y) in target.pixels_mut_local() {
+        target.fill_local(|x, y| {
             let (x, y) = (TAU * x, TAU * y);
 
-            let (x, y, z, w) = (s * x.cos(), s * y.cos(), s * x.sin(), s * y.sin());
+            let (x, y, z, w) = (sx * x.cos(), sy * y.cos(), sx * x.sin(), sy * y.sin());
             let pos1 = [x, y, z, w];
             let pos2 = [x + 10.0, y + 10.0, z + 10.0, w + 10.0];
             let pos3 = [x - 10.0, y - 10.0, z - 10.0, w - 10.0];
@@ -49,7 +55,7 @@ impl Generator for Simplex {
             let g = i(self.noise.get(pos2));
             let b = i(self.noise.get(pos3));
 
-            *pixel = Pixel::from_rgb(r, g, b);
-        }
+            Pixel::from_rgb(r, g, b)
+        });
     }
 }
@@ -1,9 +1,9 @@
 use std::f64::consts::SQRT_2;
 
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
 use crate::util::Metric;
-use crate::{Config, Generator, Node, Pixel, Texture};
+use crate::{Channels, Config, Generator, Node, Pixel, Precision, Texture};
 
 pub struct Voronoi {
     num_points: usize,
@@ -26,36 +26,35 @@ impl Voronoi {
 }
 
 impl Node for Voronoi {
-    fn init(&mut self, _config: &Config) {
-        let mut rng = thread_rng();
-
-        for _ in 0..self.num_points {
-            self.points.push(rng.gen());
-        }
+    fn init(&mut self, config: &Config) {
+        let mut rng = config.rng();
+        self.points = (0..self.num_points).map(|_| rng.gen()).collect();
     }
 }
 
 impl Generator for Voronoi {
-    fn render(&mut self, config: &Config, target: &mut Texture) {
+    fn render(&mut self, _config: &Config, target: &mut Texture) {
         const MAX_DIST: f64 = SQRT_2;
 
-        let mut dists = vec![0.0; config.width * config.height];
-        for (ndx, dist) in dists.iter_mut().enumerate() {
-            let x = ndx % config.width;
-            let y = ndx / config.height;
-
-            let (x, y) = target.to_local(x, y);
-
-            *dist = MAX_DIST;
+        let aspect = target.aspect();
+
+        // Distances are kept at full precision until they are normalised, as they can exceed the range of the target.
+        let mut dists = Texture::with_storage(
+            target.width(),
+            target.height(),
+            Channels::Gray,
+            Precision::F64,
+        );
+        dists.fill_local(|x, y| {
+            let mut dist = MAX_DIST;
             for point in self.points.iter().copied() {
-                *dist = dist.min(self.metric.min_dist((x, y), point));
+                dist = dist.min(self.metric.min_dist_in((x, y), point, aspect));
             }
-        }
+            Pixel::splat_rgb(dist)
+        });
 
-        let max = dists.iter().copied().reduce(|a, b| a.max(b)).unwrap();
+        let max = dists.max().0[0];
 
-        for (pixel, dist) in target.pixels_mut().zip(dists) {
-            *pixel = Pixel::splat_rgb(dist / max)
-        }
+        target.fill_absolute(|x, y| Pixel::splat_rgb(dists.at_absolute(x, y).0[0] / max));
     }
 }
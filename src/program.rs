@@ -0,0 +1,265 @@
+//! Compiling and rendering scripts in one call, for embedding cobble in other tools.
+
+use std::fmt;
+use std::sync::Arc;
+
+use crate::compiler::graph::{Graph, Param};
+use crate::compiler::source::{Source, SourceId, Sources};
+use crate::compiler::{compile_with_params, Errors, Params};
+use crate::render::Renderer;
+use crate::{Config, Texture};
+
+/// Compile a script and render each of its results, named as in [`Program::names`].
+pub fn render_str(source: &str, config: &Config) -> Result<Vec<(String, Texture)>, Diagnostics> {
+    Program::new(source)?.render(config)
+}
+
+/// Everything which went wrong compiling or rendering a program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostics {
+    /// The message of each error.
+    pub messages: Vec<String>,
+
+    /// Each error, pointing out where in the source it occurred.
+    pub reports: Vec<String>,
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.reports.join("\n"))
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// A script which is known to compile, and can be rendered any number of times with different configurations.
+///
+/// The script is resolved once, when the program is created or given new parameters, and the graph it resolves to is
+/// kept for every render.
+#[derive(Debug)]
+pub struct Program {
+    /// The graph of the script, which borrows from its source and the values of its parameters. Use
+    /// [`Program::graph`] rather than this, which ties the graph to the program instead of claiming it lives forever.
+    /// It is declared before what it borrows from so it is dropped first.
+    graph: Graph<'static>,
+
+    sources: Sources,
+    id: SourceId,
+
+    /// The values given to the parameters, which are never read but kept as the graph borrows from them.
+    _values: Params,
+
+    names: Vec<String>,
+    params: Vec<Param>,
+}
+
+impl Program {
+    pub fn new(source: impl Into<Source>) -> Result<Self, Diagnostics> {
+        let mut sources = Sources::new();
+        let id = sources.add(source.into());
+
+        Self::check(sources, id, Params::new())
+    }
+
+    /// Give the program's parameters the given values, rather than their defaults. Values for parameters the program
+    /// does not declare, or outside of a parameter's range, are errors.
+    pub fn with_params(self, values: Params) -> Result<Self, Diagnostics> {
+        let Program {
+            graph, sources, id, ..
+        } = self;
+
+        drop(graph);
+        Self::check(sources, id, values)
+    }
+
+    fn check(sources: Sources, id: SourceId, values: Params) -> Result<Self, Diagnostics> {
+        let mut errors = Errors::new();
+        let graph = compile_with_params(&sources, id, &values, &mut errors);
+
+        let unknown: Vec<_> = values
+            .names()
+            .filter(|name| !graph.params().iter().any(|param| param.name == *name))
+            .map(|name| format!("unknown parameter '{name}'"))
+            .collect();
+
+        if !errors.is_empty() || !unknown.is_empty() {
+            let messages = errors.errors.iter().map(|error| error.message.clone());
+            let reports = errors
+                .errors
+                .iter()
+                .map(|error| error.report(&sources).to_string());
+
+            return Err(Diagnostics {
+                messages: messages.chain(unknown.iter().cloned()).collect(),
+                reports: reports.chain(unknown.iter().cloned()).collect(),
+            });
+        }
+
+        let names = names(&graph);
+        let params = graph.params().to_vec();
+
+        // SAFETY: The graph borrows from the text of the sources and of the values, which is on the heap, so does not
+        // move when they are moved into the program. The program never changes either, and drops the graph before them,
+        // so the text outlives the graph. The graph is only handed out through `Program::graph`, bound to the program.
+        let graph = unsafe { std::mem::transmute::<Graph<'_>, Graph<'static>>(graph) };
+
+        Ok(Self {
+            graph,
+            sources,
+            id,
+            _values: values,
+            names,
+            params,
+        })
+    }
+
+    fn graph(&self) -> &Graph<'_> {
+        &self.graph
+    }
+
+    /// Get every parameter the program declares, with the values it renders with, in the order they were declared.
+    pub fn params(&self) -> &[Param] {
+        &self.params
+    }
+
+    /// Get the name of each result. A result is named after the definition which produces it if there is one, or
+    /// numbered from one otherwise.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Render every result.
+    pub fn render(&self, config: &Config) -> Result<Vec<(String, Texture)>, Diagnostics> {
+        self.render_with(&mut Renderer::new(*config))
+    }
+
+    /// Render every result with the given renderer, which may cache nodes between renders.
+    pub fn render_with(
+        &self,
+        renderer: &mut Renderer,
+    ) -> Result<Vec<(String, Texture)>, Diagnostics> {
+        let graph = self.graph();
+
+        let mut textures =
+            renderer
+                .render(graph, graph.outputs())
+                .map_err(|error| Diagnostics {
+                    messages: vec![error.to_string()],
+                    reports: vec![error.report(&self.sources)],
+                })?;
+
+        let results = graph
+            .outputs()
+            .iter()
+            .zip(self.names.iter())
+            .map(|(id, name)| {
+                // Results which share a node each get a copy of its texture.
+                let texture = match graph.outputs().iter().filter(|other| *other == id).count() {
+                    1 => textures.remove(id),
+                    _ => textures.get(id).cloned(),
+                };
+
+                // A texture the renderer's cache also holds is copied, as the results are handed out to own.
+                let texture = texture.expect("every result is rendered");
+                (name.clone(), Arc::unwrap_or_clone(texture))
+            })
+            .collect();
+
+        Ok(results)
+    }
+}
+
+fn names(graph: &Graph) -> Vec<String> {
+    graph
+        .outputs()
+        .iter()
+        .enumerate()
+        .map(|(ndx, id)| {
+            let defined = graph
+                .definitions()
+                .find(|(_, ids)| *ids == [*id])
+                .map(|(name, _)| name);
+
+            match graph.output_name(ndx).or(defined) {
+                Some(name) => name.to_string(),
+                None => (ndx + 1).to_string(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_str, Program};
+    use crate::compiler::Params;
+    use crate::Config;
+
+    #[test]
+    fn results_are_named_and_rendered() {
+        let config = Config::new(8, 4).with_seed(3);
+        let results = render_str(
+            "a -> return\na -> invert -> return\na = voronoi(5)",
+            &config,
+        )
+        .unwrap();
+
+        let names: Vec<_> = results.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(vec!["a", "2"], names);
+        assert_eq!((8, 4), (results[0].1.width(), results[0].1.height()));
+    }
+
+    #[test]
+    fn programs_render_with_any_config() {
+        let program = Program::new("random -> return").unwrap();
+
+        let a = program.render(&Config::new(4, 4).with_seed(1)).unwrap();
+        let b = program.render(&Config::new(4, 4).with_seed(2)).unwrap();
+        let c = program.render(&Config::new(16, 16).with_seed(1)).unwrap();
+
+        assert!(a[0]
+            .1
+            .pixels()
+            .zip(b[0].1.pixels())
+            .any(|(a, b)| a.as_rgba() != b.as_rgba()));
+        assert_eq!(16, c[0].1.width());
+    }
+
+    #[test]
+    fn programs_take_params() {
+        let program =
+            Program::new("-- cells\nparam density = 20 (1..200)\nvoronoi(density) -> return")
+                .unwrap();
+        assert_eq!("cells", program.params()[0].doc);
+        assert_eq!(Some((1.0, 200.0)), program.params()[0].range);
+
+        let config = Config::new(8, 8).with_seed(1);
+        let default = program.render(&config).unwrap();
+
+        let program = program
+            .with_params(Params::new().with("density", 50.0))
+            .unwrap();
+        assert_eq!(50.0, program.params()[0].value);
+        assert_ne!(
+            default[0].1.to_image(),
+            program.render(&config).unwrap()[0].1.to_image()
+        );
+
+        let diagnostics = program
+            .with_params(Params::new().with("density", 0.0).with("size", 2.0))
+            .unwrap_err();
+        assert_eq!(
+            vec!["parameter out of range", "unknown parameter 'size'"],
+            diagnostics.messages
+        );
+    }
+
+    #[test]
+    fn errors_are_reported() {
+        let diagnostics = render_str("voronio -> return", &Config::new(4, 4)).unwrap_err();
+        assert_eq!(vec!["unknown name 'voronio'"], diagnostics.messages);
+        assert!(diagnostics.to_string().starts_with("<input>:1:1: error"));
+
+        let diagnostics = render_str("math(Power) -> return", &Config::new(4, 4)).unwrap_err();
+        assert_eq!(2, diagnostics.messages.len());
+    }
+}
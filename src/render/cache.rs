@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::{Channels, Precision, Texture};
+
+const MAGIC: &[u8; 8] = b"cobble\0\x02";
+
+const CHANNELS: [Channels; 3] = [Channels::Gray, Channels::GrayAlpha, Channels::Rgba];
+const PRECISIONS: [Precision; 4] = [
+    Precision::F64,
+    Precision::F32,
+    Precision::F16,
+    Precision::U16,
+];
+
+/// How many bytes of textures a cache keeps in memory unless told otherwise.
+const DEFAULT_BUDGET: usize = 1 << 30;
+
+/// Rendered textures, keyed by a hash of everything which determines their contents.
+///
+/// Textures are kept in memory, and also written to a directory if one is given, so that they outlive the process. Keys
+/// include the version of cobble and a render version, so a directory written by another release is ignored. Builds of
+/// the same release share textures, so a change to how any node renders must bump the render version, or the directory
+/// has to be cleared by hand.
+///
+/// Textures are shared rather than copied in and out, so a texture being rendered with costs no more for also being
+/// cached. Memory is bounded by a budget in bytes. Once the textures kept exceed it, those used least recently are forgotten
+/// until they fit again, though any written to disk can still be read back.
+#[derive(Debug)]
+pub struct Cache {
+    memory: HashMap<u64, Entry>,
+    dir: Option<PathBuf>,
+    budget: usize,
+
+    /// The bytes taken by the textures in memory.
+    bytes: usize,
+
+    /// Counts uses of the cache, to tell which textures were used least recently.
+    clock: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    texture: Arc<Texture>,
+    used: u64,
+}
+
+impl Cache {
+    pub fn new() -> Self {
+        Self {
+            memory: HashMap::new(),
+            dir: None,
+            budget: DEFAULT_BUDGET,
+            bytes: 0,
+            clock: 0,
+        }
+    }
+
+    /// Also keep textures in the given directory, which is created if it does not exist.
+    pub fn with_dir(self, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: Some(dir.into()),
+            ..self
+        }
+    }
+
+    /// Keep at most the given number of bytes of textures in memory.
+    pub fn with_budget(self, budget: usize) -> Self {
+        Self { budget, ..self }
+    }
+
+    pub fn get(&mut self, key: u64) -> Option<Arc<Texture>> {
+        self.clock += 1;
+
+        if let Some(entry) = self.memory.get_mut(&key) {
+            entry.used = self.clock;
+        } else {
+            let texture = Arc::new(read(&self.path(key)?).ok()?);
+            self.keep(key, Arc::clone(&texture));
+            return Some(texture);
+        }
+
+        self.memory
+            .get(&key)
+            .map(|entry| Arc::clone(&entry.texture))
+    }
+
+    /// Keep a texture. Failing to write it to disk is not an error, as it can always be rendered again.
+    pub fn insert(&mut self, key: u64, texture: Arc<Texture>) {
+        if let Some(path) = self.path(key) {
+            let _ = write(&path, &texture);
+        }
+
+        self.clock += 1;
+        self.keep(key, texture);
+    }
+
+    /// Forget every texture kept in memory, leaving any written to disk.
+    pub fn clear(&mut self) {
+        self.memory.clear();
+        self.bytes = 0;
+    }
+
+    /// Get the number of bytes taken by the textures kept in memory.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.memory.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.memory.is_empty()
+    }
+
+    /// Keep a texture in memory, then forget the least recently used textures until the rest fit in the budget. A
+    /// texture larger than the whole budget is not kept at all.
+    fn keep(&mut self, key: u64, texture: Arc<Texture>) {
+        self.bytes += texture.bytes();
+        let entry = Entry {
+            texture,
+            used: self.clock,
+        };
+        if let Some(old) = self.memory.insert(key, entry) {
+            self.bytes -= old.texture.bytes();
+        }
+
+        while self.bytes > self.budget {
+            let Some(&oldest) = self
+                .memory
+                .iter()
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(key, _)| key)
+            else {
+                break;
+            };
+
+            let entry = self.memory.remove(&oldest).expect("the key was just found");
+            self.bytes -= entry.texture.bytes();
+        }
+    }
+
+    fn path(&self, key: u64) -> Option<PathBuf> {
+        let dir = self.dir.as_ref()?;
+        Some(dir.join(format!("{key:016x}.texture")))
+    }
+}
+
+/// Write a texture as its dimensions and how it is stored, followed by its channels as `f64`, all little-endian.
+fn write(path: &Path, texture: &Texture) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut file = BufWriter::new(fs::File::create(path)?);
+    file.write_all(MAGIC)?;
+    file.write_all(&(texture.width() as u64).to_le_bytes())?;
+    file.write_all(&(texture.height() as u64).to_le_bytes())?;
+    file.write_all(&[
+        position(&CHANNELS, texture.channels()),
+        position(&PRECISIONS, texture.precision()),
+    ])?;
+
+    for sample in texture.samples() {
+        file.write_all(&sample.to_le_bytes())?;
+    }
+
+    file.flush()
+}
+
+fn read(path: &Path) -> io::Result<Texture> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid cached texture");
+
+    let mut file = BufReader::new(fs::File::open(path)?);
+    let mut header = [0; 26];
+    file.read_exact(&mut header)?;
+
+    if header[..8] != MAGIC[..] {
+        return Err(invalid());
+    }
+
+    let width = u64::from_le_bytes(header[8..16].try_into().unwrap()) as usize;
+    let height = u64::from_le_bytes(header[16..24].try_into().unwrap()) as usize;
+    let channels = *CHANNELS.get(header[24] as usize).ok_or_else(invalid)?;
+    let precision = *PRECISIONS.get(header[25] as usize).ok_or_else(invalid)?;
+
+    // A file cut short reads as too few samples, and so is invalid.
+    let samples = std::iter::from_fn(|| {
+        let mut sample = [0; 8];
+        file.read_exact(&mut sample).ok()?;
+        Some(f64::from_le_bytes(sample))
+    });
+
+    Texture::from_samples(width, height, channels, precision, samples).ok_or_else(invalid)
+}
+
+fn position<T: PartialEq>(values: &[T], value: T) -> u8 {
+    values
+        .iter()
+        .position(|v| *v == value)
+        .expect("every value is listed") as u8
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::Cache;
+    use crate::{Channels, Pixel, Precision, Texture};
+
+    #[test]
+    fn textures_survive_the_disk() {
+        let dir = std::env::temp_dir().join(format!("cobble-cache-{}", std::process::id()));
+
+        let pixels = (0..6).map(|n| Pixel::splat_rgba(n as f64 / 6.0)).collect();
+        let texture = Texture::from_pixels(3, 2, pixels)
+            .unwrap()
+            .convert(Channels::GrayAlpha, Precision::F16);
+
+        Cache::new()
+            .with_dir(&dir)
+            .insert(7, Arc::new(texture.clone()));
+        let mut cache = Cache::new().with_dir(&dir);
+        let cached = cache.get(7).unwrap();
+
+        assert_eq!((3, 2), (cached.width(), cached.height()));
+        assert_eq!(Channels::GrayAlpha, cached.channels());
+        assert_eq!(Precision::F16, cached.precision());
+        assert!(cached
+            .pixels()
+            .zip(texture.pixels())
+            .all(|(a, b)| a.as_rgba() == b.as_rgba()));
+        assert!(cache.get(8).is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn least_recently_used_textures_are_forgotten() {
+        let texture = Texture::new(4, 4);
+        let size = texture.bytes();
+        let mut cache = Cache::new().with_budget(size * 2);
+
+        cache.insert(1, Arc::new(texture.clone()));
+        cache.insert(2, Arc::new(texture.clone()));
+        assert!(cache.get(1).is_some());
+
+        cache.insert(3, Arc::new(texture.clone()));
+        assert_eq!(2, cache.len());
+        assert_eq!(size * 2, cache.bytes());
+        assert!(cache.get(1).is_some() && cache.get(3).is_some());
+        assert!(cache.get(2).is_none());
+
+        // Replacing a texture does not count it twice.
+        cache.insert(3, Arc::new(texture.clone()));
+        assert_eq!(size * 2, cache.bytes());
+
+        cache.insert(4, Arc::new(Texture::new(8, 8)));
+        assert!(cache.is_empty());
+        assert_eq!(0, cache.bytes());
+    }
+}
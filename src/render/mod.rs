@@ -0,0 +1,910 @@
+//! Rendering of resolved graphs into textures.
+
+mod cache;
+mod profile;
+mod validate;
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+use std::{fmt, mem};
+
+pub use self::cache::Cache;
+pub use self::profile::{NodeProfile, Profile};
+pub use self::validate::{BadValue, Policy, Validation};
+use crate::compiler::graph::{Graph, NodeId};
+use crate::compiler::source::{Sources, Span};
+use crate::compiler::Error;
+use crate::nodes::{builtin, instantiate, Instance, NodeInfo, TextureKind};
+use crate::{Channels, Config, Texture};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderError {
+    /// The name of the node which could not be rendered.
+    pub node: String,
+
+    /// Every part of the sources which produced the node.
+    pub spans: Vec<Span>,
+
+    pub problem: Problem,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Problem {
+    /// The node is not a built-in node, or was given the wrong arguments or inputs.
+    Invalid,
+
+    /// The node could not read what it needs from outside the graph, for the given reason.
+    Load(String),
+
+    /// The node produced a value its validation does not allow.
+    BadValue(BadValue),
+}
+
+impl RenderError {
+    /// Get a report of this error which points out where in the given sources the node came from.
+    pub fn report(&self, sources: &Sources) -> String {
+        let Some((first, rest)) = self.spans.split_first() else {
+            return self.to_string();
+        };
+
+        let notes = rest
+            .iter()
+            .map(|span| format!("also produced at {}", sources.location(*span)))
+            .collect();
+
+        Error::new(*first, self.to_string())
+            .with_notes(notes)
+            .report(sources)
+            .to_string()
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.problem {
+            Problem::Invalid => write!(f, "cannot render '{}'", self.node),
+            Problem::Load(reason) => write!(f, "cannot load '{}': {reason}", self.node),
+            Problem::BadValue(bad) => write!(f, "'{}' produced {bad}", self.node),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+/// Renders graphs, optionally keeping the output of every node in a cache so that later renders of the same nodes are
+/// free.
+///
+/// A node is only cached if its output is determined by its arguments, its inputs and the configuration. Random nodes,
+/// and every node downstream of them, are therefore only cached if the configuration has a seed. Nodes which read
+/// files, and every node downstream of them, are never cached.
+#[derive(Debug)]
+pub struct Renderer {
+    config: Config,
+    cache: Option<Cache>,
+    validation: Validation,
+    profile: Option<Profile>,
+    dir: PathBuf,
+}
+
+impl Renderer {
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            cache: None,
+            validation: Validation::Off,
+            profile: None,
+            dir: PathBuf::new(),
+        }
+    }
+
+    /// Read files relative to the given directory, rather than the working directory.
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.set_dir(dir);
+        self
+    }
+
+    /// Read files relative to the given directory from now on, such as when rendering scripts from several directories
+    /// with the same renderer.
+    pub fn set_dir(&mut self, dir: impl Into<PathBuf>) {
+        self.dir = dir.into();
+    }
+
+    /// Measure every node as it is rendered.
+    pub fn with_profiling(self) -> Self {
+        Self {
+            profile: Some(Profile::new()),
+            ..self
+        }
+    }
+
+    /// Take the measurements made so far, starting a new profile if profiling.
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.as_mut().map(mem::take)
+    }
+
+    pub fn with_validation(self, validation: Validation) -> Self {
+        Self { validation, ..self }
+    }
+
+    pub fn with_cache(self, cache: Cache) -> Self {
+        Self {
+            cache: Some(cache),
+            ..self
+        }
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn cache(&self) -> Option<&Cache> {
+        self.cache.as_ref()
+    }
+
+    /// Render the given nodes of a graph, along with every node they depend upon. Nodes are only rendered once, even
+    /// if several of the given nodes depend upon them. Textures are shared with the cache, if there is one.
+    pub fn render(
+        &mut self,
+        graph: &Graph,
+        ids: &[NodeId],
+    ) -> Result<HashMap<NodeId, Arc<Texture>>, RenderError> {
+        let hashes = graph.hashes();
+        let keys = self.keys(graph, &hashes);
+        let kinds = kinds(graph);
+        let (needed, mut textures) = self.needed(graph, &keys, ids);
+
+        for id in graph.ids().filter(|id| needed.contains(id)) {
+            let start = Instant::now();
+            let node = graph.node(id);
+            let pixels = self.config.width * self.config.height;
+
+            if textures.contains_key(&id) {
+                if let Some(profile) = &mut self.profile {
+                    profile.add(
+                        id,
+                        node.to_string(),
+                        graph.spans(id).to_vec(),
+                        start,
+                        pixels,
+                        None,
+                    );
+                }
+
+                continue;
+            }
+
+            let error = |problem| RenderError {
+                node: node.to_string(),
+                spans: graph.spans(id).to_vec(),
+                problem,
+            };
+
+            // Each node draws from its own seed, so that equal random nodes in different graphs render equally.
+            let config = Config {
+                seed: self.config.seed.map(|seed| hash(&(seed, hashes[&id]))),
+                ..self.config
+            };
+
+            let inputs: Vec<&Texture> = graph
+                .inputs(id)
+                .iter()
+                .map(|input| textures[input].as_ref())
+                .collect();
+            let info = builtin(node.name());
+            let channels = info.map_or(Channels::Rgba, |info| info.channels);
+            let precision = match info.and_then(|info| info.output) {
+                Some(kind) => kind.precision(config.precision),
+                None => config.precision,
+            };
+            let mut target =
+                Texture::with_storage(config.width, config.height, channels, precision);
+
+            let instance = instantiate(node).ok_or_else(|| error(Problem::Invalid))?;
+            match (instance, inputs.as_slice()) {
+                (Instance::Generator(mut node), []) => {
+                    node.init(&config);
+                    node.load(&self.dir)
+                        .map_err(|reason| error(Problem::Load(reason)))?;
+                    node.render(&config, &mut target);
+                }
+
+                (Instance::Processor(mut node), [input]) => {
+                    node.init(&config);
+                    node.load(&self.dir)
+                        .map_err(|reason| error(Problem::Load(reason)))?;
+                    node.render(&config, input, &mut target);
+                }
+
+                (Instance::Combiner(mut node), [a, b]) => {
+                    node.init(&config);
+                    node.load(&self.dir)
+                        .map_err(|reason| error(Problem::Load(reason)))?;
+                    node.render(&config, (a, b), &mut target);
+                }
+
+                _ => return Err(error(Problem::Invalid)),
+            }
+
+            if let Some(bad) = self.validation.apply(&mut target, kinds[&id]) {
+                return Err(error(Problem::BadValue(bad)));
+            }
+
+            if let Some(profile) = &mut self.profile {
+                profile.add(
+                    id,
+                    node.to_string(),
+                    graph.spans(id).to_vec(),
+                    start,
+                    pixels,
+                    Some(target.bytes()),
+                );
+            }
+
+            let target = Arc::new(target);
+            if let (Some(cache), Some(key)) = (&mut self.cache, keys[&id]) {
+                cache.insert(key, Arc::clone(&target));
+            }
+
+            textures.insert(id, target);
+        }
+
+        textures.retain(|id, _| ids.contains(id));
+        Ok(textures)
+    }
+
+    /// Get the cache key of every node, or `None` for nodes whose output is not determined by the configuration.
+    fn keys(&self, graph: &Graph, hashes: &HashMap<NodeId, u64>) -> HashMap<NodeId, Option<u64>> {
+        let Config {
+            width,
+            height,
+            seed,
+            precision,
+        } = self.config;
+        let validation = self.validation;
+
+        let mut random: HashMap<NodeId, bool> = HashMap::new();
+        let mut external: HashMap<NodeId, bool> = HashMap::new();
+        let mut keys = HashMap::new();
+
+        for id in graph.ids() {
+            let info = builtin(graph.node(id).name());
+
+            let is_random = info.is_some_and(|info| info.random)
+                || graph.inputs(id).iter().any(|input| random[input]);
+            random.insert(id, is_random);
+
+            let is_external = info.is_some_and(|info| info.external)
+                || graph.inputs(id).iter().any(|input| external[input]);
+            external.insert(id, is_external);
+
+            let key = match (is_random, seed) {
+                _ if is_external => None,
+                (true, None) => None,
+                (true, Some(seed)) => Some(hash(&(
+                    (VERSION, RENDER_VERSION),
+                    hashes[&id],
+                    width,
+                    height,
+                    precision,
+                    validation,
+                    seed,
+                ))),
+                (false, _) => Some(hash(&(
+                    (VERSION, RENDER_VERSION),
+                    hashes[&id],
+                    width,
+                    height,
+                    precision,
+                    validation,
+                ))),
+            };
+
+            keys.insert(id, key);
+        }
+
+        keys
+    }
+
+    /// Find every node which must be rendered or fetched from the cache to render the given nodes, along with the
+    /// textures of those which are cached. The textures are taken out up front, as rendering the other nodes may evict
+    /// them from the cache before they are used, after their inputs were left out for being cached.
+    fn needed(
+        &mut self,
+        graph: &Graph,
+        keys: &HashMap<NodeId, Option<u64>>,
+        ids: &[NodeId],
+    ) -> (HashSet<NodeId>, HashMap<NodeId, Arc<Texture>>) {
+        let mut needed = HashSet::new();
+        let mut cached = HashMap::new();
+        let mut worklist = ids.to_vec();
+
+        while let Some(id) = worklist.pop() {
+            if !needed.insert(id) {
+                continue;
+            }
+
+            let texture = match (&mut self.cache, keys[&id]) {
+                (Some(cache), Some(key)) => cache.get(key),
+                _ => None,
+            };
+
+            match texture {
+                Some(texture) => {
+                    cached.insert(id, texture);
+                }
+                None => worklist.extend_from_slice(graph.inputs(id)),
+            }
+        }
+
+        (needed, cached)
+    }
+}
+
+/// Find the kind of texture each node outputs, where it is known. Nodes which output the kind of their inputs are
+/// given the kind of their first input, as resolving converts their inputs to the same kind.
+fn kinds(graph: &Graph) -> HashMap<NodeId, Option<TextureKind>> {
+    let mut kinds = HashMap::new();
+
+    for id in graph.ids() {
+        let kind = match builtin(graph.node(id).name()) {
+            Some(NodeInfo {
+                output: Some(kind), ..
+            }) => Some(*kind),
+            Some(_) => graph.inputs(id).first().and_then(|input| kinds[input]),
+            None => None,
+        };
+
+        kinds.insert(id, kind);
+    }
+
+    kinds
+}
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Part of every cache key, to be bumped whenever a change alters what any node renders, such that textures cached on
+/// disk by builds from before the change are not taken for the new output.
+pub(crate) const RENDER_VERSION: u32 = 2;
+
+fn hash(value: &impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::sync::Arc;
+
+    use super::{BadValue, Cache, Policy, Problem, Renderer, Validation};
+    use crate::compiler::compile;
+    use crate::compiler::source::Sources;
+    use crate::compiler::Errors;
+    use crate::nodes::image::tests::Fixtures;
+    use crate::nodes::{instantiate, Instance, ParamKind, BUILTINS};
+    use crate::{Channels, Config, Pixel, Precision, Texture};
+
+    /// Render the single result of a program.
+    fn render(renderer: &mut Renderer, source: &str) -> Arc<Texture> {
+        let mut sources = Sources::new();
+        let id = sources.add(source.into());
+
+        let mut errors = Errors::new();
+        let graph = compile(&sources, id, &mut errors);
+        assert!(errors.is_empty());
+
+        let output = graph.outputs()[0];
+        renderer
+            .render(&graph, &[output])
+            .unwrap()
+            .remove(&output)
+            .unwrap()
+    }
+
+    fn same(a: &Texture, b: &Texture) -> bool {
+        a.pixels()
+            .zip(b.pixels())
+            .all(|(a, b)| a.as_rgba() == b.as_rgba())
+    }
+
+    #[test]
+    fn render_readme_example() {
+        let mut sources = Sources::new();
+        let id = sources.add(
+            "a = voronoi(50) -> invert\nb = voronoi(20)\nc = (a, b) -> math(Multiply)\n\n(c, random) -> mix -> return"
+                .into(),
+        );
+
+        let mut errors = Errors::new();
+        let graph = compile(&sources, id, &mut errors);
+        assert!(errors.is_empty());
+
+        let textures = Renderer::new(Config::new(16, 16))
+            .render(&graph, graph.outputs())
+            .unwrap();
+
+        assert_eq!(1, textures.len());
+        assert_eq!(16 * 16, textures[&graph.outputs()[0]].pixels().count());
+    }
+
+    #[test]
+    fn render_reports_invalid_nodes() {
+        let mut sources = Sources::new();
+        let id = sources.add("voronio -> invert -> return".into());
+
+        let mut errors = Errors::new();
+        let graph = compile(&sources, id, &mut errors);
+
+        let error = Renderer::new(Config::new(4, 4))
+            .render(&graph, graph.outputs())
+            .map(|_| ())
+            .unwrap_err();
+
+        assert_eq!("voronio", error.node);
+        assert!(error
+            .report(&sources)
+            .starts_with("<input>:1:1: error: cannot render 'voronio'"));
+    }
+
+    #[test]
+    fn image_nodes_read_files_relative_to_the_renderer() {
+        let config = Config::new(4, 2);
+        let fixtures = Fixtures::new();
+
+        let mut sources = Sources::new();
+        let id = sources.add("image(\"gradient.png\", Nearest, Linear) -> return".into());
+        let graph = compile(&sources, id, &mut Errors::new());
+
+        let textures = Renderer::new(config)
+            .with_dir(fixtures.dir())
+            .render(&graph, graph.outputs())
+            .unwrap();
+
+        let texture = &textures[&graph.outputs()[0]];
+        assert!((texture.at_absolute(1, 0).0[0] - 1.0 / 3.0).abs() < 1e-6);
+        assert_eq!([0.0, 0.0, 1.0], texture.at_absolute(1, 0).0[1..]);
+        assert_eq!([1.0, 1.0, 0.0, 1.0], texture.at_absolute(3, 1).0);
+    }
+
+    #[test]
+    fn combiners_respect_coverage() {
+        let config = Config::new(1, 1);
+        let clear = Texture::from_pixels(1, 1, vec![Pixel::from_rgba(0.0, 1.0, 0.0, 0.0)]).unwrap();
+        let red = Texture::from_pixels(1, 1, vec![Pixel::from_rgb(1.0, 0.0, 0.0)]).unwrap();
+
+        let combine = |name: &str, a: &Texture, b: &Texture| {
+            let mut sources = Sources::new();
+            let id =
+                sources.add(format!("(a, b) -> {name} -> return\na = random\nb = random").into());
+            let graph = compile(&sources, id, &mut Errors::new());
+            let node = graph.node(graph.outputs()[0]);
+
+            let Some(Instance::Combiner(mut node)) = instantiate(node) else {
+                panic!("{name} is not a combiner");
+            };
+            let mut target = Texture::new(1, 1);
+            node.render(&config, (a, b), &mut target);
+            target.at_absolute(0, 0).0
+        };
+
+        assert_eq!([1.0, 0.0, 0.0, 0.5], combine("mix", &clear, &red));
+        assert_eq!([1.0, 0.0, 0.0, 1.0], combine("over", &clear, &red));
+        assert_eq!([1.0, 0.0, 0.0, 1.0], combine("over", &red, &clear));
+        assert_eq!(0.0, combine("math(Add)", &clear, &red)[3]);
+    }
+
+    #[test]
+    fn image_nodes_report_missing_files() {
+        let fixtures = Fixtures::new();
+        let mut sources = Sources::new();
+        let id = sources.add("image(\"missing.png\") -> invert -> return".into());
+
+        let mut errors = Errors::new();
+        let graph = compile(&sources, id, &mut errors);
+        assert!(errors.is_empty());
+
+        let error = Renderer::new(Config::new(4, 4))
+            .with_dir(fixtures.dir())
+            .render(&graph, graph.outputs())
+            .map(|_| ())
+            .unwrap_err();
+
+        assert_eq!("image(\"missing.png\")", error.node);
+        assert!(matches!(error.problem, Problem::Load(_)));
+        assert!(error.report(&sources).starts_with(
+            "<input>:1:1: error: cannot load 'image(\"missing.png\")': could not read"
+        ));
+    }
+
+    #[test]
+    fn gradients_measure_luminance_per_unit() {
+        let fixtures = Fixtures::new();
+        let mut renderer = Renderer::new(Config::new(4, 2)).with_dir(fixtures.dir());
+        let texture = render(
+            &mut renderer,
+            "image(\"gradient.png\", Nearest, Linear) -> gradient -> return",
+        );
+
+        // Red climbs by a third per pixel, and green alternates between rows, so wraps around to no slope at all.
+        let [dx, dy, _, alpha] = texture.at_absolute(1, 0).0;
+        assert!((dx - 0.2126 * 4.0 / 3.0).abs() < 1e-4, "{dx}");
+        assert_eq!((0.0, 1.0), (dy, alpha));
+    }
+
+    #[test]
+    fn labels_stay_whole_numbers() {
+        let config = Config::new(16, 16)
+            .with_seed(3)
+            .with_precision(Precision::U16);
+        let texture = render(&mut Renderer::new(config), "cells(5) -> return");
+
+        assert_eq!(Precision::F32, texture.precision());
+        let labels: HashSet<_> = texture.pixels().map(|pixel| pixel.0[0] as u64).collect();
+        assert!(labels.len() > 1 && labels.iter().all(|label| *label < 5));
+        assert!(texture.pixels().all(|pixel| pixel.0[0].fract() == 0.0));
+    }
+
+    #[test]
+    fn cache_skips_image_nodes() {
+        let fixtures = Fixtures::new();
+        let mut renderer = Renderer::new(Config::new(4, 2).with_seed(1))
+            .with_cache(Cache::new())
+            .with_dir(fixtures.dir());
+
+        render(
+            &mut renderer,
+            "(image(\"gradient.png\"), voronoi) -> mix -> return",
+        );
+
+        // Only the distances and their broadcast to a colour.
+        assert_eq!(2, renderer.cache().unwrap().len());
+    }
+
+    /// Get the mean difference in the red channel between horizontally or vertically neighbouring pixels, both within
+    /// the texture and across its wrapping edges.
+    fn differences(texture: &Texture) -> (f64, f64) {
+        let (width, height) = (texture.width(), texture.height());
+        let diff = |(ax, ay), (bx, by)| {
+            (texture.at_absolute(ax, ay).0[0] - texture.at_absolute(bx, by).0[0]).abs()
+        };
+
+        let mut inner = Vec::new();
+        let mut seams = Vec::new();
+
+        for y in 0..height {
+            for x in 0..width {
+                let right = ((x + 1) % width, y);
+                let below = (x, (y + 1) % height);
+
+                let to_right = if x + 1 == width {
+                    &mut seams
+                } else {
+                    &mut inner
+                };
+                to_right.push(diff((x, y), right));
+
+                let to_below = if y + 1 == height {
+                    &mut seams
+                } else {
+                    &mut inner
+                };
+                to_below.push(diff((x, y), below));
+            }
+        }
+
+        let mean = |diffs: Vec<f64>| diffs.iter().sum::<f64>() / diffs.len() as f64;
+        (mean(inner), mean(seams))
+    }
+
+    #[test]
+    fn every_node_renders_non_square() {
+        let fixtures = Fixtures::new();
+
+        for info in BUILTINS {
+            let args: Vec<_> = info
+                .params
+                .iter()
+                .filter(|param| param.default.is_none())
+                .map(|param| match param.kind {
+                    ParamKind::Number | ParamKind::Positive => "1",
+                    ParamKind::Symbol(options) => options[0],
+                    ParamKind::String => "\"gradient.png\"",
+                })
+                .collect();
+
+            let node = match args.is_empty() {
+                true => info.name.to_string(),
+                false => format!("{}({})", info.name, args.join(", ")),
+            };
+
+            let source = match info.kind.inputs() {
+                0 => format!("{node} -> return"),
+                1 => format!("voronoi -> {node} -> return"),
+                _ => format!("(voronoi, voronoi(5)) -> {node} -> return"),
+            };
+
+            for (width, height) in [(7, 3), (3, 7)] {
+                let config = Config::new(width, height).with_seed(1);
+
+                let mut sources = Sources::new();
+                let id = sources.add(source.as_str().into());
+                let graph = compile(&sources, id, &mut Errors::new());
+                let ids: Vec<_> = graph.ids().collect();
+                let textures = Renderer::new(config)
+                    .with_dir(fixtures.dir())
+                    .render(&graph, &ids)
+                    .unwrap();
+
+                let output = graph.outputs()[0];
+                let texture = &textures[&output];
+                assert_eq!(
+                    (width, height),
+                    (texture.width(), texture.height()),
+                    "{source}"
+                );
+                assert!(
+                    texture
+                        .pixels()
+                        .all(|pixel| pixel.0.iter().all(|v| v.is_finite())),
+                    "{source}"
+                );
+
+                // Every input here is opaque, and nodes only make things transparent if they are asked to.
+                assert!(
+                    texture.pixels().all(|pixel| pixel.alpha() == 1.0),
+                    "{source}"
+                );
+
+                // Every processor and combiner works pixel by pixel, so each pixel must be the same as the node
+                // applied to just the pixels at the same position in its inputs.
+                let inputs: Vec<_> = graph
+                    .inputs(output)
+                    .iter()
+                    .map(|id| &textures[id])
+                    .collect();
+                for y in 0..height {
+                    for x in 0..width {
+                        let pixel = |input: &Texture| {
+                            Texture::from_pixels(1, 1, vec![input.at_absolute(x, y)]).unwrap()
+                        };
+
+                        let single = Config::new(1, 1).with_seed(1);
+                        let mut target = Texture::new(1, 1);
+
+                        match (instantiate(graph.node(output)).unwrap(), inputs.as_slice()) {
+                            (Instance::Generator(_), _) => continue,
+
+                            // Gradients are taken from neighbouring pixels.
+                            (Instance::Processor(_), _) if info.name == "gradient" => continue,
+
+                            (Instance::Processor(mut node), [input]) => {
+                                node.render(&single, &pixel(input), &mut target)
+                            }
+                            (Instance::Combiner(mut node), [a, b]) => {
+                                node.render(&single, (&pixel(a), &pixel(b)), &mut target)
+                            }
+                            _ => unreachable!(),
+                        }
+
+                        assert_eq!(
+                            target.at_absolute(0, 0).as_rgba(),
+                            texture.at_absolute(x, y).as_rgba(),
+                            "{source} at ({x}, {y})"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn non_square_generators_tile() {
+        for source in ["voronoi -> return", "simplex(2) -> return"] {
+            for size in [(48, 16), (16, 48)] {
+                let config = Config::new(size.0, size.1).with_seed(4);
+                let texture = render(&mut Renderer::new(config), source);
+
+                // Seams between tiles should be no more visible than the edges between any other pixels.
+                let (inner, seams) = differences(&texture);
+                assert!(
+                    seams < 2.0 * inner,
+                    "{source} at {size:?}: {seams} across seams, {inner} within"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn seeds_make_renders_repeatable() {
+        let source = "(voronoi, random) -> mix -> return";
+        let config = Config::new(8, 8).with_seed(3);
+
+        let a = render(&mut Renderer::new(config), source);
+        let b = render(&mut Renderer::new(config), source);
+        let c = render(&mut Renderer::new(config.with_seed(4)), source);
+
+        assert!(same(&a, &b));
+        assert!(!same(&a, &c));
+    }
+
+    #[test]
+    fn cache_skips_unchanged_nodes() {
+        let mut renderer = Renderer::new(Config::new(8, 8).with_seed(1)).with_cache(Cache::new());
+
+        let a = render(&mut renderer, "voronoi(5) -> invert -> return");
+        assert_eq!(2, renderer.cache().unwrap().len());
+
+        render(&mut renderer, "voronoi(5) -> grayscale -> return");
+        assert_eq!(3, renderer.cache().unwrap().len());
+
+        let b = render(&mut renderer, "voronoi(5) -> invert -> return");
+        assert_eq!(3, renderer.cache().unwrap().len());
+        assert!(same(&a, &b));
+
+        // The cache hands back the texture it was given, rather than a copy.
+        assert!(Arc::ptr_eq(&a, &b));
+    }
+
+    #[test]
+    fn cache_evictions_do_not_lose_inputs() {
+        let budget = Texture::new(8, 8).bytes() * 2;
+        let mut renderer = Renderer::new(Config::new(8, 8).with_seed(1))
+            .with_cache(Cache::new().with_budget(budget));
+
+        render(&mut renderer, "voronoi(5) -> invert -> return");
+        let texture = render(
+            &mut renderer,
+            "(voronoi(9) -> invert -> grayscale -> invert, voronoi(5) -> invert) -> mix -> return",
+        );
+        assert_eq!((8, 8), (texture.width(), texture.height()));
+    }
+
+    #[test]
+    fn cache_skips_random_nodes_without_a_seed() {
+        let mut renderer = Renderer::new(Config::new(8, 8)).with_cache(Cache::new());
+
+        render(&mut renderer, "(voronoi, random) -> mix -> return");
+        assert_eq!(0, renderer.cache().unwrap().len());
+    }
+
+    #[test]
+    fn precision_is_part_of_the_cache_key() {
+        let source = "voronoi(5) -> grayscale -> return";
+        let dir = std::env::temp_dir().join(format!("cobble-precision-{}", std::process::id()));
+        let config = Config::new(8, 8).with_seed(1);
+
+        let mut renderer = Renderer::new(config.with_precision(Precision::U16))
+            .with_cache(Cache::new().with_dir(&dir));
+        let low = render(&mut renderer, source);
+        assert_eq!(Precision::U16, low.precision());
+        assert_eq!(Channels::GrayAlpha, low.channels());
+
+        // Textures cached at one precision are not taken for another.
+        let mut renderer = Renderer::new(config).with_cache(Cache::new().with_dir(&dir));
+        let high = render(&mut renderer, source);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(Precision::F32, high.precision());
+
+        assert!(low
+            .pixels()
+            .zip(high.pixels())
+            .all(|(a, b)| (a.0[0] - b.0[0]).abs() < 1e-4 && a.alpha() == 1.0));
+    }
+
+    #[test]
+    fn profiles_record_every_node() {
+        let source = "voronoi(5) -> invert -> return";
+        let mut sources = Sources::new();
+        sources.add(source.into());
+
+        let mut renderer = Renderer::new(Config::new(8, 8).with_seed(1))
+            .with_cache(Cache::new())
+            .with_profiling();
+
+        render(&mut renderer, source);
+        let profile = renderer.take_profile().unwrap();
+        assert_eq!(2, profile.nodes().len());
+        assert!(profile
+            .nodes()
+            .iter()
+            .all(|node| !node.cached && node.pixels == 64));
+        // Voronoi only stores a gray channel, while invert stores all four, each as a 32-bit float.
+        assert_eq!(64 * 4 + 64 * 16, profile.total_bytes());
+
+        let trace = profile.chrome_trace(&sources);
+        assert_eq!(2, trace["traceEvents"].as_array().unwrap().len());
+        assert_eq!("voronoi(5)", trace["traceEvents"][0]["name"]);
+        assert_eq!("<input>:1:1", trace["traceEvents"][0]["args"]["source"][0]);
+
+        render(&mut renderer, source);
+        let profile = renderer.take_profile().unwrap();
+        assert_eq!(1, profile.nodes().len());
+        assert!(profile.nodes()[0].cached);
+        assert_eq!(0, profile.total_bytes());
+    }
+
+    #[test]
+    fn validation_reports_the_first_bad_node() {
+        let mut sources = Sources::new();
+        let id = sources.add(
+            "a = (random, voronoi) -> math(Subtract)\na -> brighten(2) -> invert -> return".into(),
+        );
+
+        let mut errors = Errors::new();
+        let graph = compile(&sources, id, &mut errors);
+        let config = Config::new(8, 8).with_seed(0);
+
+        let finite = Renderer::new(config)
+            .with_validation(Validation::Finite)
+            .render(&graph, graph.outputs())
+            .map(|_| ())
+            .unwrap_err();
+
+        assert_eq!("brighten(2)", finite.node);
+        assert!(
+            matches!(finite.problem, Problem::BadValue(BadValue { value, .. }) if value.is_nan())
+        );
+        assert!(finite
+            .report(&sources)
+            .starts_with("<input>:2:6: error: 'brighten(2)' produced NaN in the"));
+
+        let range = Renderer::new(config)
+            .with_validation(Validation::Range)
+            .render(&graph, graph.outputs())
+            .map(|_| ())
+            .unwrap_err();
+
+        assert_eq!("math(Subtract)", range.node);
+    }
+
+    #[test]
+    fn range_validation_skips_vectors_and_labels() {
+        let config = Config::new(8, 8).with_seed(0);
+
+        for source in ["cells(5) -> return", "voronoi(5) -> gradient -> return"] {
+            let mut range = Renderer::new(config).with_validation(Validation::Range);
+            let texture = render(&mut range, source);
+            assert!(texture
+                .pixels()
+                .any(|pixel| pixel.0.iter().any(|value| !(0.0..=1.0).contains(value))));
+
+            let mut clamp =
+                Renderer::new(config).with_validation(Validation::Sanitize(Policy::Clamp));
+            assert!(same(&texture, &render(&mut clamp, source)));
+        }
+
+        // Nodes which output the kind of their input are checked as that kind.
+        let mut range = Renderer::new(config).with_validation(Validation::Range);
+        render(
+            &mut range,
+            "(a, a) -> mix -> return\na = voronoi(5) -> gradient",
+        );
+    }
+
+    #[test]
+    fn sanitizing_replaces_bad_values() {
+        let source = "(random, voronoi) -> math(Subtract) -> brighten(2) -> return";
+        let config = Config::new(8, 8).with_seed(0);
+
+        let zero = render(
+            &mut Renderer::new(config).with_validation(Validation::Sanitize(Policy::Zero)),
+            source,
+        );
+        assert!(zero
+            .pixels()
+            .all(|pixel| pixel.0.iter().all(|value| value.is_finite())));
+
+        let clamp = render(
+            &mut Renderer::new(config).with_validation(Validation::Sanitize(Policy::Clamp)),
+            source,
+        );
+        assert!(clamp
+            .pixels()
+            .all(|pixel| pixel.0.iter().all(|value| (0.0..=1.0).contains(value))));
+    }
+}
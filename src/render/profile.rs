@@ -0,0 +1,181 @@
+use std::cmp::Reverse;
+use std::fmt::Write as _;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+
+use crate::compiler::graph::NodeId;
+use crate::compiler::source::{Sources, Span};
+
+/// Measurements of every node rendered since profiling started.
+#[derive(Debug)]
+pub struct Profile {
+    start: Instant,
+    nodes: Vec<NodeProfile>,
+}
+
+#[derive(Clone, Debug)]
+pub struct NodeProfile {
+    pub id: NodeId,
+
+    /// The node's name and arguments.
+    pub node: String,
+
+    pub spans: Vec<Span>,
+
+    /// When the node started rendering, relative to the start of the profile.
+    pub start: Duration,
+
+    pub time: Duration,
+    pub pixels: usize,
+
+    /// The size of the texture allocated for the node's output.
+    pub bytes: usize,
+
+    /// Whether the node's output was taken from the cache rather than rendered.
+    pub cached: bool,
+}
+
+impl NodeProfile {
+    /// Get the number of pixels rendered per second.
+    pub fn throughput(&self) -> f64 {
+        self.pixels as f64 / self.time.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            nodes: Vec::new(),
+        }
+    }
+
+    pub fn nodes(&self) -> &[NodeProfile] {
+        &self.nodes
+    }
+
+    /// Record a node which started at the given instant and has just finished, rendering a texture of the given size, or
+    /// `None` if it was taken from the cache.
+    pub(super) fn add(
+        &mut self,
+        id: NodeId,
+        node: String,
+        spans: Vec<Span>,
+        start: Instant,
+        pixels: usize,
+        bytes: Option<usize>,
+    ) {
+        self.nodes.push(NodeProfile {
+            id,
+            node,
+            spans,
+            start: start - self.start,
+            time: start.elapsed(),
+            pixels,
+            bytes: bytes.unwrap_or(0),
+            cached: bytes.is_none(),
+        });
+    }
+
+    pub fn total_time(&self) -> Duration {
+        self.nodes.iter().map(|node| node.time).sum()
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.nodes.iter().map(|node| node.bytes).sum()
+    }
+
+    /// Describe every node as a table, slowest first.
+    pub fn table(&self, sources: &Sources) -> String {
+        let mut nodes: Vec<_> = self.nodes.iter().collect();
+        nodes.sort_by_key(|node| Reverse(node.time));
+
+        let mut table = format!(
+            "{:>10}  {:>6}  {:>12}  {:>10}  {:<32}  {}\n",
+            "time", "share", "pixels/s", "memory", "node", "source"
+        );
+
+        let total = self.total_time().as_secs_f64().max(f64::EPSILON);
+
+        for node in nodes {
+            let location = match node.spans.first() {
+                Some(span) => sources.location(*span),
+                None => String::new(),
+            };
+
+            let throughput = if node.cached {
+                "cached".into()
+            } else {
+                format!("{:.3e}", node.throughput())
+            };
+
+            let _ = writeln!(
+                table,
+                "{:>10}  {:>5.1}%  {throughput:>12}  {:>10}  {:<32}  {location}",
+                format!("{:.2?}", node.time),
+                100.0 * node.time.as_secs_f64() / total,
+                bytes(node.bytes),
+                node.node,
+            );
+        }
+
+        let _ = writeln!(
+            table,
+            "{:>10}  {:>6}  {:>12}  {:>10}  total",
+            format!("{:.2?}", self.total_time()),
+            "",
+            "",
+            bytes(self.total_bytes()),
+        );
+
+        table
+    }
+
+    /// Get the profile as a trace which `chrome://tracing` and Perfetto can open.
+    pub fn chrome_trace(&self, sources: &Sources) -> Value {
+        let events: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let locations: Vec<_> = node
+                    .spans
+                    .iter()
+                    .map(|span| sources.location(*span))
+                    .collect();
+
+                json!({
+                    "name": node.node,
+                    "cat": if node.cached { "cached" } else { "render" },
+                    "ph": "X",
+                    "ts": node.start.as_secs_f64() * 1e6,
+                    "dur": node.time.as_secs_f64() * 1e6,
+                    "pid": 0,
+                    "tid": 0,
+                    "args": {
+                        "id": node.id.to_string(),
+                        "pixels": node.pixels,
+                        "bytes": node.bytes,
+                        "source": locations,
+                    },
+                })
+            })
+            .collect();
+
+        json!({ "traceEvents": events, "displayTimeUnit": "ms" })
+    }
+}
+
+fn bytes(bytes: usize) -> String {
+    match bytes {
+        0..=1023 => format!("{bytes} B"),
+        1024..=1048575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1048576.0),
+    }
+}
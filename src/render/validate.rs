@@ -0,0 +1,114 @@
+use std::fmt;
+
+use crate::nodes::TextureKind;
+use crate::{Pixel, Texture};
+
+/// How the output of each node is checked as it is rendered.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Validation {
+    /// Outputs are not checked at all.
+    #[default]
+    Off,
+
+    /// Rendering fails at the first node which outputs an infinite or NaN value.
+    Finite,
+
+    /// Rendering fails at the first node which outputs a colour or scalar outside of the `[0, 1]` range, including
+    /// infinite and NaN values. Vectors and labels are only checked to be finite, as they are not bound to that range.
+    Range,
+
+    /// Bad values are replaced according to a policy, and rendering never fails.
+    Sanitize(Policy),
+}
+
+/// How bad values are replaced when sanitizing.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Policy {
+    /// Replace infinite and NaN values with zero, leaving other values as they are.
+    Zero,
+
+    /// Replace NaN values with zero, and clamp every other value of a colour or scalar to the `[0, 1]` range. Vectors
+    /// and labels are sanitized as with [`Policy::Zero`].
+    Clamp,
+}
+
+/// A bad value found in the output of a node.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BadValue {
+    pub x: usize,
+    pub y: usize,
+    pub channel: usize,
+    pub value: f64,
+}
+
+impl fmt::Display for BadValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const CHANNELS: [&str; 4] = ["red", "green", "blue", "alpha"];
+
+        let BadValue {
+            x,
+            y,
+            channel,
+            value,
+        } = self;
+        write!(
+            f,
+            "{value} in the {} channel at ({x}, {y})",
+            CHANNELS[*channel]
+        )
+    }
+}
+
+impl Validation {
+    /// Check a texture of the given kind, sanitizing it if this validation does so, and returning the first bad value
+    /// if it is an error. Textures of unknown kind are not assumed to be bound to the `[0, 1]` range.
+    pub fn apply(&self, texture: &mut Texture, kind: Option<TextureKind>) -> Option<BadValue> {
+        let bounded = matches!(kind, Some(TextureKind::Colour | TextureKind::Scalar));
+
+        match (self, bounded) {
+            (Validation::Off, _) => None,
+            (Validation::Finite, _) | (Validation::Range, false) => {
+                find(texture, |value| !value.is_finite())
+            }
+            (Validation::Range, true) => find(texture, |value| !(0.0..=1.0).contains(&value)),
+            (Validation::Sanitize(Policy::Clamp), false) => {
+                sanitize(texture, Policy::Zero);
+                None
+            }
+            (Validation::Sanitize(policy), _) => {
+                sanitize(texture, *policy);
+                None
+            }
+        }
+    }
+}
+
+fn find(texture: &Texture, bad: impl Fn(f64) -> bool) -> Option<BadValue> {
+    let width = texture.width();
+
+    texture.pixels().enumerate().find_map(|(ndx, pixel)| {
+        let (channel, value) = pixel
+            .0
+            .into_iter()
+            .enumerate()
+            .find(|(_, value)| bad(*value))?;
+
+        Some(BadValue {
+            x: ndx % width,
+            y: ndx / width,
+            channel,
+            value,
+        })
+    })
+}
+
+fn sanitize(texture: &mut Texture, policy: Policy) {
+    texture.update(|pixel| {
+        Pixel(pixel.0.map(|value| match policy {
+            Policy::Zero if !value.is_finite() => 0.0,
+            Policy::Zero => value,
+            Policy::Clamp if value.is_nan() => 0.0,
+            Policy::Clamp => value.clamp(0.0, 1.0),
+        }))
+    });
+}
@@ -0,0 +1,485 @@
+//! An interactive session for building graphs one definition at a time.
+
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use crate::compiler::graph::{Graph, NodeId};
+use crate::compiler::parse::cst::{Element, Kind};
+use crate::compiler::parse::parse_with_cst;
+use crate::compiler::source::{Source, SourceId, Sources, Span};
+use crate::compiler::token::{lex, Token};
+use crate::compiler::{compile, Errors};
+use crate::nodes::{NodeKind, ParamKind, BUILTINS};
+use crate::render::{Cache, RenderError, Renderer};
+use crate::{Config, Filter, Space, Texture};
+
+const HELP: &str = "\
+enter definitions such as `a = voronoi(20) -> invert` or parameters such as `param d = 20 (1..50)` to add them
+to the session, redefining any earlier ones of the same name, or pipelines ending in `-> return` to preview them.
+
+commands:
+    :show NAME [FILE]  render a definition, to a file if one is given or as a preview otherwise
+    :graph             print the graph the session resolves to
+    :defs              list the definitions in the session
+    :nodes             list the built-in nodes
+    :help              show this message
+    :quit              end the session
+";
+
+/// The number of columns taken by a preview. Each character shows two pixels, so previews are square.
+const PREVIEW: usize = 32;
+
+/// Run a session, reading lines from `input` until it ends or the session is quit.
+pub fn run<R: BufRead, W: Write>(input: R, mut output: W, config: Config) -> io::Result<()> {
+    let mut session = Session::new(config);
+
+    write!(output, "> ")?;
+    output.flush()?;
+
+    for line in input.lines() {
+        let line = line?;
+        if matches!(line.trim(), ":q" | ":quit") {
+            break;
+        }
+
+        write!(output, "{}> ", session.eval(&line))?;
+        output.flush()?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct Session {
+    /// Renders previews, caching the nodes they share with earlier previews.
+    renderer: Renderer,
+
+    /// The kind, name and source of each definition and parameter declaration, in the order they were first entered.
+    /// Definitions and parameters have names of their own, so a parameter only replaces a parameter of the same name.
+    defs: Vec<(Kind, String, String)>,
+}
+
+impl Session {
+    pub fn new(config: Config) -> Self {
+        Self {
+            renderer: Renderer::new(config).with_cache(Cache::new()),
+            defs: Vec::new(),
+        }
+    }
+
+    /// Get the source of every definition in the session.
+    pub fn source(&self) -> String {
+        self.defs
+            .iter()
+            .map(|(_, _, def)| format!("{def}\n"))
+            .collect()
+    }
+
+    /// Evaluate a line of input, returning the text to show in response.
+    pub fn eval(&mut self, line: &str) -> String {
+        let line = line.trim();
+
+        match line.split_once(char::is_whitespace).unwrap_or((line, "")) {
+            ("", _) => String::new(),
+            (":help", _) => HELP.into(),
+            (":nodes", _) => nodes(),
+            (":defs", _) => self.source(),
+            (":graph", _) => self.graph(),
+            (":show", args) => self.show(args.split_whitespace().collect()),
+            (command, _) if command.starts_with(':') => {
+                format!("unknown command '{command}', see :help\n")
+            }
+            _ => self.enter(line),
+        }
+    }
+
+    /// Add the definitions in a line to the session, and preview any results it has.
+    fn enter(&mut self, line: &str) -> String {
+        let mut sources = Sources::new();
+        let id = sources.add(Source::from(line).with_name("<input>"));
+
+        let mut errors = Errors::new();
+        let (program, cst) = parse_with_cst(line, id, lex(&sources, id), &mut errors);
+
+        if !errors.is_empty() {
+            return errors
+                .errors
+                .iter()
+                .map(|error| format!("{}\n", error.report(&sources)))
+                .collect();
+        }
+
+        let assigned: Vec<_> = cst
+            .children
+            .iter()
+            .filter_map(|child| match child {
+                Element::Node(node) if node.kind == Kind::Assign => Some((node, 0)),
+                // Skip the `param` keyword to get to the name.
+                Element::Node(node) if node.kind == Kind::Param => Some((node, 1)),
+                _ => None,
+            })
+            .filter_map(|(node, skip)| match node.significant().nth(skip)? {
+                Element::Token {
+                    token: Token::Ident(name),
+                    ..
+                } => Some((
+                    node.kind,
+                    name.to_string(),
+                    node.to_string().trim().to_string(),
+                )),
+                _ => None,
+            })
+            .collect();
+
+        // Check the line along with every definition it does not replace.
+        let kept = self
+            .defs
+            .iter()
+            .filter(|(kind, name, _)| {
+                !assigned
+                    .iter()
+                    .any(|(new_kind, new, _)| new_kind == kind && new == name)
+            })
+            .map(|(kind, name, def)| (source_name(*kind, name), def.as_str()));
+        let joined = Joined::new(kept.chain([("<input>".to_string(), line)]));
+
+        let mut errors = Errors::new();
+        let graph = compile(&joined.sources, joined.id, &mut errors);
+
+        if !errors.is_empty() {
+            return joined.report(errors);
+        }
+
+        let response = if program.results.is_empty() {
+            String::new()
+        } else {
+            self.render(&joined, &graph, graph.outputs())
+        };
+
+        for (kind, name, def) in assigned {
+            match self
+                .defs
+                .iter_mut()
+                .find(|(old_kind, old, _)| *old_kind == kind && *old == name)
+            {
+                Some(old) => old.2 = def,
+                None => self.defs.push((kind, name, def)),
+            }
+        }
+
+        response
+    }
+
+    fn graph(&self) -> String {
+        let mut sources = Sources::new();
+        let id = sources.add(Source::from(self.source()).with_name("<session>"));
+
+        let mut errors = Errors::new();
+        compile(&sources, id, &mut errors)
+            .annotated(&sources)
+            .to_string()
+    }
+
+    fn show(&mut self, args: Vec<&str>) -> String {
+        let (name, path) = match args.as_slice() {
+            [name] => (name, None),
+            [name, path] => (name, Some(path)),
+            _ => return "usage: :show NAME [FILE]\n".into(),
+        };
+
+        let defs = self
+            .defs
+            .iter()
+            .map(|(kind, name, def)| (source_name(*kind, name), def.as_str()));
+        let joined = Joined::new(defs);
+
+        let mut errors = Errors::new();
+        let graph = compile(&joined.sources, joined.id, &mut errors);
+
+        let Some(ids) = graph.definition(name) else {
+            return format!("unknown definition '{name}'\n");
+        };
+
+        let Some(path) = path else {
+            return self.render(&joined, &graph, ids);
+        };
+
+        let textures = match self.renderer.render(&graph, ids) {
+            Ok(textures) => textures,
+            Err(error) => return joined.report_render(error),
+        };
+
+        let paths = numbered(Path::new(path), ids.len());
+        for (id, path) in ids.iter().zip(paths.iter()) {
+            if let Err(error) = textures[id].to_image().save(path) {
+                return format!("could not save '{name}' to {}: {error}\n", path.display());
+            }
+        }
+
+        let paths: Vec<_> = paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        format!("saved '{name}' to {}\n", paths.join(", "))
+    }
+
+    /// Render the given nodes of a graph as previews.
+    fn render(&mut self, joined: &Joined, graph: &Graph, ids: &[NodeId]) -> String {
+        match self.renderer.render(graph, ids) {
+            Ok(textures) => ids.iter().map(|id| preview(&textures[id])).collect(),
+            Err(error) => joined.report_render(error),
+        }
+    }
+}
+
+/// Definitions joined into a single source to be compiled together. Each is also kept as a source of its own, such that
+/// errors point into the text as it was entered rather than into the joined source.
+#[derive(Debug)]
+struct Joined {
+    sources: Sources,
+    id: SourceId,
+
+    /// Where each definition starts in the joined source, and the source it is kept as.
+    parts: Vec<(usize, SourceId)>,
+}
+
+impl Joined {
+    fn new<'a>(parts: impl IntoIterator<Item = (String, &'a str)>) -> Self {
+        let mut sources = Sources::new();
+        let mut joined = String::new();
+        let mut starts = Vec::new();
+
+        for (name, text) in parts {
+            let id = sources.add(Source::from(text).with_name(name));
+            starts.push((joined.len(), id));
+            joined.push_str(text);
+            joined.push('\n');
+        }
+
+        let id = sources.add(Source::from(joined).with_name("<session>"));
+        Self {
+            sources,
+            id,
+            parts: starts,
+        }
+    }
+
+    /// Move a span of the joined source into the definition it falls in.
+    fn locate(&self, span: Span) -> Span {
+        let Some((start, file)) = self
+            .parts
+            .iter()
+            .rev()
+            .find(|(start, _)| span.file == self.id && *start <= span.start)
+        else {
+            return span;
+        };
+
+        let len = self.sources.get(file).content.len();
+        let local = |at: usize| (at - start).min(len);
+        Span::new(local(span.start)..local(span.end), *file)
+    }
+
+    fn report(&self, errors: Errors) -> String {
+        errors
+            .errors
+            .into_iter()
+            .map(|mut error| {
+                error.at = self.locate(error.at);
+                format!("{}\n", error.report(&self.sources))
+            })
+            .collect()
+    }
+
+    fn report_render(&self, mut error: RenderError) -> String {
+        error.spans = error.spans.iter().map(|span| self.locate(*span)).collect();
+        format!("{}\n", error.report(&self.sources))
+    }
+}
+
+/// Get the name of the source a definition is reported against.
+fn source_name(kind: Kind, name: &str) -> String {
+    match kind {
+        Kind::Param => format!("<param {name}>"),
+        _ => format!("<{name}>"),
+    }
+}
+
+/// Get the paths the nodes a definition produces are saved to: the given path if there is just one, or otherwise one
+/// path for each, numbered from 1 as `cobble render` numbers results.
+fn numbered(path: &Path, count: usize) -> Vec<PathBuf> {
+    if count == 1 {
+        return vec![path.to_path_buf()];
+    }
+
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    (1..=count)
+        .map(|ndx| path.with_file_name(format!("{stem}-{ndx}.{extension}")))
+        .collect()
+}
+
+/// Describe every built-in node, one per line.
+fn nodes() -> String {
+    let mut text = String::new();
+
+    for info in BUILTINS {
+        let params: Vec<_> = info
+            .params
+            .iter()
+            .map(|param| match (param.default, param.kind) {
+                (Some(default), _) => format!("{} = {default}", param.name),
+                (None, ParamKind::Number | ParamKind::Positive) => param.name.to_string(),
+                (None, ParamKind::Symbol(options)) => {
+                    format!("{}: {}", param.name, options.join(" | "))
+                }
+                (None, ParamKind::String) => format!("{}: \"...\"", param.name),
+            })
+            .collect();
+
+        let signature = if params.is_empty() {
+            info.name.to_string()
+        } else {
+            format!("{}({})", info.name, params.join(", "))
+        };
+
+        let kind = match info.kind {
+            NodeKind::Generator => "generator",
+            NodeKind::Processor => "processor",
+            NodeKind::Combiner => "combiner",
+        };
+
+        let _ = writeln!(
+            text,
+            "{signature:44} {kind:9}  {:32}  {}",
+            info.kinds(),
+            info.doc
+        );
+    }
+
+    text
+}
+
+/// Draw a texture for a terminal, using half blocks with true colour foregrounds and backgrounds.
+fn preview(texture: &Texture) -> String {
+    let mut text = String::new();
+    let at = |x, y| {
+        let u = (x as f64 + 0.5) / PREVIEW as f64;
+        let v = (y as f64 + 0.5) / PREVIEW as f64;
+        texture
+            .sample(u, v, Filter::Bilinear)
+            .encode(Space::Srgb)
+            .as_rgb8_sat()
+    };
+
+    for y in (0..PREVIEW).step_by(2) {
+        for x in 0..PREVIEW {
+            let [r, g, b] = at(x, y);
+            let [r2, g2, b2] = at(x, y + 1);
+            let _ = write!(text, "\x1b[38;2;{r};{g};{b}m\x1b[48;2;{r2};{g2};{b2}m▀");
+        }
+
+        text.push_str("\x1b[0m\n");
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Session;
+    use crate::Config;
+
+    #[test]
+    fn definitions_are_kept_and_replaced() {
+        let mut session = Session::new(Config::new(8, 8));
+
+        assert_eq!("", session.eval("a = voronoi(20)"));
+        assert_eq!("", session.eval("b = a -> invert"));
+        assert_eq!("", session.eval("a = simplex(2)"));
+
+        assert_eq!("a = simplex(2)\nb = a -> invert\n", session.source());
+        assert_eq!(
+            "%0 = simplex(2)  -- <session>:1:5\n%1 = invert <- %0  -- <session>:2:10\na = %0\nb = %1\n",
+            session.graph()
+        );
+    }
+
+    #[test]
+    fn errors_reject_lines() {
+        let mut session = Session::new(Config::new(8, 8));
+
+        let response = session.eval("a = voronio");
+        assert!(response.starts_with("<input>:1:5: error: unknown name 'voronio'"));
+
+        let response = session.eval("a = (voronoi");
+        assert!(response.starts_with("<input>:1:5: error: unclosed group"));
+
+        assert_eq!("", session.source());
+        assert!(session
+            .eval(":show a")
+            .starts_with("unknown definition 'a'"));
+    }
+
+    #[test]
+    fn results_are_previewed() {
+        let mut session = Session::new(Config::new(8, 8));
+
+        session.eval("a = random");
+        assert_eq!(16, session.eval("a -> invert -> return").lines().count());
+        assert_eq!(16, session.eval(":show a").lines().count());
+        assert_eq!("a = random\n", session.source());
+    }
+
+    #[test]
+    fn params_are_kept_and_replaced() {
+        let mut session = Session::new(Config::new(8, 8));
+
+        assert_eq!("", session.eval("param d = 5 (1..20)"));
+        assert_eq!("", session.eval("a = voronoi(d)"));
+        assert_eq!("", session.eval("d = simplex"));
+        assert_eq!("", session.eval("param d = 8"));
+
+        assert_eq!(
+            "param d = 8\na = voronoi(d)\nd = simplex\n",
+            session.source()
+        );
+        assert!(session.graph().contains(" = voronoi(8)  -- "));
+
+        let response = session.eval("b = voronoi(e)");
+        assert!(response.starts_with("<input>:1:13: error: unknown parameter 'e'"));
+    }
+
+    #[test]
+    fn errors_point_into_each_definition() {
+        let mut session = Session::new(Config::new(8, 8));
+
+        session.eval("a = voronoi(8)");
+        let response = session.eval("b = a -> invrt");
+        assert!(response.starts_with("<input>:1:10: error: unknown name 'invrt'"));
+    }
+
+    #[test]
+    fn tuples_are_saved_numbered() {
+        let dir = std::env::temp_dir().join(format!("cobble-repl-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut session = Session::new(Config::new(8, 8));
+        session.eval("a = (random, voronoi)");
+
+        let path = dir.join("a.png");
+        let response = session.eval(&format!(":show a {}", path.display()));
+        assert_eq!(
+            format!(
+                "saved 'a' to {}, {}\n",
+                dir.join("a-1.png").display(),
+                dir.join("a-2.png").display()
+            ),
+            response
+        );
+        assert!(dir.join("a-1.png").exists() && dir.join("a-2.png").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,62 @@
+use super::Pixel;
+
+/// How the colour channels of a pixel relate to the amount of light they stand for. Textures always hold linear values,
+/// so that blending and arithmetic behave like light does. Other spaces only appear at the edges, where files are read
+/// and written.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Space {
+    /// Values proportional to the amount of light.
+    #[default]
+    Linear,
+
+    /// Values encoded with the sRGB transfer function, as most 8-bit and 16-bit images are, which spends more of
+    /// their range on dark colours.
+    Srgb,
+}
+
+impl Pixel {
+    /// Convert the colour channels of a linear pixel into the given space, leaving alpha as it is.
+    pub fn encode(&self, space: Space) -> Self {
+        match space {
+            Space::Linear => *self,
+            Space::Srgb => self.map_rgb(linear_to_srgb),
+        }
+    }
+
+    /// Convert the colour channels of a pixel in the given space into linear values, leaving alpha as it is.
+    pub fn decode(&self, space: Space) -> Self {
+        match space {
+            Space::Linear => *self,
+            Space::Srgb => self.map_rgb(srgb_to_linear),
+        }
+    }
+
+    fn map_rgb(&self, f: fn(f64) -> f64) -> Self {
+        let [r, g, b, a] = self.0;
+        Self([f(r), f(g), f(b), a])
+    }
+}
+
+/// Apply the sRGB transfer function to a linear value. Values outside of `[0, 1]` are extended along the same curve,
+/// mirrored for negative values.
+fn linear_to_srgb(value: f64) -> f64 {
+    let magnitude = value.abs();
+    let encoded = if magnitude <= 0.0031308 {
+        magnitude * 12.92
+    } else {
+        1.055 * magnitude.powf(1.0 / 2.4) - 0.055
+    };
+    encoded.copysign(value)
+}
+
+/// Undo the sRGB transfer function, giving a linear value. Values outside of `[0, 1]` are extended along the same
+/// curve, mirrored for negative values.
+fn srgb_to_linear(value: f64) -> f64 {
+    let magnitude = value.abs();
+    let decoded = if magnitude <= 0.04045 {
+        magnitude / 12.92
+    } else {
+        ((magnitude + 0.055) / 1.055).powf(2.4)
+    };
+    decoded.copysign(value)
+}
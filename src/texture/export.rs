@@ -0,0 +1,257 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use image::codecs::hdr::HdrEncoder;
+use image::error::{EncodingError, ImageFormatHint};
+use image::{ImageBuffer, ImageError, ImageFormat, ImageResult, Luma, Rgb, Rgba};
+use tiff::encoder::{colortype, TiffEncoder};
+
+use super::colour::Space;
+use super::raw::{Depth, Raw};
+use super::{Pixel, Texture};
+
+/// A file format textures can be saved in.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Format {
+    /// An 8-bit RGBA PNG.
+    Png8,
+
+    /// A 16-bit RGBA PNG.
+    Png16,
+
+    /// A 16-bit grayscale PNG of the luminance of each pixel, such as for heightmaps. Unlike the other PNG formats, it
+    /// is linear by default.
+    Gray16,
+
+    /// A 32-bit float RGBA TIFF.
+    Tiff,
+
+    /// A Radiance HDR image. It has no alpha channel, and cannot store negative values, which are clamped to zero.
+    Hdr,
+
+    /// A 32-bit float RGBA OpenEXR image, with premultiplied alpha as OpenEXR expects.
+    Exr,
+
+    /// A headerless little-endian 16-bit heightmap of the luminance of each pixel.
+    R16,
+
+    /// A headerless little-endian 32-bit float heightmap of the luminance of each pixel.
+    R32,
+}
+
+impl Format {
+    /// Get the format usually meant by a file extension, or `None` if it is not one textures can be saved in.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => Some(Self::Png8),
+            "tif" | "tiff" => Some(Self::Tiff),
+            "hdr" => Some(Self::Hdr),
+            "exr" => Some(Self::Exr),
+            "r16" | "raw" => Some(Self::R16),
+            "r32" => Some(Self::R32),
+            _ => None,
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<Self> {
+        Self::from_extension(path.extension()?.to_str()?)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Png8 | Self::Png16 | Self::Gray16 => "png",
+            Self::Tiff => "tiff",
+            Self::Hdr => "hdr",
+            Self::Exr => "exr",
+            Self::R16 => "r16",
+            Self::R32 => "r32",
+        }
+    }
+
+    /// Whether the format stores floats, rather than integers which only cover the `[0, 1]` range.
+    pub fn is_float(&self) -> bool {
+        matches!(self, Self::Tiff | Self::Hdr | Self::Exr | Self::R32)
+    }
+
+    /// Get the space the format is saved in unless another is chosen: sRGB for colour PNGs, which are usually viewed
+    /// as they are, and linear for everything else, which is either data or expected to be linear.
+    pub fn default_space(&self) -> Space {
+        match self {
+            Self::Png8 | Self::Png16 => Space::Srgb,
+            _ => Space::Linear,
+        }
+    }
+
+    /// Get the levels the format is saved with unless others are chosen: clamped for integer images, and kept as they
+    /// are for float images and for heightmaps, which remap values themselves.
+    pub fn default_levels(&self) -> Levels {
+        match self {
+            Self::Png8 | Self::Png16 | Self::Gray16 => Levels::Clamp,
+            _ => Levels::Keep,
+        }
+    }
+}
+
+/// How the values of a texture are mapped to the values of a file.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Levels {
+    /// Clamp every channel into the `[0, 1]` range.
+    Clamp,
+
+    /// Stretch the colour channels so the lowest finite value becomes 0 and the highest becomes 1, and clamp alpha.
+    Normalize,
+
+    /// Leave values as they are. Integer formats still saturate values outside of the `[0, 1]` range.
+    Keep,
+}
+
+impl Texture {
+    /// Save the texture to a file in the given format and its default space, mapping its values with the given levels.
+    pub fn save(&self, path: impl AsRef<Path>, format: Format, levels: Levels) -> ImageResult<()> {
+        self.save_as(path, format, levels, format.default_space())
+    }
+
+    /// Save the texture to a file in the given format, mapping its linear values with the given levels and then
+    /// encoding them in the given space. Raw heightmaps are always linear.
+    pub fn save_as(
+        &self,
+        path: impl AsRef<Path>,
+        format: Format,
+        levels: Levels,
+        space: Space,
+    ) -> ImageResult<()> {
+        let path = path.as_ref();
+        let levelled = self.levelled(levels);
+        let pixels: Vec<_> = levelled.iter().map(|pixel| pixel.encode(space)).collect();
+        let (width, height) = (self.width as u32, self.height as u32);
+
+        match format {
+            Format::Png8 => {
+                let data = pixels
+                    .iter()
+                    .flat_map(|pixel| pixel.as_rgba8_sat())
+                    .collect();
+                let image: ImageBuffer<Rgba<u8>, _> = buffer(width, height, data);
+                image.save_with_format(path, ImageFormat::Png)
+            }
+
+            Format::Png16 => {
+                let data = pixels
+                    .iter()
+                    .flat_map(|pixel| pixel.0.map(to_u16))
+                    .collect();
+                let image: ImageBuffer<Rgba<u16>, _> = buffer(width, height, data);
+                image.save_with_format(path, ImageFormat::Png)
+            }
+
+            Format::Gray16 => {
+                let data = levelled
+                    .iter()
+                    .map(|pixel| to_u16(Pixel::splat_rgb(pixel.luminance()).encode(space).0[0]))
+                    .collect();
+                let image: ImageBuffer<Luma<u16>, _> = buffer(width, height, data);
+                image.save_with_format(path, ImageFormat::Png)
+            }
+
+            Format::Tiff => {
+                let data: Vec<_> = pixels
+                    .iter()
+                    .flat_map(|pixel| pixel.0.map(|c| c as f32))
+                    .collect();
+                let tiff_error = |error| {
+                    ImageError::Encoding(EncodingError::new(
+                        ImageFormatHint::Exact(ImageFormat::Tiff),
+                        error,
+                    ))
+                };
+
+                let mut encoder =
+                    TiffEncoder::new(BufWriter::new(File::create(path)?)).map_err(tiff_error)?;
+                encoder
+                    .write_image::<colortype::RGBA32Float>(width, height, &data)
+                    .map_err(tiff_error)
+            }
+
+            Format::Hdr => {
+                let data: Vec<_> = pixels
+                    .iter()
+                    .map(|pixel| {
+                        let [r, g, b] = pixel.as_rgb().map(|c| c.max(0.0) as f32);
+                        Rgb([r, g, b])
+                    })
+                    .collect();
+
+                let encoder = HdrEncoder::new(BufWriter::new(File::create(path)?));
+                encoder.encode(&data, self.width, self.height)
+            }
+
+            Format::Exr => {
+                let data = pixels
+                    .iter()
+                    .flat_map(|pixel| pixel.premultiply().0.map(|c| c as f32))
+                    .collect();
+                let image: ImageBuffer<Rgba<f32>, _> = buffer(width, height, data);
+                image.save_with_format(path, ImageFormat::OpenExr)
+            }
+
+            Format::R16 => Ok(self.save_raw(path, &Raw::new(Depth::R16), levels)?),
+            Format::R32 => Ok(self.save_raw(path, &Raw::new(Depth::R32), levels)?),
+        }
+    }
+
+    /// Save a raw heightmap of the texture, mapping its values with the given levels before the heightmap remaps them.
+    pub fn save_raw(&self, path: impl AsRef<Path>, raw: &Raw, levels: Levels) -> io::Result<()> {
+        let levelled = self.levelled(levels);
+        let mut texture =
+            Texture::with_storage(self.width, self.height, self.channels, self.precision());
+        texture.fill_absolute(|x, y| levelled[x + y * self.width]);
+        raw.save(&texture, path)
+    }
+
+    /// Get the pixels of the texture, mapped with the given levels.
+    pub(super) fn levelled(&self, levels: Levels) -> Vec<Pixel> {
+        match levels {
+            Levels::Clamp => self
+                .pixels()
+                .map(|pixel| Pixel(pixel.0.map(|c| c.clamp(0.0, 1.0))))
+                .collect(),
+
+            Levels::Normalize => {
+                let stats = self.stats();
+                let min = stats.min.0[..3]
+                    .iter()
+                    .copied()
+                    .fold(f64::INFINITY, f64::min);
+                let max = stats.max.0[..3]
+                    .iter()
+                    .copied()
+                    .fold(f64::NEG_INFINITY, f64::max);
+
+                let scale = if max > min { 1.0 / (max - min) } else { 0.0 };
+
+                self.pixels()
+                    .map(|pixel| {
+                        let [r, g, b, a] = pixel.0;
+                        let [r, g, b] = [r, g, b].map(|c| ((c - min) * scale).clamp(0.0, 1.0));
+                        Pixel([r, g, b, a.clamp(0.0, 1.0)])
+                    })
+                    .collect()
+            }
+
+            Levels::Keep => self.pixels().collect(),
+        }
+    }
+}
+
+fn buffer<P: image::Pixel>(
+    width: u32,
+    height: u32,
+    data: Vec<P::Subpixel>,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    ImageBuffer::from_vec(width, height, data).expect("buffer matches dimensions")
+}
+
+pub(super) fn to_u16(value: f64) -> u16 {
+    (value.clamp(0.0, 1.0) * u16::MAX as f64).round() as u16
+}
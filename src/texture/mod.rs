@@ -1,20 +1,111 @@
+mod colour;
+mod export;
 mod pixel;
+mod raw;
+mod sample;
+mod stats;
+mod storage;
 
+use image::RgbaImage;
+
+pub use colour::Space;
+pub use export::{Format, Levels};
 pub use pixel::Pixel;
+pub use raw::{Channel, Depth, Endian, Raw};
+pub use sample::Filter;
+pub use stats::{Histogram, Stats};
+pub use storage::{Channels, Precision};
+
+use storage::Buffer;
 
+/// A grid of pixels. Textures store their pixels with some number of channels and at some precision, but are always
+/// read and written a whole [`Pixel`] at a time, so code working on textures does not depend on how they are stored.
+#[derive(Clone, Debug)]
 pub struct Texture {
     width: usize,
     height: usize,
-    buffer: Vec<Pixel>,
+    channels: Channels,
+    buffer: Buffer,
 }
 
 impl Texture {
+    /// Create a transparent black texture, storing RGBA channels at the default precision.
     pub fn new(width: usize, height: usize) -> Self {
+        Self::with_storage(width, height, Channels::default(), Precision::default())
+    }
+
+    /// Create a transparent black texture, or an opaque black one if it stores no alpha, with the given storage.
+    pub fn with_storage(
+        width: usize,
+        height: usize,
+        channels: Channels,
+        precision: Precision,
+    ) -> Self {
         Self {
             width,
             height,
-            buffer: vec![Pixel::ZERO; width * height],
+            channels,
+            buffer: Buffer::new(precision, width * height * channels.count()),
+        }
+    }
+
+    /// Create a texture from its pixels, row by row from the top left, or `None` if the number of pixels does not match
+    /// the dimensions. The pixels are stored as RGBA at the default precision.
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Pixel>) -> Option<Self> {
+        if pixels.len() != width * height {
+            return None;
         }
+
+        let mut texture = Self::new(width, height);
+        texture.buffer.fill(texture.channels, |ndx| pixels[ndx]);
+        Some(texture)
+    }
+
+    /// Store the texture with other channels or at another precision.
+    pub fn convert(&self, channels: Channels, precision: Precision) -> Self {
+        let mut texture = Self::with_storage(self.width, self.height, channels, precision);
+        texture
+            .buffer
+            .fill(channels, |ndx| self.buffer.get(self.channels, ndx));
+        texture
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn channels(&self) -> Channels {
+        self.channels
+    }
+
+    pub fn precision(&self) -> Precision {
+        self.buffer.precision()
+    }
+
+    /// Get the number of bytes the pixels of the texture take.
+    pub fn bytes(&self) -> usize {
+        self.buffer.len() * self.precision().bytes()
+    }
+
+    /// Get the sides of the texture relative to its longer side. Scaling local coordinates by these makes distances
+    /// along either axis comparable, as a local unit along the shorter side covers fewer pixels.
+    pub fn aspect(&self) -> (f64, f64) {
+        let longest = self.width.max(self.height).max(1) as f64;
+        (self.width as f64 / longest, self.height as f64 / longest)
+    }
+
+    /// Convert the texture into an 8-bit sRGB image, saturating each channel.
+    pub fn to_image(&self) -> RgbaImage {
+        let pixels = self
+            .pixels()
+            .flat_map(|pix| pix.encode(Space::Srgb).as_rgba8_sat())
+            .collect();
+        RgbaImage::from_vec(self.width as u32, self.height as u32, pixels)
+            .expect("buffer matches dimensions")
     }
 
     pub fn at_absolute(&self, x: usize, y: usize) -> Pixel {
@@ -22,7 +113,7 @@ impl Texture {
         let y = y % self.height;
 
         let ndx = x + y * self.width;
-        self.buffer[ndx]
+        self.buffer.get(self.channels, ndx)
     }
 
     pub fn at_absolute_wrap(&self, x: isize, y: isize) -> Pixel {
@@ -30,52 +121,66 @@ impl Texture {
         let y = y.rem_euclid(self.height as isize) as usize;
 
         let ndx = x + y * self.width;
-        self.buffer[ndx]
+        self.buffer.get(self.channels, ndx)
     }
 
     pub fn at_local(&self, x: f64, y: f64) -> Pixel {
         let (x, y) = self.to_absolute(x, y);
 
         let ndx = x + y * self.width;
-        self.buffer[ndx]
+        self.buffer.get(self.channels, ndx)
     }
 
+    /// Get every pixel, row by row from the top left.
     pub fn pixels(&self) -> impl Iterator<Item = Pixel> + '_ {
-        self.buffer.iter().copied()
+        (0..self.width * self.height).map(|ndx| self.buffer.get(self.channels, ndx))
     }
 
-    pub fn pixels_mut(&mut self) -> impl Iterator<Item = &mut Pixel> + '_ {
-        self.buffer.iter_mut()
+    /// Set every pixel to the one the function gives for its absolute coordinates, row by row from the top left.
+    pub fn fill_absolute(&mut self, mut f: impl FnMut(usize, usize) -> Pixel) {
+        let Self { width, .. } = *self;
+        self.buffer
+            .fill(self.channels, |ndx| f(ndx % width, ndx / width));
     }
 
-    pub fn pixels_mut_absolute(&mut self) -> impl Iterator<Item = (&mut Pixel, usize, usize)> + '_ {
+    /// Set every pixel to the one the function gives for its local coordinates, row by row from the top left.
+    pub fn fill_local(&mut self, mut f: impl FnMut(f64, f64) -> Pixel) {
         let Self { width, height, .. } = *self;
+        self.buffer.fill(self.channels, |ndx| {
+            let x = (ndx % width) as f64 / width as f64;
+            let y = (ndx / width) as f64 / height as f64;
+            f(x, y)
+        });
+    }
 
-        self.buffer.iter_mut().enumerate().map(move |(ndx, pixel)| {
-            let x = ndx % width;
-            let y = ndx / height;
-            (pixel, x, y)
-        })
+    /// Replace every pixel with the one the function gives for it.
+    pub fn update(&mut self, f: impl FnMut(Pixel) -> Pixel) {
+        self.buffer.update(self.channels, f);
     }
 
-    pub fn pixels_mut_local(&mut self) -> impl Iterator<Item = (&mut Pixel, f64, f64)> + '_ {
-        let Self { width, height, .. } = *self;
+    /// Get every stored channel of every pixel, row by row from the top left.
+    pub(crate) fn samples(&self) -> impl Iterator<Item = f64> + '_ {
+        (0..self.buffer.len()).map(|ndx| self.buffer.sample(ndx))
+    }
 
-        self.buffer.iter_mut().enumerate().map(move |(ndx, pixel)| {
-            let x = (ndx % width) as f64 / width as f64;
-            let y = (ndx / height) as f64 / height as f64;
-            (pixel, x, y)
-        })
+    /// Create a texture from every stored channel of every pixel, or `None` if the number of samples does not match.
+    pub(crate) fn from_samples(
+        width: usize,
+        height: usize,
+        channels: Channels,
+        precision: Precision,
+        samples: impl IntoIterator<Item = f64>,
+    ) -> Option<Self> {
+        let mut texture = Self::with_storage(width, height, channels, precision);
+        texture.buffer.set_samples(samples).then_some(texture)
     }
 
     pub fn to_absolute(&self, x: f64, y: f64) -> (usize, usize) {
-        let w = self.width as f64;
-        let h = self.height as f64;
+        // Wrap after flooring, as wrapping a float can round up to the width itself, such as for tiny negative values.
+        let x = ((x * self.width as f64).floor() as isize).rem_euclid(self.width as isize);
+        let y = ((y * self.height as f64).floor() as isize).rem_euclid(self.height as isize);
 
-        let x = (x * w).rem_euclid(w) as usize;
-        let y = (y * h).rem_euclid(h) as usize;
-
-        (x, y)
+        (x as usize, y as usize)
     }
 
     pub fn to_local(&self, x: usize, y: usize) -> (f64, f64) {
@@ -87,3 +192,535 @@ impl Texture {
         (x, y)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{
+        Channel, Channels, Depth, Endian, Filter, Format, Levels, Pixel, Precision, Raw, Space,
+        Texture,
+    };
+
+    const FILTERS: [Filter; 4] = [
+        Filter::Nearest,
+        Filter::Bilinear,
+        Filter::Bicubic,
+        Filter::Lanczos,
+    ];
+
+    /// A texture whose red channel counts up from zero, row by row.
+    fn ramp(width: usize, height: usize) -> Texture {
+        let pixels = (0..width * height)
+            .map(|ndx| Pixel::from_rgb(ndx as f64, 0.0, 0.0))
+            .collect();
+        Texture::from_pixels(width, height, pixels).unwrap()
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn pixels_are_visited_row_by_row() {
+        for (width, height) in [(5, 2), (2, 5)] {
+            let mut texture = Texture::new(width, height);
+
+            let mut absolute = Vec::new();
+            texture.fill_absolute(|x, y| {
+                absolute.push((x, y));
+                Pixel::ZERO
+            });
+            let expected: Vec<_> = (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .collect();
+            assert_eq!(expected, absolute);
+
+            let mut local = Vec::new();
+            texture.fill_local(|x, y| {
+                local.push((x, y));
+                Pixel::ZERO
+            });
+            let expected: Vec<_> = expected
+                .iter()
+                .map(|(x, y)| texture.to_local(*x, *y))
+                .collect();
+            assert_eq!(expected, local);
+        }
+    }
+
+    #[test]
+    fn storage_keeps_what_it_can() {
+        let pixel = Pixel::from_rgba(0.3, 0.6, 1.5, 0.5);
+        let texture = Texture::from_pixels(1, 1, vec![pixel]).unwrap();
+
+        let read = |channels, precision| texture.convert(channels, precision).at_absolute(0, 0);
+        assert_eq!(
+            pixel.0.map(|c| c as f32 as f64),
+            read(Channels::Rgba, Precision::F64).0
+        );
+        assert!(read(Channels::Rgba, Precision::F16)
+            .0
+            .iter()
+            .zip(pixel.0)
+            .all(|(a, b)| (a - b).abs() < 1e-3));
+
+        // Integers saturate, and channels which are not stored are taken from red, or are opaque.
+        assert_eq!(1.0, read(Channels::Rgba, Precision::U16).0[2]);
+        let gray = read(Channels::GrayAlpha, Precision::F32);
+        assert_eq!(
+            [0.3, 0.3, 0.3, 0.5],
+            gray.0.map(|c| (c * 1e6).round() / 1e6)
+        );
+        assert_eq!(1.0, read(Channels::Gray, Precision::F32).alpha());
+
+        let bytes = |channels, precision| Texture::with_storage(4, 4, channels, precision).bytes();
+        assert_eq!(256, bytes(Channels::Rgba, Precision::F32));
+        assert_eq!(32, bytes(Channels::Gray, Precision::F16));
+    }
+
+    #[test]
+    fn aspect_is_relative_to_the_longer_side() {
+        assert_eq!((1.0, 0.5), Texture::new(8, 4).aspect());
+        assert_eq!((0.25, 1.0), Texture::new(2, 8).aspect());
+        assert_eq!((1.0, 1.0), Texture::new(3, 3).aspect());
+    }
+
+    #[test]
+    fn filters_give_pixels_at_their_centres() {
+        let texture = ramp(5, 3);
+
+        for filter in FILTERS {
+            for y in 0..3 {
+                for x in 0..5 {
+                    let u = (x as f64 + 0.5) / 5.0;
+                    let v = (y as f64 + 0.5) / 3.0;
+
+                    let sampled = texture.sample(u, v, filter).0[0];
+                    assert!(
+                        close(texture.at_absolute(x, y).0[0], sampled),
+                        "{filter:?} at ({x}, {y})"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn filters_wrap_around_edges() {
+        let texture = ramp(4, 2);
+
+        // Halfway between the last and first pixels of the first row.
+        assert!(close(1.5, texture.sample(0.0, 0.25, Filter::Bilinear).0[0]));
+        assert!(close(1.5, texture.sample(1.0, 0.25, Filter::Bilinear).0[0]));
+
+        // Halfway between the first pixels of the last and first rows.
+        assert!(close(
+            2.0,
+            texture.sample(0.125, 0.0, Filter::Bilinear).0[0]
+        ));
+
+        // Smooth filters see the same neighbourhood from either side of the torus.
+        for filter in FILTERS {
+            let a = texture.sample(-0.1, -0.3, filter);
+            let b = texture.sample(0.9, 0.7, filter);
+            assert!(close(a.0[0], b.0[0]), "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn filters_wrap_points_on_the_edges() {
+        let texture = ramp(3, 3);
+
+        for filter in FILTERS {
+            for (u, v) in [(-1e-17, 0.9), (0.5, -1e-17), (1.0, 1.0), (1.0, 0.0)] {
+                assert!(texture.sample(u, v, filter).0[0].is_finite(), "{filter:?}");
+            }
+        }
+
+        assert_eq!((2, 0), texture.to_absolute(-1e-17, 1.0));
+        assert_eq!((0, 0), texture.to_absolute(1.0, 3.0));
+    }
+
+    #[test]
+    fn filters_keep_flat_textures_flat() {
+        let texture = Texture::from_pixels(3, 2, vec![Pixel::splat_rgb(0.25); 6]).unwrap();
+
+        for filter in FILTERS {
+            for (u, v) in [(0.1, 0.2), (0.55, 0.9), (0.99, 0.01)] {
+                assert!(close(0.25, texture.sample(u, v, filter).0[0]), "{filter:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn resampling_round_trips_smooth_textures() {
+        let mut texture = Texture::new(16, 8);
+        texture.fill_local(|x, y| {
+            let value = (std::f64::consts::TAU * x).sin() * (std::f64::consts::TAU * y).cos();
+            Pixel::splat_rgb(value)
+        });
+
+        // Shrinking back widens the filter to two pixels, which softens the peaks most for the broad bilinear tent.
+        for (filter, tolerance) in [
+            (Filter::Bilinear, 0.15),
+            (Filter::Bicubic, 0.05),
+            (Filter::Lanczos, 0.05),
+        ] {
+            let back = texture.resample(32, 24, filter).resample(16, 8, filter);
+            assert_eq!((16, 8), (back.width(), back.height()));
+
+            let error = texture
+                .pixels()
+                .zip(back.pixels())
+                .map(|(a, b)| (a.0[0] - b.0[0]).abs())
+                .fold(0.0, f64::max);
+            assert!(error < tolerance, "{filter:?} is off by {error}");
+        }
+    }
+
+    #[test]
+    fn shrinking_averages_every_pixel() {
+        // Stripes a pixel wide, which a filter that is not widened would shrink into a single stripe.
+        let mut texture = Texture::new(16, 16);
+        texture.fill_absolute(|x, _| Pixel::splat_rgb((x % 2) as f64));
+
+        for filter in FILTERS {
+            let small = texture.resample(4, 4, filter);
+            for pixel in small.pixels() {
+                assert!(
+                    (pixel.0[0] - 0.5).abs() < 0.05,
+                    "{filter:?} gives {}",
+                    pixel.0[0]
+                );
+            }
+        }
+    }
+
+    /// Get a texture whose red channel runs from -1 to 2 along each row, with a fine step between neighbouring pixels
+    /// that 8-bit images cannot tell apart.
+    fn overshooting() -> Texture {
+        let mut texture = Texture::new(4, 2);
+        texture.fill_absolute(|x, y| {
+            Pixel::from_rgba(x as f64 - 1.0, 0.5 + y as f64 * 0.001, 0.25, 1.0)
+        });
+        texture
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("cobble-{}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn formats_come_from_extensions() {
+        assert_eq!(Some(Format::Png8), Format::from_extension("PNG"));
+        assert_eq!(Some(Format::Tiff), Format::from_extension("tif"));
+        assert_eq!(
+            Some(Format::Exr),
+            Format::from_path("out/height.exr".as_ref())
+        );
+        assert_eq!(None, Format::from_path("height".as_ref()));
+        assert_eq!(Levels::Clamp, Format::Gray16.default_levels());
+        assert_eq!(Levels::Keep, Format::Hdr.default_levels());
+    }
+
+    #[test]
+    fn sixteen_bit_pngs_keep_fine_steps() {
+        let path = temp_path("rgba16.png");
+        overshooting()
+            .save_as(&path, Format::Png16, Levels::Clamp, Space::Linear)
+            .unwrap();
+
+        let image = image::open(&path).unwrap().into_rgba16();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!((4, 2), image.dimensions());
+        assert_eq!([0, 32768, 16384, 65535], image.get_pixel(0, 0).0);
+        assert_eq!(65535, image.get_pixel(3, 0).0[0]);
+        assert_ne!(image.get_pixel(0, 0).0[1], image.get_pixel(0, 1).0[1]);
+    }
+
+    #[test]
+    fn gray_pngs_store_luminance() {
+        let pixels = vec![Pixel::splat_rgba(0.5), Pixel::from_rgb(0.0, 1.0, 0.0)];
+        let texture = Texture::from_pixels(2, 1, pixels).unwrap();
+
+        let path = temp_path("gray16.png");
+        texture.save(&path, Format::Gray16, Levels::Clamp).unwrap();
+
+        let image = image::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(image::ColorType::L16, image.color());
+        let image = image.into_luma16();
+        assert_eq!(32768, image.get_pixel(0, 0).0[0]);
+        assert_eq!(46871, image.get_pixel(1, 0).0[0]);
+    }
+
+    #[test]
+    fn float_formats_keep_values_outside_of_the_unit_range() {
+        let texture = overshooting();
+
+        let path = temp_path("float.exr");
+        texture.save(&path, Format::Exr, Levels::Keep).unwrap();
+        let image = image::open(&path).unwrap().into_rgba32f();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!([-1.0, 0.5, 0.25, 1.0], image.get_pixel(0, 0).0);
+        assert_eq!(2.0, image.get_pixel(3, 0).0[0]);
+
+        let path = temp_path("float.hdr");
+        texture.save(&path, Format::Hdr, Levels::Keep).unwrap();
+        let file = std::io::BufReader::new(std::fs::File::open(&path).unwrap());
+        let data = image::codecs::hdr::HdrDecoder::new(file)
+            .unwrap()
+            .read_image_hdr()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(0.0, data[0].0[0]);
+        assert_eq!(2.0, data[3].0[0]);
+
+        let path = temp_path("float.tiff");
+        texture.save(&path, Format::Tiff, Levels::Keep).unwrap();
+        let mut decoder = tiff::decoder::Decoder::new(std::fs::File::open(&path).unwrap()).unwrap();
+        let data = decoder.read_image().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        match data {
+            tiff::decoder::DecodingResult::F32(data) => {
+                assert_eq!(4 * 4 * 2, data.len());
+                assert_eq!([-1.0, 0.5, 0.25, 1.0], data[..4]);
+                assert_eq!(2.0, data[12]);
+            }
+            _ => panic!("expected floats"),
+        }
+    }
+
+    #[test]
+    fn levels_clamp_or_normalize() {
+        let texture = overshooting();
+
+        let clamped = texture.levelled(Levels::Clamp);
+        assert_eq!([0.0, 0.5, 0.25, 1.0], clamped[0].0);
+        assert_eq!(1.0, clamped[3].0[0]);
+
+        let normalized = texture.levelled(Levels::Normalize);
+        assert_eq!(
+            [0.0, 0.5, 1.25 / 3.0, 1.0].map(|c: f64| (c * 1e9).round() / 1e9),
+            normalized[0].0.map(|c| (c * 1e9).round() / 1e9)
+        );
+        assert_eq!(1.0, normalized[3].0[0]);
+        assert_eq!(1.0 / 3.0, normalized[1].0[0]);
+    }
+
+    #[test]
+    fn raw_heightmaps_round_trip() {
+        let texture = overshooting();
+
+        for depth in [Depth::R16, Depth::R32] {
+            for endian in [Endian::Little, Endian::Big] {
+                let raw = Raw::new(depth)
+                    .with_channel(Channel::Red)
+                    .with_endian(endian)
+                    .with_range(-1.0, 2.0)
+                    .unwrap();
+                let back = raw.decode(&raw.encode(&texture), Some(4)).unwrap();
+
+                assert_eq!((4, 2), (back.width(), back.height()));
+                for (a, b) in texture.pixels().zip(back.pixels()) {
+                    assert!((a.0[0] - b.0[0]).abs() < 1e-4, "{depth:?} {endian:?}");
+                    assert_eq!(b.0[0], b.0[2]);
+                    assert_eq!(1.0, b.0[3]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn raw_heightmaps_are_headerless() {
+        let texture =
+            Texture::from_pixels(2, 1, vec![Pixel::splat_rgb(1.0), Pixel::splat_rgb(2.0)]).unwrap();
+
+        assert_eq!(
+            vec![0xff, 0xff, 0xff, 0xff],
+            Raw::new(Depth::R16).encode(&texture)
+        );
+        assert_eq!(
+            vec![0x00, 0x00, 0x80, 0x3f, 0x00, 0x00, 0x00, 0x40],
+            Raw::new(Depth::R32).encode(&texture)
+        );
+        assert_eq!(
+            vec![0x80, 0x00, 0xff, 0xff],
+            Raw::new(Depth::R16)
+                .with_endian(Endian::Big)
+                .with_range(0.0, 2.0)
+                .unwrap()
+                .encode(&texture)
+        );
+    }
+
+    #[test]
+    fn raw_heightmaps_need_a_range() {
+        let raw = Raw::new(Depth::R32);
+
+        assert!(raw.with_range(1.0, 1.0).is_none());
+        assert!(raw.with_range(0.0, f64::INFINITY).is_none());
+        assert!(raw.with_range(f64::NAN, 1.0).is_none());
+        assert!(raw.with_range(1.0, 0.0).is_some());
+    }
+
+    #[test]
+    fn raw_heightmaps_need_whole_rows() {
+        let raw = Raw::new(Depth::R16);
+
+        let square = raw.decode(&[0; 2 * 9], None).unwrap();
+        assert_eq!((3, 3), (square.width(), square.height()));
+
+        assert!(raw.decode(&[0; 2 * 8], None).is_none());
+        assert!(raw.decode(&[0; 2 * 8], Some(3)).is_none());
+        assert!(raw.decode(&[0; 2 * 8 + 1], Some(4)).is_none());
+        assert!(raw.decode(&[], None).is_none());
+        assert_eq!(Some(Format::R16), Format::from_extension("raw"));
+    }
+
+    #[test]
+    fn srgb_round_trips() {
+        for value in [-0.5, 0.0, 0.002, 0.2, 0.5, 1.0, 4.0] {
+            let pixel = Pixel::from_rgba(value, value, value, 0.25);
+            let back = pixel.encode(Space::Srgb).decode(Space::Srgb);
+            assert!((back.0[0] - value).abs() < 1e-12, "{value}");
+            assert_eq!(0.25, back.0[3]);
+        }
+
+        assert_eq!(0.5, Pixel::splat_rgb(0.5).encode(Space::Linear).0[0]);
+        assert!((Pixel::splat_rgb(0.5).encode(Space::Srgb).0[0] - 0.7354).abs() < 1e-4);
+    }
+
+    #[test]
+    fn images_are_saved_as_srgb_unless_they_are_data() {
+        let texture = Texture::from_pixels(1, 1, vec![Pixel::splat_rgb(0.5)]).unwrap();
+        assert_eq!([188, 188, 188, 255], texture.to_image().get_pixel(0, 0).0);
+
+        let path = temp_path("srgb.png");
+        texture.save(&path, Format::Png16, Levels::Clamp).unwrap();
+        let colour = image::open(&path).unwrap().into_rgba16();
+        texture.save(&path, Format::Gray16, Levels::Clamp).unwrap();
+        let gray = image::open(&path).unwrap().into_luma16();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(48192, colour.get_pixel(0, 0).0[0]);
+        assert_eq!(32768, gray.get_pixel(0, 0).0[0]);
+    }
+
+    #[test]
+    fn premultiplying_round_trips() {
+        let pixel = Pixel::from_rgba(0.8, 0.4, 0.2, 0.5);
+        assert_eq!([0.4, 0.2, 0.1, 0.5], pixel.premultiply().0);
+        assert_eq!(pixel.0, pixel.premultiply().unpremultiply().0);
+        assert_eq!(
+            Pixel::ZERO.0,
+            Pixel::from_rgba(1.0, 1.0, 1.0, 0.0)
+                .premultiply()
+                .unpremultiply()
+                .0
+        );
+    }
+
+    #[test]
+    fn pixels_composite_over_each_other() {
+        let red = Pixel::from_rgba(1.0, 0.0, 0.0, 0.5);
+        let blue = Pixel::from_rgb(0.0, 0.0, 1.0);
+
+        assert_eq!([0.5, 0.0, 0.5, 1.0], red.over(blue).0);
+        assert_eq!(blue.0, Pixel::ZERO.over(blue).0);
+        assert_eq!(red.0, red.over(Pixel::ZERO).0);
+
+        let [r, g, b, a] = red.over(red).0;
+        assert_eq!([1.0, 0.0, 0.0, 0.75], [r, g, b, a]);
+    }
+
+    #[test]
+    fn pixel_arithmetic_treats_alpha_like_colour() {
+        let a = Pixel::from_rgba(0.2, 0.4, 0.6, 1.0);
+        let b = Pixel::from_rgba(0.6, 0.4, 0.2, 0.0);
+
+        assert_eq!(0.5, (0.5 * a + 0.5 * b).alpha());
+        assert_eq!(1.0, (a - b).alpha());
+        assert_eq!(1.0, a.powf(2.0).alpha());
+        assert_eq!(0.0, b.sqrt().alpha());
+    }
+
+    #[test]
+    fn filters_do_not_bleed_transparent_colours() {
+        let texture = Texture::from_pixels(
+            2,
+            1,
+            vec![
+                Pixel::from_rgb(1.0, 0.0, 0.0),
+                Pixel::from_rgba(0.0, 1.0, 0.0, 0.0),
+            ],
+        )
+        .unwrap();
+
+        for filter in [Filter::Bilinear, Filter::Bicubic, Filter::Lanczos] {
+            let between = texture.sample(0.5, 0.5, filter);
+            assert!(close(1.0, between.0[0]), "{filter:?}");
+            assert!(close(0.0, between.0[1]), "{filter:?}");
+            assert!(close(0.5, between.alpha()), "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn stats_skip_non_finite_values() {
+        let mut texture = ramp(4, 1);
+        texture.update(|pixel| match pixel.0[0] {
+            2.0 => Pixel::from_rgb(f64::NAN, 0.0, 0.0),
+            _ => pixel,
+        });
+
+        let stats = texture.stats();
+        assert_eq!([1, 0, 0, 0], stats.non_finite);
+        assert_eq!(0.0, stats.min.0[0]);
+        assert_eq!(3.0, stats.max.0[0]);
+        assert!(close(4.0 / 3.0, stats.mean.0[0]));
+        assert!(close(14.0 / 9.0, stats.variance.0[0]));
+        assert_eq!(0.0, stats.variance.0[1]);
+        assert_eq!(stats.min.0, texture.min().0);
+        assert_eq!(stats.max.0, texture.max().0);
+
+        let empty = Texture::new(0, 0).stats();
+        assert_eq!(f64::INFINITY, empty.min.0[0]);
+        assert!(empty.mean.0[0].is_nan());
+    }
+
+    #[test]
+    fn histograms_cover_every_channel() {
+        let histogram = ramp(4, 2).histogram(4);
+        assert_eq!([0.0, -0.5, -0.5, 0.5], histogram.min);
+        assert_eq!([7.0, 0.5, 0.5, 1.5], histogram.max);
+        assert_eq!(vec![2, 2, 2, 2], histogram.counts[0]);
+        assert_eq!(vec![0, 0, 8, 0], histogram.counts[1]);
+        assert_eq!(vec![0, 0, 8, 0], histogram.counts[3]);
+        assert_eq!((1.75, 3.5), histogram.bin_range(0, 1));
+
+        let histogram = ramp(4, 2).histogram_in(2, 1.0, 4.0);
+        assert_eq!(vec![2, 2], histogram.counts[0]);
+        assert_eq!([1, 8, 8, 0], histogram.below);
+        assert_eq!([3, 0, 0, 0], histogram.above);
+
+        let flat = Texture::new(2, 2).histogram(3);
+        assert_eq!((-0.5, 0.5), (flat.min[0], flat.max[0]));
+        assert_eq!(vec![0, 4, 0], flat.counts[0]);
+    }
+
+    #[test]
+    fn histograms_range_each_channel_alone() {
+        let pixels = (0..4)
+            .map(|n| Pixel::from_rgba(n as f64 * 100.0, n as f64 / 4.0, 0.0, 1.0))
+            .collect();
+        let histogram = Texture::from_pixels(2, 2, pixels).unwrap().histogram(4);
+
+        assert_eq!((0.0, 300.0), (histogram.min[0], histogram.max[0]));
+        assert_eq!((0.0, 0.75), (histogram.min[1], histogram.max[1]));
+        assert_eq!(vec![1, 1, 1, 1], histogram.counts[0]);
+        assert_eq!(vec![1, 1, 1, 1], histogram.counts[1]);
+    }
+}
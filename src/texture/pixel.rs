@@ -1,5 +1,12 @@
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Sub, SubAssign};
 
+/// A colour with straight alpha: the colour channels hold the colour of whatever covers the pixel, and alpha how much
+/// of the pixel it covers, from 0 to 1. Textures always hold straight pixels, as files do.
+///
+/// Arithmetic on pixels treats all four channels alike, without clamping, so that weighted sums and interpolations
+/// also interpolate alpha. Mixing pixels of different coverage by weight is only correct for premultiplied pixels,
+/// where the colour channels are scaled by alpha, so filters and blends convert to them first with
+/// [`Pixel::premultiply`] and back with [`Pixel::unpremultiply`].
 #[derive(Clone, Copy, Debug, Default)]
 pub struct Pixel(pub [f64; 4]);
 
@@ -55,14 +62,54 @@ impl Pixel {
         ]
     }
 
+    pub fn alpha(&self) -> f64 {
+        self.0[3]
+    }
+
+    pub fn with_alpha(self, alpha: f64) -> Self {
+        let [r, g, b, _] = self.0;
+        Self([r, g, b, alpha])
+    }
+
+    /// Scale the colour channels of a straight pixel by its alpha.
+    pub fn premultiply(&self) -> Self {
+        let [r, g, b, a] = self.0;
+        Self([r * a, g * a, b * a, a])
+    }
+
+    /// Divide the colour channels of a premultiplied pixel by its alpha. Fully transparent pixels have no colour, and
+    /// become transparent black.
+    pub fn unpremultiply(&self) -> Self {
+        let [r, g, b, a] = self.0;
+        if a == 0.0 {
+            return Self::ZERO;
+        }
+
+        Self([r / a, g / a, b / a, a])
+    }
+
+    /// Composite the pixel over another, as though it were painted on top of it.
+    pub fn over(&self, below: Pixel) -> Self {
+        let (above, below) = (self.premultiply(), below.premultiply());
+        (above + (1.0 - above.alpha()) * below).unpremultiply()
+    }
+
+    /// Get the Rec. 709 luminance of the colour channels.
+    pub fn luminance(&self) -> f64 {
+        let [r, g, b] = self.as_rgb();
+        0.2126 * r + 0.7152 * g + 0.0722 * b
+    }
+
+    /// Raise the colour channels to a power, leaving alpha as it is.
     pub fn powf(&self, n: f64) -> Self {
         let [r, g, b, a] = self.0;
-        Self([r.powf(n), g.powf(n), b.powf(n), a.powf(n).clamp(0.0, 1.0)])
+        Self([r.powf(n), g.powf(n), b.powf(n), a])
     }
 
+    /// Take the square root of the colour channels, leaving alpha as it is.
     pub fn sqrt(&self) -> Self {
         let [r, g, b, a] = self.0;
-        Self([r.sqrt(), g.sqrt(), b.sqrt(), a.sqrt().clamp(0.0, 1.0)])
+        Self([r.sqrt(), g.sqrt(), b.sqrt(), a])
     }
 }
 
@@ -73,7 +120,7 @@ impl Add for Pixel {
         let [ar, ag, ab, aa] = self.0;
         let [br, bg, bb, ba] = rhs.0;
 
-        Self([ar + br, ag + bg, ab + bb, (aa + ba).clamp(0.0, 1.0)])
+        Self([ar + br, ag + bg, ab + bb, aa + ba])
     }
 }
 
@@ -90,7 +137,7 @@ impl Sub for Pixel {
         let [ar, ag, ab, aa] = self.0;
         let [br, bg, bb, ba] = rhs.0;
 
-        Self([ar - br, ag - bg, ab - bb, aa.max(ba)])
+        Self([ar - br, ag - bg, ab - bb, aa - ba])
     }
 }
 
@@ -106,7 +153,7 @@ impl Mul for Pixel {
     fn mul(self, rhs: Self) -> Self::Output {
         let [ar, ag, ab, aa] = self.0;
         let [br, bg, bb, ba] = rhs.0;
-        Self([ar * br, ag * bg, ab * bb, (aa * ba).clamp(0.0, 1.0)])
+        Self([ar * br, ag * bg, ab * bb, aa * ba])
     }
 }
 
@@ -115,7 +162,7 @@ impl Mul<f64> for Pixel {
 
     fn mul(self, rhs: f64) -> Self::Output {
         let [r, g, b, a] = self.0;
-        Self([r * rhs, g * rhs, b * rhs, a])
+        Self([r * rhs, g * rhs, b * rhs, a * rhs])
     }
 }
 
@@ -139,7 +186,7 @@ impl Div for Pixel {
     fn div(self, rhs: Self) -> Self::Output {
         let [ar, ag, ab, aa] = self.0;
         let [br, bg, bb, ba] = rhs.0;
-        Self([ar / br, ag / bg, ab / bb, (aa / ba).clamp(0.0, 1.0)])
+        Self([ar / br, ag / bg, ab / bb, aa / ba])
     }
 }
 
@@ -148,7 +195,7 @@ impl Div<f64> for Pixel {
 
     fn div(self, rhs: f64) -> Self::Output {
         let [r, g, b, a] = self.0;
-        Self([r / rhs, g / rhs, b / rhs, a])
+        Self([r / rhs, g / rhs, b / rhs, a / rhs])
     }
 }
 
@@ -157,7 +204,7 @@ impl Div<Pixel> for f64 {
 
     fn div(self, rhs: Pixel) -> Self::Output {
         let [r, g, b, a] = rhs.0;
-        Pixel([self / r, self / g, self / b, a])
+        Pixel([self / r, self / g, self / b, self / a])
     }
 }
 
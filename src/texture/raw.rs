@@ -0,0 +1,186 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::export::to_u16;
+use super::{Channels, Pixel, Precision, Texture};
+
+/// How each sample of a raw heightmap is stored.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Depth {
+    /// An unsigned 16-bit integer, where the whole range of the integer covers the range of heights.
+    R16,
+
+    /// A 32-bit float.
+    R32,
+}
+
+impl Depth {
+    fn bytes(&self) -> usize {
+        match self {
+            Depth::R16 => 2,
+            Depth::R32 => 4,
+        }
+    }
+}
+
+/// The part of each pixel a raw heightmap stores.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+    Alpha,
+
+    /// The Rec. 709 luminance of the colour channels.
+    #[default]
+    Luminance,
+}
+
+/// The order of the bytes within each sample of a raw heightmap.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+/// A headerless heightmap of a single channel, stored row by row from the top left, as read by terrain tools.
+///
+/// Heights are remapped from a range to `[0, 1]` as they are written, and back as they are read. 16-bit heightmaps
+/// saturate heights outside of the range, while 32-bit heightmaps keep them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Raw {
+    depth: Depth,
+    channel: Channel,
+    endian: Endian,
+    range: (f64, f64),
+}
+
+impl Raw {
+    pub fn new(depth: Depth) -> Self {
+        Self {
+            depth,
+            channel: Channel::default(),
+            endian: Endian::default(),
+            range: (0.0, 1.0),
+        }
+    }
+
+    pub fn with_channel(self, channel: Channel) -> Self {
+        Self { channel, ..self }
+    }
+
+    pub fn with_endian(self, endian: Endian) -> Self {
+        Self { endian, ..self }
+    }
+
+    /// Map heights from `min` to `max` onto the whole range of the heightmap, rather than from 0 to 1. Heights are
+    /// divided by the width of the range, so gives `None` if the range is empty or either end is not finite.
+    pub fn with_range(self, min: f64, max: f64) -> Option<Self> {
+        if !min.is_finite() || !max.is_finite() || min == max {
+            return None;
+        }
+
+        Some(Self {
+            range: (min, max),
+            ..self
+        })
+    }
+
+    /// Get the bytes of a heightmap of the texture.
+    pub fn encode(&self, texture: &Texture) -> Vec<u8> {
+        let (min, max) = self.range;
+        let mut bytes = Vec::with_capacity(texture.width() * texture.height() * self.depth.bytes());
+
+        for pixel in texture.pixels() {
+            let height = match self.channel {
+                Channel::Red => pixel.0[0],
+                Channel::Green => pixel.0[1],
+                Channel::Blue => pixel.0[2],
+                Channel::Alpha => pixel.0[3],
+                Channel::Luminance => pixel.luminance(),
+            };
+            let t = (height - min) / (max - min);
+
+            match (self.depth, self.endian) {
+                (Depth::R16, Endian::Little) => bytes.extend(to_u16(t).to_le_bytes()),
+                (Depth::R16, Endian::Big) => bytes.extend(to_u16(t).to_be_bytes()),
+                (Depth::R32, Endian::Little) => bytes.extend((t as f32).to_le_bytes()),
+                (Depth::R32, Endian::Big) => bytes.extend((t as f32).to_be_bytes()),
+            }
+        }
+
+        bytes
+    }
+
+    /// Read a heightmap into an opaque gray texture. Without a width, the heightmap must be square.
+    /// Gives `None` if the bytes do not make up a whole number of rows.
+    pub fn decode(&self, bytes: &[u8], width: Option<usize>) -> Option<Texture> {
+        if !bytes.len().is_multiple_of(self.depth.bytes()) {
+            return None;
+        }
+
+        let len = bytes.len() / self.depth.bytes();
+        let width = match width {
+            Some(width) => width,
+            None => {
+                let side = (len as f64).sqrt().round() as usize;
+                (side * side == len).then_some(side)?
+            }
+        };
+
+        if width == 0 || len == 0 || !len.is_multiple_of(width) {
+            return None;
+        }
+
+        let (min, max) = self.range;
+        let heights: Vec<_> = bytes
+            .chunks_exact(self.depth.bytes())
+            .map(|chunk| {
+                let t = match (self.depth, self.endian) {
+                    (Depth::R16, Endian::Little) => {
+                        u16::from_le_bytes([chunk[0], chunk[1]]) as f64 / u16::MAX as f64
+                    }
+                    (Depth::R16, Endian::Big) => {
+                        u16::from_be_bytes([chunk[0], chunk[1]]) as f64 / u16::MAX as f64
+                    }
+                    (Depth::R32, Endian::Little) => {
+                        f32::from_le_bytes(chunk.try_into().unwrap()) as f64
+                    }
+                    (Depth::R32, Endian::Big) => {
+                        f32::from_be_bytes(chunk.try_into().unwrap()) as f64
+                    }
+                };
+
+                min + t * (max - min)
+            })
+            .collect();
+
+        let mut texture =
+            Texture::with_storage(width, len / width, Channels::Gray, Precision::default());
+        texture.fill_absolute(|x, y| Pixel::splat_rgb(heights[x + y * width]));
+        Some(texture)
+    }
+
+    pub fn save(&self, texture: &Texture, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.encode(texture))
+    }
+
+    /// Read a heightmap from a file. Without a width, the heightmap must be square.
+    pub fn load(&self, path: impl AsRef<Path>, width: Option<usize>) -> io::Result<Texture> {
+        let bytes = fs::read(path)?;
+        self.decode(&bytes, width).ok_or_else(|| {
+            let shape = match width {
+                Some(width) => format!("rows of {width}"),
+                None => "a square".into(),
+            };
+            let message = format!(
+                "{} bytes cannot be {shape} of {:?} heights",
+                bytes.len(),
+                self.depth
+            );
+            io::Error::new(io::ErrorKind::InvalidData, message)
+        })
+    }
+}
@@ -0,0 +1,163 @@
+use std::f64::consts::PI;
+
+use super::{Pixel, Texture};
+
+/// How a texture is read at points between the centres of its pixels.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Filter {
+    /// The pixel the point falls within.
+    #[default]
+    Nearest,
+
+    /// A linear blend of the 2×2 closest pixels.
+    Bilinear,
+
+    /// A Catmull-Rom spline through the 4×4 closest pixels. Sharper than bilinear filtering, but may overshoot the
+    /// range of its inputs next to hard edges.
+    Bicubic,
+
+    /// A three-lobed windowed sinc over the 6×6 closest pixels. Keeps the most detail, but rings the most next to hard
+    /// edges.
+    Lanczos,
+}
+
+impl Filter {
+    /// Get the number of pixels on either side of a point which the filter reads from.
+    fn radius(&self) -> isize {
+        match self {
+            Filter::Nearest => 0,
+            Filter::Bilinear => 1,
+            Filter::Bicubic => 2,
+            Filter::Lanczos => 3,
+        }
+    }
+
+    /// Get the distance from a point beyond which the filter gives no weight, in pixels.
+    fn support(&self) -> f64 {
+        match self {
+            Filter::Nearest => 0.5,
+            _ => self.radius() as f64,
+        }
+    }
+
+    /// Get the weight of a pixel whose centre is the given number of pixels away from a point.
+    fn weight(&self, dist: f64) -> f64 {
+        let d = dist.abs();
+
+        match self {
+            Filter::Nearest => f64::from(d < 0.5),
+            Filter::Bilinear => (1.0 - d).max(0.0),
+
+            Filter::Bicubic if d < 1.0 => 1.5 * d * d * d - 2.5 * d * d + 1.0,
+            Filter::Bicubic if d < 2.0 => -0.5 * d * d * d + 2.5 * d * d - 4.0 * d + 2.0,
+            Filter::Bicubic => 0.0,
+
+            Filter::Lanczos if d == 0.0 => 1.0,
+            Filter::Lanczos if d < 3.0 => {
+                let x = PI * d;
+                3.0 * x.sin() * (x / 3.0).sin() / (x * x)
+            }
+            Filter::Lanczos => 0.0,
+        }
+    }
+}
+
+impl Texture {
+    /// Read the texture at a point in local coordinates, wrapping around its edges in both directions. The centre of
+    /// the pixel at `(x, y)` is at `((x + 0.5) / width, (y + 0.5) / height)`, where every filter gives that pixel
+    /// exactly. Pixels are filtered premultiplied, so transparent pixels do not bleed their colour into their
+    /// neighbours.
+    pub fn sample(&self, u: f64, v: f64, filter: Filter) -> Pixel {
+        if filter == Filter::Nearest {
+            return self.at_local(u, v);
+        }
+
+        let x = u * self.width as f64 - 0.5;
+        let y = v * self.height as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+
+        let taps = 1 - filter.radius()..=filter.radius();
+
+        let mut sum = Pixel::ZERO;
+        let mut total = 0.0;
+
+        for j in taps.clone() {
+            let wy = filter.weight(y - (y0 + j as f64));
+            if wy == 0.0 {
+                continue;
+            }
+
+            for i in taps.clone() {
+                let wx = filter.weight(x - (x0 + i as f64));
+                let px = x0 as isize + i;
+                let py = y0 as isize + j;
+
+                sum += (wx * wy) * self.at_absolute_wrap(px, py).premultiply();
+                total += wx * wy;
+            }
+        }
+
+        // Only Lanczos weights do not already sum to one, and only bicubic and Lanczos filters can overshoot.
+        let sum = sum / total;
+        sum.with_alpha(sum.alpha().clamp(0.0, 1.0)).unpremultiply()
+    }
+
+    /// Read the texture at a point in local coordinates like [`Texture::sample`], with the filter stretched by the given
+    /// factor along each axis, such that it covers that many pixels for every pixel it would otherwise cover. Factors
+    /// must be at least one.
+    fn sample_wide(&self, u: f64, v: f64, filter: Filter, (sx, sy): (f64, f64)) -> Pixel {
+        if sx <= 1.0 && sy <= 1.0 {
+            return self.sample(u, v, filter);
+        }
+
+        let x = u * self.width as f64 - 0.5;
+        let y = v * self.height as f64 - 0.5;
+        let taps = |at: f64, scale: f64| {
+            let reach = filter.support() * scale;
+            (at - reach).ceil() as isize..=(at + reach).floor() as isize
+        };
+
+        let mut sum = Pixel::ZERO;
+        let mut total = 0.0;
+
+        for py in taps(y, sy) {
+            let wy = filter.weight((y - py as f64) / sy);
+            if wy == 0.0 {
+                continue;
+            }
+
+            for px in taps(x, sx) {
+                let wx = filter.weight((x - px as f64) / sx);
+                sum += (wx * wy) * self.at_absolute_wrap(px, py).premultiply();
+                total += wx * wy;
+            }
+        }
+
+        let sum = sum / total;
+        sum.with_alpha(sum.alpha().clamp(0.0, 1.0)).unpremultiply()
+    }
+
+    /// Resample the texture to a different size, reading each new pixel at its centre. When shrinking, the filter is
+    /// widened by the ratio of the sizes, so every pixel contributes to the pixels it shrinks into rather than some being
+    /// skipped over.
+    pub fn resample(&self, width: usize, height: usize, filter: Filter) -> Texture {
+        let mut target = Texture::with_storage(width, height, self.channels, self.precision());
+        self.resample_into(&mut target, filter);
+        target
+    }
+
+    /// Resample the texture to the size of another, overwriting its pixels but keeping how they are stored.
+    pub fn resample_into(&self, target: &mut Texture, filter: Filter) {
+        let (width, height) = (target.width(), target.height());
+        let scale = (
+            (self.width as f64 / width as f64).max(1.0),
+            (self.height as f64 / height as f64).max(1.0),
+        );
+
+        target.fill_absolute(|x, y| {
+            let u = (x as f64 + 0.5) / width as f64;
+            let v = (y as f64 + 0.5) / height as f64;
+            self.sample_wide(u, v, filter, scale)
+        });
+    }
+}
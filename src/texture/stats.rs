@@ -0,0 +1,191 @@
+use super::{Pixel, Texture};
+
+/// Summary statistics of each channel of a texture, in RGBA order. Values which are not finite are left out of every
+/// statistic and counted instead, so a single NaN does not hide the range of the rest of the texture. Channels with no
+/// finite values have an infinite minimum and maximum, and a NaN mean and variance.
+#[derive(Clone, Copy, Debug)]
+pub struct Stats {
+    pub min: Pixel,
+    pub max: Pixel,
+    pub mean: Pixel,
+
+    /// The population variance, i.e. the mean squared distance from the mean.
+    pub variance: Pixel,
+
+    /// The number of values in each channel which are NaN or infinite.
+    pub non_finite: [usize; 4],
+}
+
+/// How the values of each channel of a texture are spread over a range, split into bins of equal width. Each channel has
+/// a range of its own, so a channel with small values is not squashed into a few bins by one with large values. The
+/// last bin includes the top of the range.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Histogram {
+    /// The bottom and top of the range of each channel, in RGBA order.
+    pub min: [f64; 4],
+    pub max: [f64; 4],
+
+    /// The number of values falling in each bin, for each channel in RGBA order.
+    pub counts: [Vec<usize>; 4],
+
+    /// The number of finite values of each channel below or above its range.
+    pub below: [usize; 4],
+    pub above: [usize; 4],
+}
+
+impl Histogram {
+    /// Get the number of bins.
+    pub fn bins(&self) -> usize {
+        self.counts[0].len()
+    }
+
+    /// Get the range of values of a channel falling in the bin with the given index.
+    pub fn bin_range(&self, ch: usize, bin: usize) -> (f64, f64) {
+        let width = (self.max[ch] - self.min[ch]) / self.bins() as f64;
+        (
+            self.min[ch] + width * bin as f64,
+            self.min[ch] + width * (bin + 1) as f64,
+        )
+    }
+}
+
+impl Texture {
+    /// Compute the minimum, maximum, mean and variance of each channel in a single pass.
+    pub fn stats(&self) -> Stats {
+        let mut min = [f64::INFINITY; 4];
+        let mut max = [f64::NEG_INFINITY; 4];
+        let mut mean = [0.0; 4];
+        let mut m2 = [0.0; 4];
+        let mut counts = [0usize; 4];
+        let mut non_finite = [0; 4];
+
+        for pixel in self.pixels() {
+            for (ch, &value) in pixel.0.iter().enumerate() {
+                if !value.is_finite() {
+                    non_finite[ch] += 1;
+                    continue;
+                }
+
+                min[ch] = min[ch].min(value);
+                max[ch] = max[ch].max(value);
+
+                // Welford's method, which stays accurate where the variance is tiny compared to the mean.
+                counts[ch] += 1;
+                let delta = value - mean[ch];
+                mean[ch] += delta / counts[ch] as f64;
+                m2[ch] += delta * (value - mean[ch]);
+            }
+        }
+
+        let mean = std::array::from_fn(|ch| match counts[ch] {
+            0 => f64::NAN,
+            _ => mean[ch],
+        });
+        let variance = std::array::from_fn(|ch| match counts[ch] {
+            0 => f64::NAN,
+            count => m2[ch] / count as f64,
+        });
+
+        Stats {
+            min: Pixel(min),
+            max: Pixel(max),
+            mean: Pixel(mean),
+            variance: Pixel(variance),
+            non_finite,
+        }
+    }
+
+    /// Get the lowest finite value of each channel. Use [`Texture::stats`] to compute several statistics at once.
+    pub fn min(&self) -> Pixel {
+        self.extremes().0
+    }
+
+    /// Get the highest finite value of each channel.
+    pub fn max(&self) -> Pixel {
+        self.extremes().1
+    }
+
+    /// Get the mean of the finite values of each channel. This computes every statistic, as [`Texture::stats`] does.
+    pub fn mean(&self) -> Pixel {
+        self.stats().mean
+    }
+
+    /// Get the variance of the finite values of each channel. This computes every statistic, as [`Texture::stats`]
+    /// does.
+    pub fn variance(&self) -> Pixel {
+        self.stats().variance
+    }
+
+    /// Get the lowest and highest finite values of each channel, without the cost of the other statistics.
+    fn extremes(&self) -> (Pixel, Pixel) {
+        let mut min = [f64::INFINITY; 4];
+        let mut max = [f64::NEG_INFINITY; 4];
+
+        for pixel in self.pixels() {
+            for (ch, &value) in pixel.0.iter().enumerate() {
+                if value.is_finite() {
+                    min[ch] = min[ch].min(value);
+                    max[ch] = max[ch].max(value);
+                }
+            }
+        }
+
+        (Pixel(min), Pixel(max))
+    }
+
+    /// Count the values of each channel falling in each of the given number of bins, which evenly split the range from
+    /// the lowest to the highest finite value of that channel. Where every value of a channel is the same, its range is
+    /// widened to one around it.
+    pub fn histogram(&self, bins: usize) -> Histogram {
+        let (min, max) = self.extremes();
+        let ranges = std::array::from_fn(|ch| match (min.0[ch], max.0[ch]) {
+            (min, max) if min < max => (min, max),
+            (min, _) if min.is_finite() => (min - 0.5, min + 0.5),
+            _ => (0.0, 1.0),
+        });
+
+        self.histogram_over(bins, ranges)
+    }
+
+    /// Count the values of each channel falling in each of the given number of bins, which evenly split the range from
+    /// `min` to `max` for every channel. Values outside the range are counted separately, and values which are not
+    /// finite are left out.
+    pub fn histogram_in(&self, bins: usize, min: f64, max: f64) -> Histogram {
+        self.histogram_over(bins, [(min, max); 4])
+    }
+
+    fn histogram_over(&self, bins: usize, ranges: [(f64, f64); 4]) -> Histogram {
+        let mut histogram = Histogram {
+            min: ranges.map(|(min, _)| min),
+            max: ranges.map(|(_, max)| max),
+            counts: std::array::from_fn(|_| vec![0; bins]),
+            below: [0; 4],
+            above: [0; 4],
+        };
+
+        if bins == 0 {
+            return histogram;
+        }
+
+        let scales = ranges.map(|(min, max)| bins as f64 / (max - min));
+        for pixel in self.pixels() {
+            for (ch, &value) in pixel.0.iter().enumerate() {
+                let (min, max) = ranges[ch];
+                if !value.is_finite() || max <= min {
+                    continue;
+                }
+
+                if value < min {
+                    histogram.below[ch] += 1;
+                } else if value > max {
+                    histogram.above[ch] += 1;
+                } else {
+                    let bin = (((value - min) * scales[ch]) as usize).min(bins - 1);
+                    histogram.counts[ch][bin] += 1;
+                }
+            }
+        }
+
+        histogram
+    }
+}
@@ -0,0 +1,231 @@
+use half::f16;
+
+use super::Pixel;
+
+/// The channels a texture stores for each pixel. Pixels are always read and written as RGBA, so textures which store
+/// fewer channels give up what they do not store.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Channels {
+    /// A single value, read back as an opaque gray. Writing keeps only the red channel, so this suits textures which
+    /// are always gray and opaque, like masks and heightmaps.
+    Gray,
+
+    /// A gray value and alpha. Writing keeps only the red and alpha channels.
+    GrayAlpha,
+
+    #[default]
+    Rgba,
+}
+
+impl Channels {
+    pub fn count(&self) -> usize {
+        match self {
+            Channels::Gray => 1,
+            Channels::GrayAlpha => 2,
+            Channels::Rgba => 4,
+        }
+    }
+}
+
+/// The type each channel of a texture is stored as. Every type is read and written as `f64`, so this only decides how
+/// much memory textures take, and how much of each value survives being stored.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Precision {
+    F64,
+
+    #[default]
+    F32,
+
+    /// A half-precision float, which keeps about three significant digits.
+    F16,
+
+    /// An unsigned integer covering the `[0, 1]` range, which saturates values outside of it.
+    U16,
+}
+
+impl Precision {
+    /// Get the number of bytes each channel takes.
+    pub fn bytes(&self) -> usize {
+        match self {
+            Precision::F64 => 8,
+            Precision::F32 => 4,
+            Precision::F16 | Precision::U16 => 2,
+        }
+    }
+}
+
+/// A type the channels of a texture can be stored as.
+trait Scalar: Copy {
+    const ZERO: Self;
+
+    fn from_f64(value: f64) -> Self;
+    fn to_f64(self) -> f64;
+}
+
+impl Scalar for f64 {
+    const ZERO: Self = 0.0;
+
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+impl Scalar for f32 {
+    const ZERO: Self = 0.0;
+
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Scalar for f16 {
+    const ZERO: Self = f16::ZERO;
+
+    fn from_f64(value: f64) -> Self {
+        f16::from_f64(value)
+    }
+
+    fn to_f64(self) -> f64 {
+        f16::to_f64(self)
+    }
+}
+
+impl Scalar for u16 {
+    const ZERO: Self = 0;
+
+    fn from_f64(value: f64) -> Self {
+        // NaN saturates to zero when cast.
+        (value.clamp(0.0, 1.0) * u16::MAX as f64).round() as u16
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64 / u16::MAX as f64
+    }
+}
+
+/// The channels of every pixel of a texture, row by row from the top left.
+#[derive(Clone, Debug)]
+pub(super) enum Buffer {
+    F64(Vec<f64>),
+    F32(Vec<f32>),
+    F16(Vec<f16>),
+    U16(Vec<u16>),
+}
+
+/// Run the same code on the samples of a buffer, whatever type they are stored as.
+macro_rules! with_samples {
+    ($buffer:expr, $samples:ident => $body:expr) => {
+        match $buffer {
+            Buffer::F64($samples) => $body,
+            Buffer::F32($samples) => $body,
+            Buffer::F16($samples) => $body,
+            Buffer::U16($samples) => $body,
+        }
+    };
+}
+
+impl Buffer {
+    /// Create a buffer of the given number of samples, all zero.
+    pub fn new(precision: Precision, len: usize) -> Self {
+        match precision {
+            Precision::F64 => Buffer::F64(vec![f64::ZERO; len]),
+            Precision::F32 => Buffer::F32(vec![f32::ZERO; len]),
+            Precision::F16 => Buffer::F16(vec![f16::ZERO; len]),
+            Precision::U16 => Buffer::U16(vec![u16::ZERO; len]),
+        }
+    }
+
+    pub fn precision(&self) -> Precision {
+        match self {
+            Buffer::F64(_) => Precision::F64,
+            Buffer::F32(_) => Precision::F32,
+            Buffer::F16(_) => Precision::F16,
+            Buffer::U16(_) => Precision::U16,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        with_samples!(self, samples => samples.len())
+    }
+
+    /// Get the pixel at the given index.
+    pub fn get(&self, channels: Channels, ndx: usize) -> Pixel {
+        let count = channels.count();
+        with_samples!(self, samples => unpack(channels, &samples[ndx * count..(ndx + 1) * count]))
+    }
+
+    /// Set every pixel to the one the function gives for its index, in order.
+    pub fn fill(&mut self, channels: Channels, mut f: impl FnMut(usize) -> Pixel) {
+        let count = channels.count();
+        with_samples!(self, samples => {
+            for (ndx, pixel) in samples.chunks_exact_mut(count).enumerate() {
+                pack(channels, f(ndx), pixel);
+            }
+        })
+    }
+
+    /// Replace every pixel with the one the function gives for it, in order.
+    pub fn update(&mut self, channels: Channels, mut f: impl FnMut(Pixel) -> Pixel) {
+        let count = channels.count();
+        with_samples!(self, samples => {
+            for pixel in samples.chunks_exact_mut(count) {
+                pack(channels, f(unpack(channels, pixel)), pixel);
+            }
+        })
+    }
+
+    /// Get the sample at the given index as `f64`.
+    pub fn sample(&self, ndx: usize) -> f64 {
+        with_samples!(self, samples => samples[ndx].to_f64())
+    }
+
+    /// Overwrite every sample of the buffer, returning whether there were exactly as many values as samples.
+    pub fn set_samples(&mut self, values: impl IntoIterator<Item = f64>) -> bool {
+        let mut values = values.into_iter();
+        with_samples!(self, samples => {
+            for sample in samples.iter_mut() {
+                match values.next() {
+                    Some(value) => *sample = Scalar::from_f64(value),
+                    None => return false,
+                }
+            }
+        });
+
+        values.next().is_none()
+    }
+}
+
+fn unpack<S: Scalar>(channels: Channels, samples: &[S]) -> Pixel {
+    match channels {
+        Channels::Gray => Pixel::splat_rgb(samples[0].to_f64()),
+        Channels::GrayAlpha => {
+            Pixel::splat_rgb(samples[0].to_f64()).with_alpha(samples[1].to_f64())
+        }
+        Channels::Rgba => Pixel(std::array::from_fn(|ndx| samples[ndx].to_f64())),
+    }
+}
+
+fn pack<S: Scalar>(channels: Channels, pixel: Pixel, samples: &mut [S]) {
+    let [r, _, _, a] = pixel.0;
+
+    match channels {
+        Channels::Gray => samples[0] = S::from_f64(r),
+        Channels::GrayAlpha => {
+            samples[0] = S::from_f64(r);
+            samples[1] = S::from_f64(a);
+        }
+        Channels::Rgba => {
+            for (sample, value) in samples.iter_mut().zip(pixel.0) {
+                *sample = S::from_f64(value);
+            }
+        }
+    }
+}
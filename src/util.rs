@@ -9,7 +9,14 @@ pub enum Metric {
 impl Metric {
     /// Get the smallest wrap-around distance between the two points. Assumes the points are in the `[0, 1]` range.
     pub fn min_dist(&self, a: (f64, f64), b: (f64, f64)) -> f64 {
+        self.min_dist_in(a, b, (1.0, 1.0))
+    }
+
+    /// Get the smallest wrap-around distance between the two points, in local `[0, 1]` coordinates of a rectangle with
+    /// the given sides, such as those of [`Texture::aspect`](crate::Texture::aspect).
+    pub fn min_dist_in(&self, a: (f64, f64), b: (f64, f64), (w, h): (f64, f64)) -> f64 {
         let (dx, dy) = self.min_deltas(a, b);
+        let (dx, dy) = (dx * w, dy * h);
 
         match self {
             Metric::Euclidian => dx.hypot(dy),
@@ -34,3 +41,34 @@ impl Metric {
         (dx, dy)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Metric;
+
+    #[test]
+    fn distances_wrap_and_scale() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+
+        assert!(close(
+            0.2,
+            Metric::Euclidian.min_dist((0.1, 0.5), (0.9, 0.5))
+        ));
+        assert!(close(
+            0.1,
+            Metric::Euclidian.min_dist_in((0.1, 0.5), (0.9, 0.5), (0.5, 1.0))
+        ));
+        assert!(close(
+            0.4,
+            Metric::Manhattan.min_dist_in((0.5, 0.1), (0.5, 0.5), (1.0, 1.0))
+        ));
+        assert!(close(
+            0.2,
+            Metric::Manhattan.min_dist_in((0.5, 0.1), (0.5, 0.5), (1.0, 0.5))
+        ));
+        assert!(close(
+            0.3,
+            Metric::Chebyshev.min_dist_in((0.0, 0.0), (0.3, 0.8), (1.0, 0.5))
+        ));
+    }
+}